@@ -0,0 +1,181 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Implementation details of the `cssparser` crate.
+//! Use the `ascii_case_insensitive_phf_map!` macro of `cssparser` instead of this crate.
+
+extern crate cssparser_phf_shared;
+extern crate proc_macro;
+
+use cssparser_phf_shared::{hash, displace};
+use proc_macro::{TokenStream, TokenTree, Delimiter, Group, Literal, Punct, Spacing};
+
+
+/// Average number of keys per bucket of displacements.
+const LAMBDA: usize = 5;
+
+
+/// Expand to a `PhfMap` expression.
+///
+/// The input is the path of the `cssparser` crate followed by `;`,
+/// then `"key" => value,` entries.
+#[doc(hidden)]
+#[proc_macro]
+pub fn _cssparser_internal_phf_map(input: TokenStream) -> TokenStream {
+    match phf_map(input) {
+        Ok(output) => output,
+        Err(message) => format!("compile_error!({:?})", message).parse().unwrap(),
+    }
+}
+
+
+fn phf_map(input: TokenStream) -> Result<TokenStream, String> {
+    let mut tokens = input.into_iter();
+    let mut crate_path = Vec::new();
+    loop {
+        match tokens.next() {
+            Some(TokenTree::Punct(ref punct)) if punct.as_char() == ';' => break,
+            Some(token) => crate_path.push(token),
+            None => return Err("expected `;` after the crate path".to_owned()),
+        }
+    }
+
+    let mut keys = Vec::new();
+    let mut values = Vec::new();
+    while let Some(token) = tokens.next() {
+        let key = try!(parse_key(token));
+        if keys.contains(&key) {
+            return Err(format!("duplicate key {:?}", key))
+        }
+        match (tokens.next(), tokens.next()) {
+            (Some(TokenTree::Punct(ref equal)), Some(TokenTree::Punct(ref greater)))
+            if equal.as_char() == '=' && greater.as_char() == '>' => {}
+            _ => return Err(format!("expected `=>` after {:?}", key)),
+        }
+        let mut value = Vec::new();
+        loop {
+            match tokens.next() {
+                Some(TokenTree::Punct(ref punct)) if punct.as_char() == ',' => break,
+                Some(token) => value.push(token),
+                None => break,
+            }
+        }
+        if value.is_empty() {
+            return Err(format!("expected a value for {:?}", key))
+        }
+        keys.push(key);
+        values.push(value);
+    }
+    if keys.is_empty() {
+        return Err("expected at least one entry".to_owned())
+    }
+
+    let (hash_key, disps, slots) = generate(&keys);
+
+    let mut entries = Vec::new();
+    for &index in &slots {
+        let mut entry: Vec<TokenTree> = vec![
+            Literal::string(&keys[index]).into(),
+            Punct::new(',', Spacing::Alone).into(),
+        ];
+        entry.extend(values[index].iter().cloned());
+        let entry = Group::new(Delimiter::Parenthesis, entry.into_iter().collect());
+        entries.push(TokenTree::from(entry));
+        entries.push(Punct::new(',', Spacing::Alone).into());
+    }
+    let disps = disps.iter().map(|&(d1, d2)| format!("({}, {})", d1, d2)).collect::<Vec<_>>();
+    let mut fields: TokenStream = format!("key: {}, disps: &[{}], entries: &", hash_key,
+                                          disps.join(", ")).parse().unwrap();
+    let entries = Group::new(Delimiter::Bracket, entries.into_iter().collect());
+    fields.extend(Some(TokenTree::from(entries)));
+
+    let mut output: TokenStream = crate_path.into_iter().collect();
+    output.extend("::PhfMap".parse::<TokenStream>().unwrap());
+    output.extend(Some(TokenTree::from(Group::new(Delimiter::Brace, fields))));
+    Ok(output)
+}
+
+
+/// Return the given string literal in ASCII lower case.
+fn parse_key(token: TokenTree) -> Result<String, String> {
+    let literal = match token {
+        TokenTree::Literal(literal) => literal.to_string(),
+        TokenTree::Group(ref group) if group.delimiter() == Delimiter::None => {
+            let mut tokens = group.stream().into_iter();
+            match (tokens.next(), tokens.next()) {
+                (Some(token), None) => return parse_key(token),
+                _ => return Err(format!("expected a string literal, got {}", group)),
+            }
+        }
+        token => return Err(format!("expected a string literal, got {}", token)),
+    };
+    if literal.len() < 2 || !literal.starts_with('"') || !literal.ends_with('"') ||
+       literal.contains('\\') {
+        return Err(format!("expected a string literal without escapes, got {}", literal))
+    }
+    Ok(literal[1..literal.len() - 1].to_ascii_lowercase())
+}
+
+
+/// Find a hash key and displacements for the given keys, with the CHD algorithm:
+/// http://cmph.sourceforge.net/papers/esa09.pdf
+///
+/// Also return the index in `keys` of the key for each slot of the map.
+fn generate(keys: &[String]) -> (u64, Vec<(u32, u32)>, Vec<usize>) {
+    // Any sequence of hash keys works, this one is deterministic.
+    let mut hash_key = 0x2545_f491_4f6c_dd1d_u64;
+    loop {
+        if let Some((disps, slots)) = try_generate(hash_key, keys) {
+            return (hash_key, disps, slots)
+        }
+        hash_key = hash_key.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+    }
+}
+
+
+fn try_generate(hash_key: u64, keys: &[String]) -> Option<(Vec<(u32, u32)>, Vec<usize>)> {
+    let hashes: Vec<_> = keys.iter().map(|key| hash(hash_key, key)).collect();
+    let buckets_len = (keys.len() + LAMBDA - 1) / LAMBDA;
+    let mut buckets: Vec<(usize, Vec<usize>)> = (0..buckets_len).map(|i| {
+        (i, Vec::new())
+    }).collect();
+    for (index, &(g, _, _)) in hashes.iter().enumerate() {
+        buckets[(g % buckets_len as u32) as usize].1.push(index)
+    }
+    // Place the largest buckets first, while there are many free slots.
+    buckets.sort_by(|a, b| b.1.len().cmp(&a.1.len()));
+
+    let table_len = keys.len();
+    let mut slots = vec![None; table_len];
+    let mut disps = vec![(0, 0); buckets_len];
+    // The generation in which each slot was last tried for the current bucket.
+    let mut tried = vec![0; table_len];
+    let mut generation = 0;
+    let mut bucket_slots = Vec::new();
+    'buckets: for &(bucket, ref indices) in &buckets {
+        for d1 in 0..table_len as u32 {
+            'disps: for d2 in 0..table_len as u32 {
+                generation += 1;
+                bucket_slots.clear();
+                for &index in indices {
+                    let (_, f1, f2) = hashes[index];
+                    let slot = (displace(f1, f2, d1, d2) % table_len as u32) as usize;
+                    if slots[slot].is_some() || tried[slot] == generation {
+                        continue 'disps
+                    }
+                    tried[slot] = generation;
+                    bucket_slots.push(slot);
+                }
+                for (&index, &slot) in indices.iter().zip(&bucket_slots) {
+                    slots[slot] = Some(index)
+                }
+                disps[bucket] = (d1, d2);
+                continue 'buckets
+            }
+        }
+        return None
+    }
+    Some((disps, slots.into_iter().map(Option::unwrap).collect()))
+}
+
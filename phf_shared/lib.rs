@@ -0,0 +1,41 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Implementation details of the `cssparser` and `cssparser-macros` crates.
+//!
+//! The hash functions of `PhfMap` are used both by the procedural macro
+//! that generates maps at compile-time and by lookups at run-time,
+//! so they live in this crate that both depend on.
+
+#![deny(missing_docs)]
+
+
+/// FNV-1a of the input in ASCII lower case, split into three 21-bit values.
+///
+/// The first value selects a bucket of displacements,
+/// the other two are combined with them by `displace`.
+#[inline]
+pub fn hash(key: u64, input: &str) -> (u32, u32, u32) {
+    let mut hash = 0xcbf2_9ce4_8422_2325 ^ key;
+    for byte in input.bytes() {
+        hash ^= byte.to_ascii_lowercase() as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    // Mix like SplitMix64 so that every bit depends on the whole input.
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    hash ^= hash >> 31;
+    let g = (hash >> 42) & 0x1f_ffff;
+    let f1 = (hash >> 21) & 0x1f_ffff;
+    let f2 = hash & 0x1f_ffff;
+    (g as u32, f1 as u32, f2 as u32)
+}
+
+
+/// Combine the last two values of `hash` with the displacements of their bucket.
+/// The result modulo the number of entries is the index of the entry.
+#[inline]
+pub fn displace(f1: u32, f2: u32, d1: u32, d2: u32) -> u32 {
+    d2.wrapping_add(f1.wrapping_mul(d1)).wrapping_add(f2)
+}
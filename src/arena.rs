@@ -0,0 +1,135 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::borrow::Cow::{self, Borrowed};
+use std::cell::RefCell;
+use std::cmp;
+
+use tokenizer::Tokenizer;
+use super::Token;
+
+
+const MINIMUM_CHUNK_CAPACITY: usize = 4096;
+
+
+/// A bump allocator for the string payloads of tokens.
+///
+/// Tokens returned by `TokenArena` methods borrow their strings from the arena
+/// instead of from the input or from separately-allocated `String`s,
+/// so they can outlive the input and they are all freed at once when the arena is dropped.
+/// Strings are copied into large chunks of memory,
+/// so that a whole stylesheet only needs a few allocations
+/// (plus temporary ones for values that contain escapes).
+///
+/// ```{rust,ignore}
+/// let arena = TokenArena::with_capacity(css.len());
+/// let tokens = arena.tokenize(&css);
+/// drop(css);
+/// // `tokens` can still be used here.
+/// ```
+pub struct TokenArena {
+    /// Strings are never reallocated once created (bytes are only ever pushed within capacity)
+    /// so that `&str` slices of their content stay valid while the arena lives.
+    chunks: RefCell<Vec<String>>,
+}
+
+
+impl TokenArena {
+    /// Create a new, empty arena. Memory is allocated when first needed.
+    pub fn new() -> TokenArena {
+        TokenArena {
+            chunks: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Create a new arena that can hold at least `bytes` bytes of strings
+    /// before allocating more memory.
+    ///
+    /// The length of the input is usually enough for all of its tokens.
+    pub fn with_capacity(bytes: usize) -> TokenArena {
+        TokenArena {
+            chunks: RefCell::new(vec![String::with_capacity(bytes)]),
+        }
+    }
+
+    /// Copy a string into the arena.
+    pub fn alloc_str(&self, value: &str) -> &str {
+        if value.is_empty() {
+            return ""
+        }
+        let mut chunks = self.chunks.borrow_mut();
+        let needs_new_chunk = chunks.last().map_or(true, |chunk| {
+            chunk.capacity() - chunk.len() < value.len()
+        });
+        if needs_new_chunk {
+            let previous_capacity = chunks.last().map_or(0, |chunk| chunk.capacity());
+            let capacity = cmp::max(value.len(), cmp::max(previous_capacity * 2,
+                                                          MINIMUM_CHUNK_CAPACITY));
+            chunks.push(String::with_capacity(capacity))
+        }
+        let chunk = chunks.last_mut().unwrap();
+        let start = chunk.len();
+        chunk.push_str(value);
+        let slice: *const str = &chunk[start..];
+        // Safety: the content of the chunk is never moved or modified (see `chunks`),
+        // and chunks live as long as `self`.
+        unsafe { &*slice }
+    }
+
+    /// Return a copy of the given token, with its strings copied into the arena.
+    pub fn alloc_token<'a>(&'a self, token: &Token) -> Token<'a> {
+        let copy = |value: &Cow<str>| -> Cow<'a, str> { Borrowed(self.alloc_str(value)) };
+        match *token {
+            Token::Ident(ref value) => Token::Ident(copy(value)),
+            Token::AtKeyword(ref value) => Token::AtKeyword(copy(value)),
+            Token::Hash(ref value) => Token::Hash(copy(value)),
+            Token::IDHash(ref value) => Token::IDHash(copy(value)),
+            Token::QuotedString(ref value) => Token::QuotedString(copy(value)),
+            Token::UnquotedUrl(ref value) => Token::UnquotedUrl(copy(value)),
+            Token::Dimension(value, ref unit) => Token::Dimension(value, copy(unit)),
+            Token::Function(ref name) => Token::Function(copy(name)),
+            Token::WhiteSpace(ref value) => Token::WhiteSpace(copy(value)),
+            Token::Comment(ref value) => Token::Comment(copy(value)),
+            Token::Delim(value) => Token::Delim(value),
+            Token::Number(value) => Token::Number(value),
+            Token::Percentage(value) => Token::Percentage(value),
+            Token::UnicodeRange(start, end) => Token::UnicodeRange(start, end),
+            Token::Colon => Token::Colon,
+            Token::Semicolon => Token::Semicolon,
+            Token::Comma => Token::Comma,
+            Token::IncludeMatch => Token::IncludeMatch,
+            Token::DashMatch => Token::DashMatch,
+            Token::PrefixMatch => Token::PrefixMatch,
+            Token::SuffixMatch => Token::SuffixMatch,
+            Token::SubstringMatch => Token::SubstringMatch,
+            Token::Column => Token::Column,
+            Token::CDO => Token::CDO,
+            Token::CDC => Token::CDC,
+            Token::ParenthesisBlock => Token::ParenthesisBlock,
+            Token::SquareBracketBlock => Token::SquareBracketBlock,
+            Token::CurlyBracketBlock => Token::CurlyBracketBlock,
+            Token::BadUrl => Token::BadUrl,
+            Token::BadString => Token::BadString,
+            Token::CloseParenthesis => Token::CloseParenthesis,
+            Token::CloseSquareBracket => Token::CloseSquareBracket,
+            Token::CloseCurlyBracket => Token::CloseCurlyBracket,
+        }
+    }
+
+    /// Return a copy of the given tokens, with their strings copied into the arena.
+    pub fn alloc_tokens<'a>(&'a self, tokens: &[Token]) -> Vec<Token<'a>> {
+        tokens.iter().map(|token| self.alloc_token(token)).collect()
+    }
+
+    /// Tokenize the given input, including whitespace and comments,
+    /// and return the tokens with their strings in the arena.
+    pub fn tokenize<'a>(&'a self, input: &str) -> Vec<Token<'a>> {
+        let mut tokenizer = Tokenizer::new(input);
+        let mut tokens = Vec::new();
+        while let Ok(token) = tokenizer.next() {
+            tokens.push(self.alloc_token(&token))
+        }
+        tokens
+    }
+}
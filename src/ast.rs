@@ -0,0 +1,1024 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+// https://drafts.csswg.org/css-syntax/#parsing
+
+use std::ascii::AsciiExt;
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+
+use parser::byte_index;
+use super::{Token, Parser, ParseError, ParseErrorKind, Delimiter, SourcePosition, SourceRange};
+use super::{ToCss, TokenSerializationType, SerializationOptions, serialize_identifier};
+
+
+/// A [component value](https://drafts.csswg.org/css-syntax/#component-value),
+/// as found in a tree built by `parse_component_value_list`.
+#[derive(PartialEq, Debug, Clone)]
+pub enum ComponentValue<'i> {
+    /// A preserved token.
+    ///
+    /// This is never a `Function`, `ParenthesisBlock`, `SquareBracketBlock`,
+    /// or `CurlyBracketBlock` token (these are represented by the other variants)
+    /// nor a `Comment` token.
+    PreservedToken(Token<'i>),
+
+    /// A function: its name (without the `(` marker) and its arguments.
+    Function(Cow<'i, str>, Vec<Node<'i>>),
+
+    /// A `( … )` block and its content.
+    ParenthesisBlock(Vec<Node<'i>>),
+
+    /// A `[ … ]` block and its content.
+    SquareBracketBlock(Vec<Node<'i>>),
+
+    /// A `{ … }` block and its content.
+    CurlyBracketBlock(Vec<Node<'i>>),
+}
+
+
+impl<'i> ComponentValue<'i> {
+    /// Return a component value that owns its strings, and so does not borrow the input.
+    /// See `Token::into_owned`.
+    pub fn into_owned(self) -> ComponentValue<'static> {
+        match self {
+            ComponentValue::PreservedToken(token) => {
+                ComponentValue::PreservedToken(token.into_owned())
+            }
+            ComponentValue::Function(name, arguments) => {
+                ComponentValue::Function(Cow::Owned(name.into_owned()), owned_nodes(arguments))
+            }
+            ComponentValue::ParenthesisBlock(content) => {
+                ComponentValue::ParenthesisBlock(owned_nodes(content))
+            }
+            ComponentValue::SquareBracketBlock(content) => {
+                ComponentValue::SquareBracketBlock(owned_nodes(content))
+            }
+            ComponentValue::CurlyBracketBlock(content) => {
+                ComponentValue::CurlyBracketBlock(owned_nodes(content))
+            }
+        }
+    }
+}
+
+
+/// A component value together with its location in the input.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Node<'i> {
+    /// The component value.
+    pub value: ComponentValue<'i>,
+
+    /// The start and end of the component value in the input.
+    ///
+    /// For functions and blocks, this includes the closing token if there is one.
+    /// The corresponding source text can be obtained with `Parser::slice`.
+    pub span: SourceRange,
+}
+
+
+impl<'i> Node<'i> {
+    /// Return a node that owns its strings, and so does not borrow the input.
+    /// See `Token::into_owned`.
+    pub fn into_owned(self) -> Node<'static> {
+        Node {
+            value: self.value.into_owned(),
+            span: self.span,
+        }
+    }
+}
+
+
+fn owned_nodes(nodes: Vec<Node>) -> Vec<Node<'static>> {
+    nodes.into_iter().map(Node::into_owned).collect()
+}
+
+
+/// Serialize a component value from its tokens,
+/// with the closing token of functions and blocks even if it was missing in the input.
+impl<'i> ToCss for ComponentValue<'i> {
+    fn to_css<W>(&self, dest: &mut W) -> fmt::Result where W: fmt::Write {
+        self.to_css_with_options(&SerializationOptions::default(), dest)
+    }
+
+    fn to_css_with_options<W>(&self, options: &SerializationOptions, dest: &mut W)
+                              -> fmt::Result where W: fmt::Write {
+        NodeWriter::new(dest, *options).write_component_value(self)
+    }
+}
+
+
+impl<'i> ToCss for Node<'i> {
+    fn to_css<W>(&self, dest: &mut W) -> fmt::Result where W: fmt::Write {
+        self.value.to_css(dest)
+    }
+
+    fn to_css_with_options<W>(&self, options: &SerializationOptions, dest: &mut W)
+                              -> fmt::Result where W: fmt::Write {
+        self.value.to_css_with_options(options, dest)
+    }
+}
+
+
+/// Serialize a list of component values, such as from `parse_component_value_list`.
+///
+/// Since comments are not kept in the tree,
+/// `/**/` is written between tokens that would otherwise be tokenized as one.
+/// The result parses to the same component values.
+impl<'i> ToCss for [Node<'i>] {
+    fn to_css<W>(&self, dest: &mut W) -> fmt::Result where W: fmt::Write {
+        self.to_css_with_options(&SerializationOptions::default(), dest)
+    }
+
+    fn to_css_with_options<W>(&self, options: &SerializationOptions, dest: &mut W)
+                              -> fmt::Result where W: fmt::Write {
+        NodeWriter::new(dest, *options).write_nodes(self)
+    }
+}
+
+
+/// Serialize a rule with its nested rules and declarations.
+///
+/// With the default options, each declaration and nested rule is on its own line
+/// and indented, and declarations end with a `;` semicolon.
+/// With `minify_whitespace`, there are no line breaks and only the necessary whitespace.
+/// Preludes and declaration values are written like lists of component values,
+/// except that the values of custom properties keep their whitespace
+/// and are never changed by `shortest_hex_colors` or `shortest_numbers`.
+impl<'i> ToCss for Rule<'i> {
+    fn to_css<W>(&self, dest: &mut W) -> fmt::Result where W: fmt::Write {
+        self.to_css_with_options(&SerializationOptions::default(), dest)
+    }
+
+    fn to_css_with_options<W>(&self, options: &SerializationOptions, dest: &mut W)
+                              -> fmt::Result where W: fmt::Write {
+        write_rule(self, 0, options, dest)
+    }
+}
+
+
+/// Serialize rules like `Rule` does, such as a stylesheet from `parse_stylesheet`,
+/// separated by line breaks unless `minify_whitespace` is set.
+impl<'i> ToCss for [Rule<'i>] {
+    fn to_css<W>(&self, dest: &mut W) -> fmt::Result where W: fmt::Write {
+        self.to_css_with_options(&SerializationOptions::default(), dest)
+    }
+
+    fn to_css_with_options<W>(&self, options: &SerializationOptions, dest: &mut W)
+                              -> fmt::Result where W: fmt::Write {
+        for (i, rule) in self.iter().enumerate() {
+            if i > 0 && !options.minify_whitespace {
+                try!(dest.write_str(options.newline))
+            }
+            try!(write_rule(rule, 0, options, dest))
+        }
+        Ok(())
+    }
+}
+
+
+/// Serialize a declaration like in the block of a `Rule`, without a `;` semicolon.
+impl<'i> ToCss for Declaration<'i> {
+    fn to_css<W>(&self, dest: &mut W) -> fmt::Result where W: fmt::Write {
+        self.to_css_with_options(&SerializationOptions::default(), dest)
+    }
+
+    fn to_css_with_options<W>(&self, options: &SerializationOptions, dest: &mut W)
+                              -> fmt::Result where W: fmt::Write {
+        try!(serialize_identifier(&self.name, dest));
+        try!(dest.write_str(if options.minify_whitespace { ":" } else { ": " }));
+        let value_options = if self.name.starts_with("--") {
+            SerializationOptions {
+                minify_whitespace: false,
+                ..prelude_options(options)
+            }
+        } else {
+            *options
+        };
+        try!(NodeWriter::new(dest, value_options).write_nodes(&self.value));
+        if self.important {
+            if !options.minify_whitespace {
+                try!(dest.write_str(" "))
+            }
+            try!(dest.write_str("!important"))
+        }
+        Ok(())
+    }
+}
+
+
+fn write_rule<W>(rule: &Rule, depth: usize, options: &SerializationOptions, dest: &mut W)
+                 -> fmt::Result where W: fmt::Write {
+    match *rule {
+        Rule::QualifiedRule(ref rule) => {
+            {
+                let mut writer = NodeWriter::new(&mut *dest, prelude_options(options));
+                writer.in_selector = true;
+                try!(writer.write_nodes(&rule.prelude));
+            }
+            if !options.minify_whitespace && !rule.prelude.is_empty() {
+                try!(dest.write_str(" "))
+            }
+            write_block(&rule.block, depth, options, dest)
+        }
+        Rule::AtRule(ref rule) => {
+            {
+                let mut writer = NodeWriter::new(&mut *dest, prelude_options(options));
+                try!(writer.write_token(&Token::AtKeyword(rule.name.clone())));
+                writer.space = if options.minify_whitespace {
+                    Space::Optional
+                } else {
+                    Space::Significant
+                };
+                try!(writer.write_nodes(&rule.prelude));
+            }
+            match rule.block {
+                Some(ref block) => {
+                    if !options.minify_whitespace {
+                        try!(dest.write_str(" "))
+                    }
+                    write_block(block, depth, options, dest)
+                }
+                None => dest.write_str(";"),
+            }
+        }
+    }
+}
+
+
+fn write_block<W>(items: &[BlockItem], depth: usize, options: &SerializationOptions, dest: &mut W)
+                  -> fmt::Result where W: fmt::Write {
+    try!(dest.write_str("{"));
+    if items.is_empty() {
+        return dest.write_str("}")
+    }
+    let mut previous_was_declaration = false;
+    for item in items {
+        if options.minify_whitespace {
+            if previous_was_declaration {
+                try!(dest.write_str(";"))
+            }
+        } else {
+            try!(write_line_start(depth + 1, options, dest))
+        }
+        match *item {
+            BlockItem::Declaration(ref declaration) => {
+                try!(declaration.to_css_with_options(options, dest));
+                if !options.minify_whitespace {
+                    try!(dest.write_str(";"))
+                }
+                previous_was_declaration = true;
+            }
+            BlockItem::Rule(ref rule) => {
+                try!(write_rule(rule, depth + 1, options, dest));
+                previous_was_declaration = false;
+            }
+        }
+    }
+    if !options.minify_whitespace {
+        try!(write_line_start(depth, options, dest))
+    }
+    dest.write_str("}")
+}
+
+
+fn write_line_start<W>(depth: usize, options: &SerializationOptions, dest: &mut W) -> fmt::Result
+where W: fmt::Write {
+    try!(dest.write_str(options.newline));
+    for _ in 0..depth * options.indent_width {
+        try!(dest.write_str(" "))
+    }
+    Ok(())
+}
+
+
+/// Options for preludes, where hash tokens can be ID selectors
+/// and numbers are not shortened.
+fn prelude_options(options: &SerializationOptions) -> SerializationOptions {
+    SerializationOptions {
+        shortest_hex_colors: false,
+        shortest_numbers: false,
+        ..*options
+    }
+}
+
+
+#[derive(Copy, Clone, PartialEq)]
+enum Space {
+    None,
+    /// Only written if the tokens would otherwise be parsed differently.
+    Optional,
+    /// Whitespace that was in the input and might be significant.
+    Significant,
+}
+
+
+/// Writes component values, keeping track of what is needed between tokens.
+struct NodeWriter<'w, W: 'w> {
+    dest: &'w mut W,
+    options: SerializationOptions,
+    /// Whether whitespace around `>`, `+`, and `~` combinators can be removed.
+    in_selector: bool,
+    previous: TokenSerializationType,
+    space: Space,
+}
+
+
+impl<'w, W> NodeWriter<'w, W> where W: fmt::Write {
+    fn new(dest: &'w mut W, options: SerializationOptions) -> NodeWriter<'w, W> {
+        NodeWriter {
+            dest: dest,
+            options: options,
+            in_selector: false,
+            previous: TokenSerializationType::nothing(),
+            space: Space::None,
+        }
+    }
+
+    fn write_nodes(&mut self, nodes: &[Node]) -> fmt::Result {
+        for node in nodes {
+            try!(self.write_component_value(&node.value))
+        }
+        Ok(())
+    }
+
+    fn write_component_value(&mut self, value: &ComponentValue) -> fmt::Result {
+        let (opening, content, closing) = match *value {
+            ComponentValue::PreservedToken(ref token) => return self.write_preserved_token(token),
+            ComponentValue::Function(ref name, ref arguments) => {
+                (Token::Function(name.clone()), arguments, Token::CloseParenthesis)
+            }
+            ComponentValue::ParenthesisBlock(ref content) => {
+                (Token::ParenthesisBlock, content, Token::CloseParenthesis)
+            }
+            ComponentValue::SquareBracketBlock(ref content) => {
+                (Token::SquareBracketBlock, content, Token::CloseSquareBracket)
+            }
+            ComponentValue::CurlyBracketBlock(ref content) => {
+                (Token::CurlyBracketBlock, content, Token::CloseCurlyBracket)
+            }
+        };
+        try!(self.write_token(&opening));
+        if self.options.minify_whitespace {
+            // Whitespace after an opening token is never needed.
+            self.previous = TokenSerializationType::nothing();
+        }
+        try!(self.write_nodes(content));
+        // Nor before a closing token.
+        self.space = Space::None;
+        self.write_token(&closing)
+    }
+
+    fn write_preserved_token(&mut self, token: &Token) -> fmt::Result {
+        if !self.options.minify_whitespace {
+            return self.write_token(token)
+        }
+        let is_separator = match *token {
+            Token::WhiteSpace(_) => {
+                if self.space == Space::None && self.previous != TokenSerializationType::nothing() {
+                    self.space = Space::Significant
+                }
+                return Ok(())
+            }
+            Token::Comma => true,
+            Token::Delim('>') | Token::Delim('+') | Token::Delim('~') => self.in_selector,
+            _ => false
+        };
+        if is_separator && self.space == Space::Significant {
+            self.space = Space::Optional
+        }
+        try!(self.write_token(token));
+        if is_separator {
+            // Whitespace after the separator is not needed either.
+            self.space = Space::Optional
+        }
+        Ok(())
+    }
+
+    /// Write a token, with whatever is needed between the previous token and this one.
+    fn write_token(&mut self, token: &Token) -> fmt::Result {
+        let next = token.serialization_type();
+        let needs_separator = self.previous.needs_separator_when_before(next);
+        let space = self.space;
+        self.space = Space::None;
+        self.previous = next;
+        if space == Space::Significant || (space == Space::Optional && needs_separator) {
+            try!(self.dest.write_str(" "))
+        } else if needs_separator {
+            try!(self.dest.write_str("/**/"))
+        }
+        token.to_css_with_options(&self.options, self.dest)
+    }
+}
+
+
+/// Parse the rest of the input into a tree of component values.
+///
+/// Whitespace tokens are preserved. Comments are skipped.
+pub fn parse_component_value_list<'i, 't>(input: &mut Parser<'i, 't>) -> Vec<Node<'i>> {
+    consume_component_value_list(input, &mut Vec::new())
+}
+
+
+/// Parse a single component value, surrounded by optional whitespace.
+///
+/// Return an error if the input is empty or if there is more than one component value.
+pub fn parse_one_component_value<'i, 't>(input: &mut Parser<'i, 't>)
+                                         -> Result<Node<'i>, ParseError<'i>> {
+    input.parse_entirely(|input| {
+        loop {
+            let start_position = input.position();
+            match try!(input.next_including_whitespace_and_comments()) {
+                Token::WhiteSpace(_) | Token::Comment(_) => {}
+                token => return Ok(consume_component_value(start_position, token, input,
+                                                           &mut Vec::new()))
+            }
+        }
+    })
+}
+
+
+fn consume_component_value_list<'i, 't>(input: &mut Parser<'i, 't>,
+                                        diagnostics: &mut Vec<Diagnostic>) -> Vec<Node<'i>> {
+    let mut values = Vec::new();
+    loop {
+        let start_position = input.position();
+        match input.next_including_whitespace_and_comments() {
+            Ok(Token::Comment(_)) => {}
+            Ok(token) => {
+                values.push(consume_component_value(start_position, token, input, diagnostics))
+            }
+            Err(_) => return values,
+        }
+    }
+}
+
+
+fn consume_component_value<'i, 't>(start_position: SourcePosition, token: Token<'i>,
+                                   input: &mut Parser<'i, 't>, diagnostics: &mut Vec<Diagnostic>)
+                                   -> Node<'i> {
+    let value = match token {
+        Token::Function(name) => ComponentValue::Function(
+            name, consume_nested(start_position, input, diagnostics, consume_component_value_list)),
+        Token::ParenthesisBlock => ComponentValue::ParenthesisBlock(
+            consume_nested(start_position, input, diagnostics, consume_component_value_list)),
+        Token::SquareBracketBlock => ComponentValue::SquareBracketBlock(
+            consume_nested(start_position, input, diagnostics, consume_component_value_list)),
+        Token::CurlyBracketBlock => ComponentValue::CurlyBracketBlock(
+            consume_nested(start_position, input, diagnostics, consume_component_value_list)),
+        token => {
+            let kind = match token {
+                Token::BadString => Some(DiagnosticKind::BadString),
+                Token::BadUrl => Some(DiagnosticKind::BadUrl),
+                Token::CloseParenthesis |
+                Token::CloseSquareBracket |
+                Token::CloseCurlyBracket => Some(DiagnosticKind::UnmatchedCloseBracket),
+                _ => None,
+            };
+            if let Some(kind) = kind {
+                let end_position = input.position();
+                diagnostics.push(Diagnostic {
+                    kind: kind,
+                    range: start_position..end_position,
+                    recovered_to: end_position,
+                })
+            }
+            ComponentValue::PreservedToken(token)
+        }
+    };
+    Node {
+        value: value,
+        span: start_position..input.position(),
+    }
+}
+
+
+/// Parse the content of a block or function whose opening token was just consumed,
+/// and report a diagnostic if the input ends before the closing token.
+fn consume_nested<'i, 't, F, T>(start_position: SourcePosition, input: &mut Parser<'i, 't>,
+                               diagnostics: &mut Vec<Diagnostic>, parse: F) -> T
+where F: for<'tt> FnOnce(&mut Parser<'i, 'tt>, &mut Vec<Diagnostic>) -> T {
+    let mut content_end = start_position;
+    // `parse` consumes everything, so this never fails.
+    let result = input.parse_nested_block(|input| {
+        let result = parse(input, diagnostics);
+        content_end = input.position();
+        Ok::<_, ParseError>(result)
+    }).unwrap();
+    // Unless the input ended, the closing token was consumed after the content.
+    if byte_index(input.position()) == byte_index(content_end) {
+        diagnostics.push(Diagnostic {
+            kind: DiagnosticKind::UnclosedBlock,
+            range: start_position..content_end,
+            recovered_to: content_end,
+        })
+    }
+    result
+}
+
+
+/// A rule in a tree built by `parse_stylesheet`.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Rule<'i> {
+    /// A qualified rule, such as a style rule.
+    QualifiedRule(QualifiedRule<'i>),
+
+    /// An at-rule.
+    AtRule(AtRule<'i>),
+}
+
+
+impl<'i> Rule<'i> {
+    /// The start and end of the whole rule in the input.
+    pub fn span(&self) -> SourceRange {
+        match *self {
+            Rule::QualifiedRule(ref rule) => rule.span.clone(),
+            Rule::AtRule(ref rule) => rule.span.clone(),
+        }
+    }
+
+    /// Return a rule that owns its strings, and so does not borrow the input.
+    /// See `Token::into_owned`.
+    pub fn into_owned(self) -> Rule<'static> {
+        match self {
+            Rule::QualifiedRule(rule) => Rule::QualifiedRule(rule.into_owned()),
+            Rule::AtRule(rule) => Rule::AtRule(rule.into_owned()),
+        }
+    }
+}
+
+
+/// An item of the content of a `{ … }` block of a rule.
+#[derive(PartialEq, Debug, Clone)]
+pub enum BlockItem<'i> {
+    /// A declaration.
+    Declaration(Declaration<'i>),
+
+    /// A nested rule.
+    Rule(Rule<'i>),
+}
+
+
+impl<'i> BlockItem<'i> {
+    /// Return an item that owns its strings, and so does not borrow the input.
+    /// See `Token::into_owned`.
+    pub fn into_owned(self) -> BlockItem<'static> {
+        match self {
+            BlockItem::Declaration(declaration) => BlockItem::Declaration(declaration.into_owned()),
+            BlockItem::Rule(rule) => BlockItem::Rule(rule.into_owned()),
+        }
+    }
+}
+
+
+fn owned_items(items: Vec<BlockItem>) -> Vec<BlockItem<'static>> {
+    items.into_iter().map(BlockItem::into_owned).collect()
+}
+
+
+/// A [qualified rule](https://drafts.csswg.org/css-syntax/#qualified-rule).
+#[derive(PartialEq, Debug, Clone)]
+pub struct QualifiedRule<'i> {
+    /// The prelude, before the `{ … }` block. For style rules, this is a selector list.
+    pub prelude: Vec<Node<'i>>,
+
+    /// The content of the `{ … }` block.
+    pub block: Vec<BlockItem<'i>>,
+
+    /// The start and end of the whole rule in the input.
+    pub span: SourceRange,
+}
+
+
+impl<'i> QualifiedRule<'i> {
+    /// Return a rule that owns its strings, and so does not borrow the input.
+    /// See `Token::into_owned`.
+    pub fn into_owned(self) -> QualifiedRule<'static> {
+        QualifiedRule {
+            prelude: owned_nodes(self.prelude),
+            block: owned_items(self.block),
+            span: self.span,
+        }
+    }
+}
+
+
+/// An [at-rule](https://drafts.csswg.org/css-syntax/#at-rule).
+#[derive(PartialEq, Debug, Clone)]
+pub struct AtRule<'i> {
+    /// The name, without the `@` marker.
+    pub name: Cow<'i, str>,
+
+    /// The prelude, between the name and the `;` semicolon or `{ … }` block.
+    pub prelude: Vec<Node<'i>>,
+
+    /// The content of the `{ … }` block, or `None` for at-rules that end with a semicolon.
+    pub block: Option<Vec<BlockItem<'i>>>,
+
+    /// The start and end of the whole rule in the input.
+    pub span: SourceRange,
+}
+
+
+impl<'i> AtRule<'i> {
+    /// Return a rule that owns its strings, and so does not borrow the input.
+    /// See `Token::into_owned`.
+    pub fn into_owned(self) -> AtRule<'static> {
+        AtRule {
+            name: Cow::Owned(self.name.into_owned()),
+            prelude: owned_nodes(self.prelude),
+            block: self.block.map(owned_items),
+            span: self.span,
+        }
+    }
+}
+
+
+/// A [declaration](https://drafts.csswg.org/css-syntax/#declaration).
+#[derive(PartialEq, Debug, Clone)]
+pub struct Declaration<'i> {
+    /// The property or descriptor name.
+    pub name: Cow<'i, str>,
+
+    /// The value, without leading and trailing whitespace and without `!important`.
+    pub value: Vec<Node<'i>>,
+
+    /// Whether the declaration ended with `!important`.
+    pub important: bool,
+
+    /// The start and end of the declaration in the input,
+    /// not including the `;` semicolon.
+    pub span: SourceRange,
+}
+
+
+impl<'i> Declaration<'i> {
+    /// Return a declaration that owns its strings, and so does not borrow the input.
+    /// See `Token::into_owned`.
+    pub fn into_owned(self) -> Declaration<'static> {
+        Declaration {
+            name: Cow::Owned(self.name.into_owned()),
+            value: owned_nodes(self.value),
+            important: self.important,
+            span: self.span,
+        }
+    }
+}
+
+
+/// The kind of a syntax error found by `parse_stylesheet_with_diagnostics`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DiagnosticKind {
+    /// A `BadString` token: a quoted string with an unescaped newline.
+    BadString,
+
+    /// A `BadUrl` token: an unquoted `url(…)` with invalid characters.
+    BadUrl,
+
+    /// A `)`, `]`, or `}` token without a matching opening token.
+    UnmatchedCloseBracket,
+
+    /// A function or block that is not closed before the end of the input.
+    UnclosedBlock,
+
+    /// A qualified rule without a `{ … }` block, which is skipped.
+    InvalidQualifiedRule,
+
+    /// Content of a rule’s block that starts with an identifier
+    /// but is neither a declaration nor a nested rule, which is skipped.
+    InvalidDeclaration,
+}
+
+
+impl DiagnosticKind {
+    fn message(self) -> &'static str {
+        match self {
+            DiagnosticKind::BadString => "newline in string",
+            DiagnosticKind::BadUrl => "invalid character in url()",
+            DiagnosticKind::UnmatchedCloseBracket => "unmatched closing bracket",
+            DiagnosticKind::UnclosedBlock => "unclosed block",
+            DiagnosticKind::InvalidQualifiedRule => "qualified rule without a block",
+            DiagnosticKind::InvalidDeclaration => "invalid declaration",
+        }
+    }
+}
+
+
+impl fmt::Display for DiagnosticKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+
+impl Error for DiagnosticKind {
+    fn description(&self) -> &str {
+        self.message()
+    }
+}
+
+
+/// A syntax error found while parsing, and how parsing recovered from it.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Diagnostic {
+    /// What kind of error this is.
+    pub kind: DiagnosticKind,
+
+    /// The part of the input that is erroneous or was skipped.
+    pub range: SourceRange,
+
+    /// The position where parsing resumed normally after the error.
+    pub recovered_to: SourcePosition,
+}
+
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at bytes {}..{}", self.kind,
+               byte_index(self.range.start), byte_index(self.range.end))
+    }
+}
+
+
+impl Error for Diagnostic {
+    fn description(&self) -> &str {
+        self.kind.message()
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        Some(&self.kind)
+    }
+}
+
+
+/// Parse the rest of the input as a stylesheet, into a tree of rules.
+///
+/// Invalid rules are skipped, per the error recovery rules of CSS Syntax.
+/// The content of rule blocks is parsed as a mix of declarations and nested rules,
+/// as in [CSS Nesting](https://drafts.csswg.org/css-nesting/).
+pub fn parse_stylesheet<'i, 't>(input: &mut Parser<'i, 't>) -> Vec<Rule<'i>> {
+    consume_stylesheet(input, &mut Vec::new())
+}
+
+
+/// Like `parse_stylesheet`, but also return every syntax error found along the way,
+/// in the order they were found.
+///
+/// Errors never abort parsing, so this can be used to lint a whole stylesheet at once.
+/// Only the syntax is checked: the names and values of declarations and rules are not.
+pub fn parse_stylesheet_with_diagnostics<'i, 't>(input: &mut Parser<'i, 't>)
+                                                 -> (Vec<Rule<'i>>, Vec<Diagnostic>) {
+    let mut diagnostics = Vec::new();
+    let rules = consume_stylesheet(input, &mut diagnostics);
+    (rules, diagnostics)
+}
+
+
+/// Parse the rest of the input as the content of a rule’s `{ … }` block,
+/// such as a `style` attribute.
+///
+/// Invalid declarations and rules are skipped.
+pub fn parse_block_contents<'i, 't>(input: &mut Parser<'i, 't>) -> Vec<BlockItem<'i>> {
+    consume_block_contents(input, &mut Vec::new())
+}
+
+
+/// Like `parse_block_contents`, but also return every syntax error found along the way,
+/// in the order they were found.
+pub fn parse_block_contents_with_diagnostics<'i, 't>(input: &mut Parser<'i, 't>)
+                                                     -> (Vec<BlockItem<'i>>, Vec<Diagnostic>) {
+    let mut diagnostics = Vec::new();
+    let items = consume_block_contents(input, &mut diagnostics);
+    (items, diagnostics)
+}
+
+
+fn consume_stylesheet<'i, 't>(input: &mut Parser<'i, 't>, diagnostics: &mut Vec<Diagnostic>)
+                              -> Vec<Rule<'i>> {
+    let mut rules = Vec::new();
+    while let Some(rule) = next_stylesheet_rule(input, diagnostics) {
+        rules.push(rule)
+    }
+    rules
+}
+
+
+/// Parse the next rule of a stylesheet, skipping whitespace, comments, and invalid rules.
+/// Return `None` at the end of the input. (Not exported outside this crate.)
+pub fn next_stylesheet_rule<'i, 't>(input: &mut Parser<'i, 't>,
+                                    diagnostics: &mut Vec<Diagnostic>) -> Option<Rule<'i>> {
+    loop {
+        let start_position = input.position();
+        match input.next_including_whitespace_and_comments() {
+            Ok(Token::WhiteSpace(_)) | Ok(Token::Comment(_)) | Ok(Token::CDO) | Ok(Token::CDC) => {}
+            Ok(Token::AtKeyword(name)) => {
+                return Some(Rule::AtRule(parse_at_rule(start_position, name, input, diagnostics)))
+            }
+            Ok(_) => {
+                input.reset(start_position);
+                match parse_qualified_rule(start_position, input, false, diagnostics) {
+                    Ok(rule) => return Some(Rule::QualifiedRule(rule)),
+                    Err(range) => diagnostics.push(Diagnostic {
+                        kind: DiagnosticKind::InvalidQualifiedRule,
+                        range: range,
+                        recovered_to: input.position(),
+                    })
+                }
+            }
+            Err(_) => return None,
+        }
+    }
+}
+
+
+fn consume_block_contents<'i, 't>(input: &mut Parser<'i, 't>, diagnostics: &mut Vec<Diagnostic>)
+                                  -> Vec<BlockItem<'i>> {
+    let mut items = Vec::new();
+    loop {
+        let start_position = input.position();
+        match input.next_including_whitespace_and_comments() {
+            Ok(Token::WhiteSpace(_)) | Ok(Token::Comment(_)) | Ok(Token::Semicolon) => {}
+            Ok(Token::AtKeyword(name)) => {
+                let rule = parse_at_rule(start_position, name, input, diagnostics);
+                items.push(BlockItem::Rule(Rule::AtRule(rule)))
+            }
+            Ok(token) => {
+                let starts_with_ident = matches!(token, Token::Ident(_));
+                if let Token::Ident(name) = token {
+                    // Errors found while trying to parse a declaration are found again
+                    // when the same input is re-parsed as a rule.
+                    let diagnostics_len = diagnostics.len();
+                    let result = input.try(|input| {
+                        parse_declaration(start_position, name, input, diagnostics)
+                    });
+                    if let Ok(declaration) = result {
+                        items.push(BlockItem::Declaration(declaration));
+                        continue
+                    }
+                    diagnostics.truncate(diagnostics_len);
+                }
+                input.reset(start_position);
+                match parse_qualified_rule(start_position, input, true, diagnostics) {
+                    Ok(rule) => items.push(BlockItem::Rule(Rule::QualifiedRule(rule))),
+                    Err(range) => diagnostics.push(Diagnostic {
+                        kind: if starts_with_ident {
+                            DiagnosticKind::InvalidDeclaration
+                        } else {
+                            DiagnosticKind::InvalidQualifiedRule
+                        },
+                        range: range,
+                        recovered_to: input.position(),
+                    })
+                }
+            }
+            Err(_) => return items,
+        }
+    }
+}
+
+
+fn parse_declaration<'i, 't>(start_position: SourcePosition, name: Cow<'i, str>,
+                             input: &mut Parser<'i, 't>, diagnostics: &mut Vec<Diagnostic>)
+                             -> Result<Declaration<'i>, ParseError<'i>> {
+    let is_custom_property = name.starts_with("--");
+    let declaration = try!(input.parse_until_before(Delimiter::Semicolon, |input| {
+        try!(input.expect_colon());
+        let after_colon = input.position();
+        let mut value = consume_component_value_list(input, diagnostics);
+        trim_whitespace(&mut value);
+        let end_position = value.last().map_or(after_colon, |node| node.span.end);
+        let important = strip_important(&mut value);
+        Ok(Declaration {
+            name: name,
+            value: value,
+            important: important,
+            span: start_position..end_position,
+        })
+    }));
+    // Per CSS Nesting, something that looks like `a:hover { … }` is a nested rule,
+    // not a declaration.
+    if !is_custom_property && declaration.value.iter().any(|node| {
+        matches!(node.value, ComponentValue::CurlyBracketBlock(_))
+    }) {
+        return Err(input.new_error(ParseErrorKind::Invalid))
+    }
+    Ok(declaration)
+}
+
+
+fn parse_at_rule<'i, 't>(start_position: SourcePosition, name: Cow<'i, str>,
+                         input: &mut Parser<'i, 't>, diagnostics: &mut Vec<Diagnostic>)
+                         -> AtRule<'i> {
+    let delimiters = Delimiter::Semicolon | Delimiter::CurlyBracketBlock;
+    let mut prelude = input.parse_until_before(delimiters, |input| {
+        Ok::<_, ParseError>(consume_component_value_list(input, diagnostics))
+    }).unwrap();
+    trim_whitespace(&mut prelude);
+    let block_start = input.position();
+    let block = match input.next() {
+        Ok(Token::CurlyBracketBlock) => {
+            Some(consume_nested(block_start, input, diagnostics, consume_block_contents))
+        }
+        Ok(Token::Semicolon) | Err(_) => None,
+        Ok(_) => unreachable!()
+    };
+    AtRule {
+        name: name,
+        prelude: prelude,
+        block: block,
+        span: start_position..input.position(),
+    }
+}
+
+
+/// Return the skipped range on error.
+fn parse_qualified_rule<'i, 't>(start_position: SourcePosition, input: &mut Parser<'i, 't>,
+                                nested: bool, diagnostics: &mut Vec<Diagnostic>)
+                                -> Result<QualifiedRule<'i>, SourceRange> {
+    // In nested contexts, a semicolon ends an (invalid) qualified rule.
+    let delimiters = if nested {
+        Delimiter::CurlyBracketBlock | Delimiter::Semicolon
+    } else {
+        Delimiter::CurlyBracketBlock
+    };
+    let mut prelude = input.parse_until_before(delimiters, |input| {
+        Ok::<_, ParseError>(consume_component_value_list(input, diagnostics))
+    }).unwrap();
+    let block_start = input.position();
+    match input.next() {
+        Ok(Token::CurlyBracketBlock) => {
+            trim_whitespace(&mut prelude);
+            let block = consume_nested(block_start, input, diagnostics, consume_block_contents);
+            Ok(QualifiedRule {
+                prelude: prelude,
+                block: block,
+                span: start_position..input.position(),
+            })
+        }
+        Ok(Token::Semicolon) | Err(_) => Err(start_position..block_start),
+        Ok(_) => unreachable!()
+    }
+}
+
+
+/// Whether this is a whitespace token. (Not exported outside this crate.)
+pub fn is_whitespace(node: &Node) -> bool {
+    matches!(node.value, ComponentValue::PreservedToken(Token::WhiteSpace(_)))
+}
+
+
+/// Remove leading and trailing whitespace tokens. (Not exported outside this crate.)
+pub fn trim_whitespace(nodes: &mut Vec<Node>) {
+    while nodes.last().map_or(false, is_whitespace) {
+        nodes.pop();
+    }
+    let leading = nodes.iter().take_while(|node| is_whitespace(node)).count();
+    nodes.drain(..leading);
+}
+
+
+/// Remove a trailing `!important` (and whitespace before it), and return whether there was one.
+///
+/// Assumes that trailing whitespace has already been removed.
+/// (Not exported outside this crate.)
+pub fn strip_important(nodes: &mut Vec<Node>) -> bool {
+    let len = nodes.len();
+    let important = match nodes.last() {
+        Some(&Node { value: ComponentValue::PreservedToken(Token::Ident(ref value)), .. }) => {
+            value.eq_ignore_ascii_case("important")
+        }
+        _ => false
+    };
+    if !important {
+        return false
+    }
+    let bang = nodes[..len - 1].iter().rposition(|node| !is_whitespace(node));
+    match bang {
+        Some(index) if nodes[index].value == ComponentValue::PreservedToken(Token::Delim('!')) => {
+            nodes.truncate(index);
+            trim_whitespace(nodes);
+            true
+        }
+        _ => false
+    }
+}
+
+
+/// Consume the rest of a declaration’s value, and return its source text
+/// without leading and trailing whitespace and without `!important`,
+/// and whether it had `!important`.
+///
+/// Whitespace and `!important` are removed as in `Declaration::value`.
+/// (Not exported outside this crate.)
+#[cfg(any(feature = "capi", feature = "wasm"))]
+pub fn declaration_value_source<'i, 't>(input: &mut Parser<'i, 't>) -> (&'i str, bool) {
+    let mut value = consume_component_value_list(input, &mut Vec::new());
+    trim_whitespace(&mut value);
+    let important = strip_important(&mut value);
+    let source = match (value.first(), value.last()) {
+        (Some(first), Some(last)) => input.slice(first.span.start..last.span.end),
+        _ => "",
+    };
+    (source, important)
+}
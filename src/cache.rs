@@ -0,0 +1,379 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+// A compact binary format for tokens and component value trees.
+//
+// The data starts with a magic number, a version number, and what kind of list follows.
+// Lengths and offsets are LEB128-encoded: seven bits at a time, least significant first,
+// with the high bit set on every byte but the last.
+// Each token or component value starts with a one-byte tag.
+
+use std::borrow::Cow;
+use std::char;
+use std::mem;
+use std::str;
+
+use ast::{ComponentValue, Node};
+use parser::{byte_index, position_from_byte_index};
+use super::{Token, NumericValue, PercentageValue};
+
+
+const MAGIC: &'static [u8] = b"CSSC";
+
+/// Change this when changing the format in any way.
+const VERSION: u8 = 1;
+
+const TOKEN_LIST: u8 = b'T';
+const COMPONENT_VALUE_LIST: u8 = b'C';
+
+
+/// Encode a list of tokens in a compact binary format,
+/// so that build tools can cache them between runs.
+///
+/// The format depends on the version of this crate.
+/// `decode_tokens` returns `Err(())` for data written with another format,
+/// in which case the input should be tokenized again.
+pub fn encode_tokens(tokens: &[Token]) -> Vec<u8> {
+    let mut dest = header(TOKEN_LIST);
+    write_length(&mut dest, tokens.len());
+    for token in tokens {
+        write_token(&mut dest, token)
+    }
+    dest
+}
+
+
+/// Decode a list of tokens written by `encode_tokens`.
+///
+/// Strings in the tokens borrow from `bytes`.
+/// Return `Err(())` if `bytes` is not valid data in this format and version.
+pub fn decode_tokens<'a>(bytes: &'a [u8]) -> Result<Vec<Token<'a>>, ()> {
+    let mut reader = try!(Reader::new(bytes, TOKEN_LIST));
+    let length = try!(reader.read_length());
+    // Don’t trust `length` for allocating: every token takes at least one byte.
+    let mut tokens = Vec::with_capacity(::std::cmp::min(length, reader.bytes.len()));
+    for _ in 0..length {
+        tokens.push(try!(reader.read_token()))
+    }
+    try!(reader.expect_end());
+    Ok(tokens)
+}
+
+
+/// Encode a tree of component values, such as returned by `parse_component_value_list`.
+///
+/// Spans are stored as byte offsets in the original input.
+pub fn encode_component_values(nodes: &[Node]) -> Vec<u8> {
+    let mut dest = header(COMPONENT_VALUE_LIST);
+    write_nodes(&mut dest, nodes);
+    dest
+}
+
+
+/// Decode a tree of component values written by `encode_component_values`.
+///
+/// Strings in the tokens borrow from `bytes`.
+/// Spans can be used with a `Parser` for the original input, like those of a freshly parsed tree.
+/// Return `Err(())` if `bytes` is not valid data in this format and version.
+pub fn decode_component_values<'a>(bytes: &'a [u8]) -> Result<Vec<Node<'a>>, ()> {
+    let mut reader = try!(Reader::new(bytes, COMPONENT_VALUE_LIST));
+    let nodes = try!(reader.read_nodes());
+    try!(reader.expect_end());
+    Ok(nodes)
+}
+
+
+fn header(kind: u8) -> Vec<u8> {
+    let mut dest = MAGIC.to_vec();
+    dest.push(VERSION);
+    dest.push(kind);
+    dest
+}
+
+
+fn write_length(dest: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        dest.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    dest.push(value as u8)
+}
+
+
+fn write_u32(dest: &mut Vec<u8>, value: u32) {
+    dest.extend_from_slice(&[value as u8, (value >> 8) as u8, (value >> 16) as u8,
+                             (value >> 24) as u8])
+}
+
+
+fn write_str(dest: &mut Vec<u8>, value: &str) {
+    write_length(dest, value.len());
+    dest.extend_from_slice(value.as_bytes())
+}
+
+
+fn write_numeric(dest: &mut Vec<u8>, value: &NumericValue) {
+    write_u32(dest, value.value.to_bits());
+    write_optional_integer(dest, value.int_value);
+    dest.push(value.has_sign as u8)
+}
+
+
+fn write_optional_integer(dest: &mut Vec<u8>, value: Option<i32>) {
+    match value {
+        Some(value) => {
+            dest.push(1);
+            write_u32(dest, value as u32)
+        }
+        None => dest.push(0)
+    }
+}
+
+
+fn write_token(dest: &mut Vec<u8>, token: &Token) {
+    match *token {
+        Token::Ident(ref value) => { dest.push(0); write_str(dest, value) }
+        Token::AtKeyword(ref value) => { dest.push(1); write_str(dest, value) }
+        Token::Hash(ref value) => { dest.push(2); write_str(dest, value) }
+        Token::IDHash(ref value) => { dest.push(3); write_str(dest, value) }
+        Token::QuotedString(ref value) => { dest.push(4); write_str(dest, value) }
+        Token::UnquotedUrl(ref value) => { dest.push(5); write_str(dest, value) }
+        Token::Delim(value) => { dest.push(6); write_u32(dest, value as u32) }
+        Token::Number(ref value) => { dest.push(7); write_numeric(dest, value) }
+        Token::Percentage(ref value) => {
+            dest.push(8);
+            write_u32(dest, value.unit_value.to_bits());
+            write_optional_integer(dest, value.int_value);
+            dest.push(value.has_sign as u8)
+        }
+        Token::Dimension(ref value, ref unit) => {
+            dest.push(9);
+            write_numeric(dest, value);
+            write_str(dest, unit)
+        }
+        Token::UnicodeRange(start, end) => {
+            dest.push(10);
+            write_u32(dest, start);
+            write_u32(dest, end)
+        }
+        Token::WhiteSpace(ref value) => { dest.push(11); write_str(dest, value) }
+        Token::Comment(ref value) => { dest.push(12); write_str(dest, value) }
+        Token::Colon => dest.push(13),
+        Token::Semicolon => dest.push(14),
+        Token::Comma => dest.push(15),
+        Token::IncludeMatch => dest.push(16),
+        Token::DashMatch => dest.push(17),
+        Token::PrefixMatch => dest.push(18),
+        Token::SuffixMatch => dest.push(19),
+        Token::SubstringMatch => dest.push(20),
+        Token::Column => dest.push(21),
+        Token::CDO => dest.push(22),
+        Token::CDC => dest.push(23),
+        Token::Function(ref name) => { dest.push(24); write_str(dest, name) }
+        Token::ParenthesisBlock => dest.push(25),
+        Token::SquareBracketBlock => dest.push(26),
+        Token::CurlyBracketBlock => dest.push(27),
+        Token::BadUrl => dest.push(28),
+        Token::BadString => dest.push(29),
+        Token::CloseParenthesis => dest.push(30),
+        Token::CloseSquareBracket => dest.push(31),
+        Token::CloseCurlyBracket => dest.push(32),
+    }
+}
+
+
+fn write_nodes(dest: &mut Vec<u8>, nodes: &[Node]) {
+    write_length(dest, nodes.len());
+    for node in nodes {
+        match node.value {
+            ComponentValue::PreservedToken(ref token) => {
+                dest.push(0);
+                write_token(dest, token)
+            }
+            ComponentValue::Function(ref name, ref arguments) => {
+                dest.push(1);
+                write_str(dest, name);
+                write_nodes(dest, arguments)
+            }
+            ComponentValue::ParenthesisBlock(ref content) => {
+                dest.push(2);
+                write_nodes(dest, content)
+            }
+            ComponentValue::SquareBracketBlock(ref content) => {
+                dest.push(3);
+                write_nodes(dest, content)
+            }
+            ComponentValue::CurlyBracketBlock(ref content) => {
+                dest.push(4);
+                write_nodes(dest, content)
+            }
+        }
+        write_length(dest, byte_index(node.span.start));
+        write_length(dest, byte_index(node.span.end));
+    }
+}
+
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8], kind: u8) -> Result<Reader<'a>, ()> {
+        let header_length = MAGIC.len() + 2;
+        if bytes.len() < header_length || &bytes[..MAGIC.len()] != MAGIC ||
+           bytes[MAGIC.len()] != VERSION || bytes[MAGIC.len() + 1] != kind {
+            return Err(())
+        }
+        Ok(Reader { bytes: &bytes[header_length..] })
+    }
+
+    fn expect_end(&self) -> Result<(), ()> {
+        if self.bytes.is_empty() { Ok(()) } else { Err(()) }
+    }
+
+    fn read_bytes(&mut self, length: usize) -> Result<&'a [u8], ()> {
+        if length > self.bytes.len() {
+            return Err(())
+        }
+        let (result, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+        Ok(result)
+    }
+
+    fn read_byte(&mut self) -> Result<u8, ()> {
+        self.read_bytes(1).map(|bytes| bytes[0])
+    }
+
+    fn read_bool(&mut self) -> Result<bool, ()> {
+        match try!(self.read_byte()) {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(())
+        }
+    }
+
+    fn read_length(&mut self) -> Result<usize, ()> {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let byte = try!(self.read_byte());
+            if shift >= 8 * mem::size_of::<usize>() {
+                return Err(())
+            }
+            value |= ((byte & 0x7F) as usize) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value)
+            }
+            shift += 7;
+        }
+    }
+
+    fn read_u32(&mut self) -> Result<u32, ()> {
+        let bytes = try!(self.read_bytes(4));
+        Ok(bytes[0] as u32 | (bytes[1] as u32) << 8 | (bytes[2] as u32) << 16 |
+           (bytes[3] as u32) << 24)
+    }
+
+    fn read_str(&mut self) -> Result<&'a str, ()> {
+        let length = try!(self.read_length());
+        str::from_utf8(try!(self.read_bytes(length))).map_err(|_| ())
+    }
+
+    fn read_cow(&mut self) -> Result<Cow<'a, str>, ()> {
+        self.read_str().map(Cow::Borrowed)
+    }
+
+    fn read_optional_integer(&mut self) -> Result<Option<i32>, ()> {
+        if try!(self.read_bool()) {
+            Ok(Some(try!(self.read_u32()) as i32))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn read_numeric(&mut self) -> Result<NumericValue, ()> {
+        Ok(NumericValue {
+            value: f32::from_bits(try!(self.read_u32())),
+            int_value: try!(self.read_optional_integer()),
+            has_sign: try!(self.read_bool()),
+        })
+    }
+
+    fn read_token(&mut self) -> Result<Token<'a>, ()> {
+        Ok(match try!(self.read_byte()) {
+            0 => Token::Ident(try!(self.read_cow())),
+            1 => Token::AtKeyword(try!(self.read_cow())),
+            2 => Token::Hash(try!(self.read_cow())),
+            3 => Token::IDHash(try!(self.read_cow())),
+            4 => Token::QuotedString(try!(self.read_cow())),
+            5 => Token::UnquotedUrl(try!(self.read_cow())),
+            6 => Token::Delim(try!(char::from_u32(try!(self.read_u32())).ok_or(()))),
+            7 => Token::Number(try!(self.read_numeric())),
+            8 => Token::Percentage(PercentageValue {
+                unit_value: f32::from_bits(try!(self.read_u32())),
+                int_value: try!(self.read_optional_integer()),
+                has_sign: try!(self.read_bool()),
+            }),
+            9 => {
+                let value = try!(self.read_numeric());
+                Token::Dimension(value, try!(self.read_cow()))
+            }
+            10 => {
+                let start = try!(self.read_u32());
+                Token::UnicodeRange(start, try!(self.read_u32()))
+            }
+            11 => Token::WhiteSpace(try!(self.read_cow())),
+            12 => Token::Comment(try!(self.read_cow())),
+            13 => Token::Colon,
+            14 => Token::Semicolon,
+            15 => Token::Comma,
+            16 => Token::IncludeMatch,
+            17 => Token::DashMatch,
+            18 => Token::PrefixMatch,
+            19 => Token::SuffixMatch,
+            20 => Token::SubstringMatch,
+            21 => Token::Column,
+            22 => Token::CDO,
+            23 => Token::CDC,
+            24 => Token::Function(try!(self.read_cow())),
+            25 => Token::ParenthesisBlock,
+            26 => Token::SquareBracketBlock,
+            27 => Token::CurlyBracketBlock,
+            28 => Token::BadUrl,
+            29 => Token::BadString,
+            30 => Token::CloseParenthesis,
+            31 => Token::CloseSquareBracket,
+            32 => Token::CloseCurlyBracket,
+            _ => return Err(())
+        })
+    }
+
+    fn read_nodes(&mut self) -> Result<Vec<Node<'a>>, ()> {
+        let length = try!(self.read_length());
+        // Don’t trust `length` for allocating: every node takes at least three bytes.
+        let mut nodes = Vec::with_capacity(::std::cmp::min(length, self.bytes.len() / 3));
+        for _ in 0..length {
+            let value = match try!(self.read_byte()) {
+                0 => ComponentValue::PreservedToken(try!(self.read_token())),
+                1 => {
+                    let name = try!(self.read_cow());
+                    ComponentValue::Function(name, try!(self.read_nodes()))
+                }
+                2 => ComponentValue::ParenthesisBlock(try!(self.read_nodes())),
+                3 => ComponentValue::SquareBracketBlock(try!(self.read_nodes())),
+                4 => ComponentValue::CurlyBracketBlock(try!(self.read_nodes())),
+                _ => return Err(())
+            };
+            let start = try!(self.read_length());
+            let end = try!(self.read_length());
+            nodes.push(Node {
+                value: value,
+                span: position_from_byte_index(start)..position_from_byte_index(end),
+            })
+        }
+        Ok(nodes)
+    }
+}
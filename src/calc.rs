@@ -0,0 +1,172 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+// https://drafts.csswg.org/css-values/#calc-syntax
+
+use std::borrow::Cow;
+
+use super::{Token, Parser, ParseError, ParseErrorKind, Delimiter};
+use parser::unexpected_token;
+
+
+/// A node of the expression tree of a math function, as returned by `parse_math_function`.
+///
+/// Units are not resolved or checked for consistency: that is left to the caller.
+#[derive(Clone, PartialEq, Debug)]
+pub enum CalcNode<'i> {
+    /// A number.
+    Number(f32),
+
+    /// A percentage, divided by 100 so that the nominal range is 0.0 to 1.0.
+    Percentage(f32),
+
+    /// A dimension: a number and its unit.
+    Dimension(f32, Cow<'i, str>),
+
+    /// An identifier, such as the `e` and `pi` constants.
+    Ident(Cow<'i, str>),
+
+    /// `a + b`
+    Sum(Box<CalcNode<'i>>, Box<CalcNode<'i>>),
+
+    /// `a - b`
+    Difference(Box<CalcNode<'i>>, Box<CalcNode<'i>>),
+
+    /// `a * b`
+    Product(Box<CalcNode<'i>>, Box<CalcNode<'i>>),
+
+    /// `a / b`
+    Quotient(Box<CalcNode<'i>>, Box<CalcNode<'i>>),
+
+    /// `min(a, b, …)`, with at least one argument.
+    Min(Vec<CalcNode<'i>>),
+
+    /// `max(a, b, …)`, with at least one argument.
+    Max(Vec<CalcNode<'i>>),
+
+    /// `clamp(min, value, max)`
+    Clamp(Box<CalcNode<'i>>, Box<CalcNode<'i>>, Box<CalcNode<'i>>),
+}
+
+
+/// Parse the arguments of a `calc()`, `min()`, `max()`, or `clamp()` math function.
+///
+/// The input is typically the arguments of a function, as in:
+///
+/// ```{rust,ignore}
+/// match try!(input.next()) {
+///     Token::Function(ref name) => input.parse_nested_block(|input| {
+///         parse_math_function(name, input)
+///     }),
+///     // …
+/// }
+/// ```
+///
+/// Nested math functions and parenthesized expressions are supported,
+/// and `*` and `/` take precedence over `+` and `-`.
+/// Per spec, `+` and `-` operators need to be surrounded by whitespace:
+/// `calc(1px+2px)` and `calc(1px -2px)` are syntax errors.
+///
+/// Return an error for an unknown function name, a syntax error, or leftover input.
+pub fn parse_math_function<'i, 't>(name: &str, input: &mut Parser<'i, 't>)
+                                   -> Result<CalcNode<'i>, ParseError<'i>> {
+    match_ignore_ascii_case! { name,
+        "calc" => input.parse_entirely(parse_sum),
+        "min" => Ok(CalcNode::Min(try!(parse_arguments(input)))),
+        "max" => Ok(CalcNode::Max(try!(parse_arguments(input)))),
+        "clamp" => {
+            let mut arguments = try!(parse_arguments(input));
+            if arguments.len() != 3 {
+                return Err(input.new_error(ParseErrorKind::Invalid))
+            }
+            let max = arguments.pop().unwrap();
+            let value = arguments.pop().unwrap();
+            let min = arguments.pop().unwrap();
+            Ok(CalcNode::Clamp(Box::new(min), Box::new(value), Box::new(max)))
+        },
+        _ => Err(input.new_error(ParseErrorKind::Invalid))
+    }
+}
+
+
+fn parse_arguments<'i, 't>(input: &mut Parser<'i, 't>)
+                           -> Result<Vec<CalcNode<'i>>, ParseError<'i>> {
+    let mut arguments = Vec::new();
+    loop {
+        arguments.push(try!(input.parse_until_before(Delimiter::Comma, parse_sum)));
+        match input.next() {
+            Err(_) => return Ok(arguments),
+            Ok(Token::Comma) => continue,
+            Ok(_) => unreachable!(),
+        }
+    }
+}
+
+
+fn parse_sum<'i, 't>(input: &mut Parser<'i, 't>) -> Result<CalcNode<'i>, ParseError<'i>> {
+    let mut sum = try!(parse_product(input));
+    loop {
+        let start_position = input.position();
+        match input.next_including_whitespace() {
+            Ok(Token::WhiteSpace(_)) => {}
+            _ => {
+                input.reset(start_position);
+                return Ok(sum)
+            }
+        }
+        let is_sum = match input.next() {
+            Ok(Token::Delim('+')) => true,
+            Ok(Token::Delim('-')) => false,
+            _ => {
+                input.reset(start_position);
+                return Ok(sum)
+            }
+        };
+        try!(input.expect_whitespace());
+        let operand = Box::new(try!(parse_product(input)));
+        sum = if is_sum {
+            CalcNode::Sum(Box::new(sum), operand)
+        } else {
+            CalcNode::Difference(Box::new(sum), operand)
+        }
+    }
+}
+
+
+fn parse_product<'i, 't>(input: &mut Parser<'i, 't>) -> Result<CalcNode<'i>, ParseError<'i>> {
+    let mut product = try!(parse_value(input));
+    loop {
+        let start_position = input.position();
+        let is_product = match input.next() {
+            Ok(Token::Delim('*')) => true,
+            Ok(Token::Delim('/')) => false,
+            _ => {
+                input.reset(start_position);
+                return Ok(product)
+            }
+        };
+        let operand = Box::new(try!(parse_value(input)));
+        product = if is_product {
+            CalcNode::Product(Box::new(product), operand)
+        } else {
+            CalcNode::Quotient(Box::new(product), operand)
+        }
+    }
+}
+
+
+fn parse_value<'i, 't>(input: &mut Parser<'i, 't>) -> Result<CalcNode<'i>, ParseError<'i>> {
+    let start = input.position();
+    match try!(input.next()) {
+        Token::Number(value) => Ok(CalcNode::Number(value.value)),
+        Token::Percentage(value) => Ok(CalcNode::Percentage(value.unit_value)),
+        Token::Dimension(value, unit) => Ok(CalcNode::Dimension(value.value, unit)),
+        Token::Ident(name) => Ok(CalcNode::Ident(name)),
+        Token::ParenthesisBlock => input.parse_nested_block(parse_sum),
+        Token::Function(name) => {
+            input.parse_nested_block(|input| parse_math_function(&name, input))
+        }
+        token => Err(unexpected_token(token, start))
+    }
+}
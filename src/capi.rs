@@ -0,0 +1,396 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A C API for the tokenizer and for some one-shot parsers,
+//! enabled by the `capi` Cargo feature.
+//!
+//! All types are `#[repr(C)]` so that a header can be generated with cbindgen.
+//! Strings are passed as UTF-8 pointer + length pairs (`CssStr`) and are never NUL-terminated.
+//! Strings given to callbacks are only valid for the duration of the callback.
+//!
+//! Byte offsets are counted in bytes from the start of the input, not in code points.
+//!
+//! Panics never unwind across the C boundary:
+//! a function that panics returns `false` instead.
+
+use std::os::raw::c_void;
+use std::panic::{self, AssertUnwindSafe};
+use std::slice;
+use std::str;
+
+use ast::declaration_value_source;
+use tokenizer::Tokenizer;
+use parser::byte_index;
+use super::{Token, Parser, ParseError, DeclarationParser, DeclarationListParser, AtRuleParser};
+#[cfg(feature = "color")] use super::Color;
+
+
+/// A borrowed UTF-8 string.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct CssStr {
+    /// Pointer to the first byte. May be dangling (but not null) if `len` is zero.
+    pub ptr: *const u8,
+    /// Length in bytes.
+    pub len: usize,
+}
+
+impl CssStr {
+    fn new(s: &str) -> CssStr {
+        CssStr { ptr: s.as_ptr(), len: s.len() }
+    }
+}
+
+
+/// The kind of a `CssToken`. See `Token` for the meaning of each variant.
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[allow(missing_docs)]
+pub enum CssTokenKind {
+    Ident,
+    AtKeyword,
+    Hash,
+    IDHash,
+    QuotedString,
+    UnquotedUrl,
+    Delim,
+    Number,
+    Percentage,
+    Dimension,
+    UnicodeRange,
+    WhiteSpace,
+    Comment,
+    Colon,
+    Semicolon,
+    Comma,
+    IncludeMatch,
+    DashMatch,
+    PrefixMatch,
+    SuffixMatch,
+    SubstringMatch,
+    Column,
+    CDO,
+    CDC,
+    Function,
+    ParenthesisBlock,
+    SquareBracketBlock,
+    CurlyBracketBlock,
+    BadUrl,
+    BadString,
+    CloseParenthesis,
+    CloseSquareBracket,
+    CloseCurlyBracket,
+}
+
+
+/// A token, as passed to the `cssparser_tokenize` callback.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct CssToken {
+    /// The kind of token.
+    pub kind: CssTokenKind,
+    /// Byte offset of the start of the token.
+    pub start: usize,
+    /// Byte offset of the end of the token (exclusive).
+    pub end: usize,
+    /// The unescaped value of `Ident`, `AtKeyword`, `Hash`, `IDHash`, `QuotedString`,
+    /// `UnquotedUrl`, `WhiteSpace` and `Comment` tokens, the name of `Function` tokens,
+    /// or the unit of `Dimension` tokens. Empty for other tokens.
+    pub value: CssStr,
+    /// The value of `Number` and `Dimension` tokens,
+    /// or the value of `Percentage` tokens divided by 100.
+    pub number: f32,
+    /// Whether `int_value` is meaningful:
+    /// the numeric value did not have a fractional part in the source.
+    pub has_int_value: bool,
+    /// The integer value of `Number`, `Dimension` and `Percentage` tokens, if `has_int_value`.
+    /// (Not divided by 100 for percentages.)
+    pub int_value: i32,
+    /// Whether the number had a `+` or `-` sign.
+    pub has_sign: bool,
+    /// The code point of `Delim` tokens, or the start of `UnicodeRange` tokens.
+    pub code_point: u32,
+    /// The end of `UnicodeRange` tokens.
+    pub range_end: u32,
+}
+
+
+/// Called for each token by `cssparser_tokenize`.
+pub type CssTokenCallback = extern "C" fn(user_data: *mut c_void, token: *const CssToken);
+
+
+/// Tokenize the given UTF-8 input and call `callback` for each token, in order.
+///
+/// Whitespace and comments are included, and blocks are not matched:
+/// closing brackets are reported as they appear.
+///
+/// Return `false` without calling `callback` if the input is not valid UTF-8,
+/// and `false` if tokenizing panicked.
+///
+/// # Safety
+///
+/// `input` must point to `input_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn cssparser_tokenize(input: *const u8, input_len: usize,
+                                            callback: CssTokenCallback,
+                                            user_data: *mut c_void) -> bool {
+    catch_panic(|| {
+        let input = match input_str(input, input_len) {
+            Some(input) => input,
+            None => return false,
+        };
+        let mut tokenizer = Tokenizer::new(input);
+        loop {
+            let start = tokenizer.position().byte_index();
+            let token = match tokenizer.next() {
+                Ok(token) => token,
+                Err(_) => return true,
+            };
+            let end = tokenizer.position().byte_index();
+            let c_token = to_c_token(&token, start, end);
+            callback(user_data, &c_token);
+        }
+    })
+}
+
+
+/// The kind of a `CssColor`.
+#[cfg(feature = "color")]
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum CssColorKind {
+    /// The `currentColor` keyword. The channels are unspecified.
+    CurrentColor,
+    /// An RGBA color.
+    RGBA,
+}
+
+
+/// A parsed color, see `cssparser_parse_color`.
+#[cfg(feature = "color")]
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct CssColor {
+    /// Whether this is `currentColor`.
+    pub kind: CssColorKind,
+    /// The red channel. Nominally in 0.0 ... 1.0.
+    pub red: f32,
+    /// The green channel. Nominally in 0.0 ... 1.0.
+    pub green: f32,
+    /// The blue channel. Nominally in 0.0 ... 1.0.
+    pub blue: f32,
+    /// The alpha (opacity) channel. Clamped to 0.0 ... 1.0.
+    pub alpha: f32,
+}
+
+
+/// Parse the given UTF-8 input as a `<color>` value, and write the result to `*result`.
+///
+/// Return `false` and leave `*result` unchanged if the input is not valid UTF-8,
+/// is not a valid color, has other tokens after the color, or if parsing panicked.
+///
+/// # Safety
+///
+/// `input` must point to `input_len` readable bytes, and `result` to a writable `CssColor`.
+#[cfg(feature = "color")]
+#[no_mangle]
+pub unsafe extern "C" fn cssparser_parse_color(input: *const u8, input_len: usize,
+                                               result: *mut CssColor) -> bool {
+    catch_panic(|| {
+        let input = match input_str(input, input_len) {
+            Some(input) => input,
+            None => return false,
+        };
+        let color = match Parser::new(input).parse_entirely(Color::parse) {
+            Ok(color) => color,
+            Err(_) => return false,
+        };
+        *result = match color {
+            Color::CurrentColor => CssColor {
+                kind: CssColorKind::CurrentColor,
+                red: 0.,
+                green: 0.,
+                blue: 0.,
+                alpha: 1.,
+            },
+            Color::RGBA(rgba) => CssColor {
+                kind: CssColorKind::RGBA,
+                red: rgba.red,
+                green: rgba.green,
+                blue: rgba.blue,
+                alpha: rgba.alpha,
+            },
+        };
+        true
+    })
+}
+
+
+/// Called for each valid declaration by `cssparser_parse_declaration_list`.
+///
+/// `value` is the source text of the declaration value,
+/// without leading and trailing whitespace and without `!important`.
+pub type CssDeclarationCallback = extern "C" fn(user_data: *mut c_void, name: CssStr,
+                                                value: CssStr, important: bool);
+
+/// Called for each invalid declaration or at-rule by `cssparser_parse_declaration_list`,
+/// with the byte offsets of the start and end of the ignored input.
+pub type CssErrorCallback = extern "C" fn(user_data: *mut c_void, start: usize, end: usize);
+
+
+/// Parse the given UTF-8 input as a declaration list,
+/// such as the content of a `style` attribute or of a `{ … }` block.
+///
+/// `on_declaration` is called for each declaration, and `on_error` (if not null)
+/// for each declaration that could not be parsed.
+/// At-rules are not supported and reported as errors.
+///
+/// Return `false` without calling any callback if the input is not valid UTF-8,
+/// and `false` if parsing panicked.
+///
+/// # Safety
+///
+/// `input` must point to `input_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn cssparser_parse_declaration_list(input: *const u8, input_len: usize,
+                                                          on_declaration: CssDeclarationCallback,
+                                                          on_error: Option<CssErrorCallback>,
+                                                          user_data: *mut c_void) -> bool {
+    catch_panic(|| {
+        let input = match input_str(input, input_len) {
+            Some(input) => input,
+            None => return false,
+        };
+        let mut parser = Parser::new(input);
+        let declaration_parser = CallbackDeclarationParser {
+            on_declaration: on_declaration,
+            user_data: user_data,
+        };
+        for result in DeclarationListParser::new(&mut parser, declaration_parser) {
+            if let Err(error) = result {
+                if let Some(on_error) = on_error {
+                    on_error(user_data, byte_index(error.span.start), byte_index(error.span.end))
+                }
+            }
+        }
+        true
+    })
+}
+
+
+struct CallbackDeclarationParser {
+    on_declaration: CssDeclarationCallback,
+    user_data: *mut c_void,
+}
+
+impl DeclarationParser for CallbackDeclarationParser {
+    type Declaration = ();
+
+    fn parse_value<'i, 't>(&mut self, _context: &mut (), name: &str, input: &mut Parser<'i, 't>)
+                           -> Result<(), ParseError<'i>> {
+        let (value, important) = declaration_value_source(input);
+        (self.on_declaration)(self.user_data, CssStr::new(name), CssStr::new(value), important);
+        Ok(())
+    }
+}
+
+impl AtRuleParser for CallbackDeclarationParser {
+    type Prelude = ();
+    type AtRule = ();
+}
+
+
+/// Run `f`, and return `false` instead of unwinding into the caller if it panics.
+fn catch_panic<F: FnOnce() -> bool>(f: F) -> bool {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(false)
+}
+
+
+unsafe fn input_str<'a>(input: *const u8, input_len: usize) -> Option<&'a str> {
+    if input_len == 0 {
+        return Some("")
+    }
+    str::from_utf8(slice::from_raw_parts(input, input_len)).ok()
+}
+
+
+fn to_c_token(token: &Token, start: usize, end: usize) -> CssToken {
+    let mut c_token = CssToken {
+        kind: CssTokenKind::Delim,
+        start: start,
+        end: end,
+        value: CssStr::new(""),
+        number: 0.,
+        has_int_value: false,
+        int_value: 0,
+        has_sign: false,
+        code_point: 0,
+        range_end: 0,
+    };
+    c_token.kind = match *token {
+        Token::Ident(ref value) => { c_token.value = CssStr::new(value); CssTokenKind::Ident }
+        Token::AtKeyword(ref value) => { c_token.value = CssStr::new(value); CssTokenKind::AtKeyword }
+        Token::Hash(ref value) => { c_token.value = CssStr::new(value); CssTokenKind::Hash }
+        Token::IDHash(ref value) => { c_token.value = CssStr::new(value); CssTokenKind::IDHash }
+        Token::QuotedString(ref value) => {
+            c_token.value = CssStr::new(value);
+            CssTokenKind::QuotedString
+        }
+        Token::UnquotedUrl(ref value) => {
+            c_token.value = CssStr::new(value);
+            CssTokenKind::UnquotedUrl
+        }
+        Token::Delim(c) => { c_token.code_point = c as u32; CssTokenKind::Delim }
+        Token::Number(value) => {
+            c_token.number = value.value;
+            c_token.has_int_value = value.int_value.is_some();
+            c_token.int_value = value.int_value.unwrap_or(0);
+            c_token.has_sign = value.has_sign;
+            CssTokenKind::Number
+        }
+        Token::Percentage(value) => {
+            c_token.number = value.unit_value;
+            c_token.has_int_value = value.int_value.is_some();
+            c_token.int_value = value.int_value.unwrap_or(0);
+            c_token.has_sign = value.has_sign;
+            CssTokenKind::Percentage
+        }
+        Token::Dimension(value, ref unit) => {
+            c_token.number = value.value;
+            c_token.has_int_value = value.int_value.is_some();
+            c_token.int_value = value.int_value.unwrap_or(0);
+            c_token.has_sign = value.has_sign;
+            c_token.value = CssStr::new(unit);
+            CssTokenKind::Dimension
+        }
+        Token::UnicodeRange(start, end) => {
+            c_token.code_point = start;
+            c_token.range_end = end;
+            CssTokenKind::UnicodeRange
+        }
+        Token::WhiteSpace(ref value) => { c_token.value = CssStr::new(value); CssTokenKind::WhiteSpace }
+        Token::Comment(ref value) => { c_token.value = CssStr::new(value); CssTokenKind::Comment }
+        Token::Colon => CssTokenKind::Colon,
+        Token::Semicolon => CssTokenKind::Semicolon,
+        Token::Comma => CssTokenKind::Comma,
+        Token::IncludeMatch => CssTokenKind::IncludeMatch,
+        Token::DashMatch => CssTokenKind::DashMatch,
+        Token::PrefixMatch => CssTokenKind::PrefixMatch,
+        Token::SuffixMatch => CssTokenKind::SuffixMatch,
+        Token::SubstringMatch => CssTokenKind::SubstringMatch,
+        Token::Column => CssTokenKind::Column,
+        Token::CDO => CssTokenKind::CDO,
+        Token::CDC => CssTokenKind::CDC,
+        Token::Function(ref name) => { c_token.value = CssStr::new(name); CssTokenKind::Function }
+        Token::ParenthesisBlock => CssTokenKind::ParenthesisBlock,
+        Token::SquareBracketBlock => CssTokenKind::SquareBracketBlock,
+        Token::CurlyBracketBlock => CssTokenKind::CurlyBracketBlock,
+        Token::BadUrl => CssTokenKind::BadUrl,
+        Token::BadString => CssTokenKind::BadString,
+        Token::CloseParenthesis => CssTokenKind::CloseParenthesis,
+        Token::CloseSquareBracket => CssTokenKind::CloseSquareBracket,
+        Token::CloseCurlyBracket => CssTokenKind::CloseCurlyBracket,
+    };
+    c_token
+}
@@ -0,0 +1,276 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::borrow::Cow;
+use std::ops::Range;
+
+use tokenizer::{Tokenizer, Token};
+
+
+/// The number of code points after the end of a token that the tokenizer might look at
+/// to decide where the token ends, e.g. for `1e+5` or `<!--`.
+const MAX_LOOKAHEAD: usize = 3;
+
+
+/// Tokenize input that is not stored contiguously, such as a rope, given as an iterator of chunks.
+///
+/// `callback` is called for each token in order,
+/// with the byte range of the token in the whole input (the concatenation of all chunks).
+/// Tokens are the same as `Tokenizer` would give with the concatenated input,
+/// including those that span several chunks.
+///
+/// Only the text of tokens near chunk boundaries is copied:
+/// other tokens borrow from the chunks.
+pub fn tokenize_chunks<'a, I, F>(chunks: I, mut callback: F)
+where I: IntoIterator<Item=&'a str>, F: FnMut(Token, Range<usize>) {
+    let mut input = ChunkedInput {
+        chunks: chunks.into_iter(),
+        chunk: "",
+        window: Cow::Borrowed(""),
+        window_start: 0,
+        position: 0,
+        chunk_start_in_window: None,
+        at_end: false,
+    };
+    loop {
+        let token_end = {
+            let rest = &input.window[input.position..];
+            let mut tokenizer = Tokenizer::new(rest);
+            match tokenizer.next() {
+                Ok(token) => {
+                    let end = tokenizer.position().byte_index();
+                    // Unless enough input follows, the token might continue in the next chunk.
+                    if input.at_end || rest[end..].chars().nth(MAX_LOOKAHEAD - 1).is_some() {
+                        let start = input.window_start + input.position;
+                        callback(token, start..start + end);
+                        Some(end)
+                    } else {
+                        None
+                    }
+                }
+                Err(()) if input.at_end => return,
+                Err(_) => None,
+            }
+        };
+        match token_end {
+            Some(end) => input.advance(end),
+            None => input.extend(),
+        }
+    }
+}
+
+
+struct ChunkedInput<'a, I> {
+    chunks: I,
+    /// The last chunk obtained from `chunks`.
+    chunk: &'a str,
+    /// The input being tokenized: either a suffix of `chunk`,
+    /// or the end of previous chunks followed by a prefix of `chunk`.
+    window: Cow<'a, str>,
+    /// The byte index of the start of `window` in the whole input.
+    window_start: usize,
+    /// The byte index in `window` of the next token.
+    position: usize,
+    /// If `window` is a copy, where the prefix of `chunk` starts in it.
+    chunk_start_in_window: Option<usize>,
+    /// Whether `window` ends at the end of the whole input.
+    at_end: bool,
+}
+
+
+impl<'a, I> ChunkedInput<'a, I> where I: Iterator<Item=&'a str> {
+    fn advance(&mut self, token_length: usize) {
+        self.position += token_length;
+        // Go back to borrowing once everything copied from previous chunks has been tokenized.
+        if let Some(chunk_start) = self.chunk_start_in_window {
+            if self.position >= chunk_start {
+                self.window_start += self.position;
+                self.window = Cow::Borrowed(&self.chunk[self.position - chunk_start..]);
+                self.position = 0;
+                self.chunk_start_in_window = None;
+            }
+        }
+    }
+
+    /// Make more input available after the end of `window`.
+    fn extend(&mut self) {
+        if let Some(chunk_start) = self.chunk_start_in_window {
+            let copied = self.window.len() - chunk_start;
+            if copied < self.chunk.len() {
+                let end = prefix_length(self.chunk, copied * 2);
+                self.window.to_mut().push_str(&self.chunk[copied..end]);
+                return
+            }
+        }
+        let next_chunk = match self.chunks.next() {
+            Some(chunk) => chunk,
+            None => {
+                self.at_end = true;
+                return
+            }
+        };
+        let rest_length = self.window.len() - self.position;
+        self.window_start += self.position;
+        self.chunk = next_chunk;
+        if rest_length == 0 {
+            self.window = Cow::Borrowed(next_chunk);
+            self.chunk_start_in_window = None;
+        } else {
+            let mut window = self.window[self.position..].to_owned();
+            window.push_str(&next_chunk[..prefix_length(next_chunk, rest_length)]);
+            self.window = Cow::Owned(window);
+            self.chunk_start_in_window = Some(rest_length);
+        }
+        self.position = 0;
+    }
+}
+
+
+/// The length of a prefix of `chunk` to copy: at least `minimum` (and at least a few bytes)
+/// unless `chunk` is shorter, ending at a code point boundary.
+fn prefix_length(chunk: &str, minimum: usize) -> usize {
+    let mut length = ::std::cmp::max(minimum, 4 * MAX_LOOKAHEAD + 16);
+    if length >= chunk.len() {
+        return chunk.len()
+    }
+    while !chunk.is_char_boundary(length) {
+        length += 1
+    }
+    length
+}
+
+
+/// Split a stylesheet into top-level rules as its text becomes available in chunks,
+/// for example while it is being downloaded.
+///
+/// Each call to `feed` appends a chunk and passes every rule that is now complete
+/// to a callback, with the byte index of its text in the whole input.
+/// The text of a rule may include preceding whitespace and comments,
+/// and can be parsed with `parse_stylesheet` or a `RuleListParser`.
+/// When `feed` returns, the rest of the input is needed to find where the next rule ends.
+///
+/// Rules are split where `RuleListParser` would end them:
+/// after a top-level `;` for at-rules, or after the `{}` block of at-rules and qualified rules.
+#[derive(Clone, Debug, Default)]
+pub struct StylesheetStream {
+    /// The input after the last complete rule.
+    buffer: String,
+    /// The byte index of the start of `buffer` in the whole input.
+    buffer_start: usize,
+    /// The byte index in `buffer` of the end of the last token that was scanned.
+    scanned: usize,
+    rule: RuleState,
+}
+
+
+#[derive(Clone, Debug, Default)]
+struct RuleState {
+    /// `None` until the first token of a rule, then whether it is an at-rule.
+    is_at_rule: Option<bool>,
+    /// The blocks that are open, innermost last.
+    blocks: Vec<Block>,
+}
+
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Block {
+    Parenthesis,
+    SquareBracket,
+    CurlyBracket,
+}
+
+
+impl StylesheetStream {
+    /// Create a stream with no input yet.
+    pub fn new() -> StylesheetStream {
+        StylesheetStream::default()
+    }
+
+    /// Append a chunk of the input, and call `callback` for each rule that it completes.
+    pub fn feed<F>(&mut self, chunk: &str, mut callback: F) where F: FnMut(&str, usize) {
+        self.buffer.push_str(chunk);
+        self.scan(false, &mut callback)
+    }
+
+    /// Signal the end of the input, and call `callback` for the remaining rules.
+    /// The last one may be incomplete, such as with an unclosed block.
+    pub fn finish<F>(mut self, mut callback: F) where F: FnMut(&str, usize) {
+        self.scan(true, &mut callback);
+        if self.rule.is_at_rule.is_some() {
+            callback(&self.buffer, self.buffer_start)
+        }
+    }
+
+    /// The input that was fed after the last complete rule.
+    pub fn pending_input(&self) -> &str {
+        &self.buffer
+    }
+
+    fn scan<F>(&mut self, at_end: bool, callback: &mut F) where F: FnMut(&str, usize) {
+        loop {
+            let rule_ends = {
+                let rest = &self.buffer[self.scanned..];
+                let mut tokenizer = Tokenizer::new(rest);
+                let token = match tokenizer.next() {
+                    Ok(token) => token,
+                    Err(()) => return,
+                };
+                let end = tokenizer.position().byte_index();
+                // Unless enough input follows, the token might continue in the next chunk.
+                if !at_end && rest[end..].chars().nth(MAX_LOOKAHEAD - 1).is_none() {
+                    return
+                }
+                self.scanned += end;
+                self.rule.ends_after(&token)
+            };
+            if rule_ends {
+                callback(&self.buffer[..self.scanned], self.buffer_start);
+                self.buffer.drain(..self.scanned);
+                self.buffer_start += self.scanned;
+                self.scanned = 0;
+                self.rule = RuleState::default();
+            }
+        }
+    }
+}
+
+
+impl RuleState {
+    /// Take the next token into account, and return whether it ends the rule.
+    fn ends_after(&mut self, token: &Token) -> bool {
+        let top_level = self.blocks.is_empty();
+        match *token {
+            Token::WhiteSpace(_) | Token::Comment(_) |
+            Token::CDO | Token::CDC if self.is_at_rule.is_none() => return false,
+            Token::AtKeyword(_) if self.is_at_rule.is_none() => self.is_at_rule = Some(true),
+            _ if self.is_at_rule.is_none() => self.is_at_rule = Some(false),
+            _ => {}
+        }
+        let closing = match *token {
+            Token::Function(_) | Token::ParenthesisBlock => {
+                self.blocks.push(Block::Parenthesis);
+                return false
+            }
+            Token::SquareBracketBlock => {
+                self.blocks.push(Block::SquareBracket);
+                return false
+            }
+            Token::CurlyBracketBlock => {
+                self.blocks.push(Block::CurlyBracket);
+                return false
+            }
+            Token::Semicolon => return top_level && self.is_at_rule == Some(true),
+            Token::CloseParenthesis => Block::Parenthesis,
+            Token::CloseSquareBracket => Block::SquareBracket,
+            Token::CloseCurlyBracket => Block::CurlyBracket,
+            _ => return false
+        };
+        // Like `Parser`, ignore closing tokens that do not match the innermost block.
+        if self.blocks.last() == Some(&closing) {
+            self.blocks.pop();
+            return self.blocks.is_empty() && closing == Block::CurlyBracket
+        }
+        false
+    }
+}
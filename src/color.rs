@@ -4,7 +4,9 @@
 
 use std::fmt;
 
-use super::{Token, Parser, ToCss};
+use super::{Token, Parser, ParseError, ParseErrorKind, ToCss, SerializationOptions};
+use super::{NumberOrPercentage, Angle};
+use parser::unexpected_token;
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
@@ -52,17 +54,25 @@ known_heap_size!(0, RGBA);
 
 impl ToCss for RGBA {
     fn to_css<W>(&self, dest: &mut W) -> fmt::Result where W: fmt::Write {
+        self.to_css_with_options(&SerializationOptions::default(), dest)
+    }
+
+    fn to_css_with_options<W>(&self, options: &SerializationOptions, dest: &mut W)
+                              -> fmt::Result where W: fmt::Write {
+        let separator = if options.minify_whitespace { "," } else { ", " };
         if self.alpha == 1f32 {
-            write!(dest, "rgb({}, {}, {})",
+            write!(dest, "rgb({}{sep}{}{sep}{})",
                    (self.red * 255.).round(),
                    (self.green * 255.).round(),
-                   (self.blue * 255.).round())
+                   (self.blue * 255.).round(),
+                   sep = separator)
         } else {
-            write!(dest, "rgba({}, {}, {}, {})",
+            write!(dest, "rgba({}{sep}{}{sep}{}{sep}{})",
                    (self.red * 255.).round(),
                    (self.green * 255.).round(),
                    (self.blue * 255.).round(),
-                   self.alpha)
+                   self.alpha,
+                   sep = separator)
         }
     }
 }
@@ -72,13 +82,38 @@ impl ToCss for RGBA {
 pub enum Color {
     /// The 'currentColor' keyword
     CurrentColor,
-    /// Everything else gets converted to RGBA during parsing
+    /// Everything else gets converted to sRGB during parsing
     RGBA(RGBA),
 }
 
 #[cfg(feature = "heapsize")]
 known_heap_size!(0, Color);
 
+/// Colors are serialized as an `Option<RGBA>`, with `None` for `currentColor`.
+#[cfg(feature = "serde")]
+impl Serialize for Color {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), S::Error>
+        where S: Serializer
+    {
+        match *self {
+            Color::CurrentColor => None,
+            Color::RGBA(rgba) => Some(rgba),
+        }.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Deserialize for Color {
+    fn deserialize<D>(deserializer: &mut D) -> Result<Self, D::Error>
+        where D: Deserializer
+    {
+        Ok(match try!(Deserialize::deserialize(deserializer)) {
+            None => Color::CurrentColor,
+            Some(rgba) => Color::RGBA(rgba),
+        })
+    }
+}
+
 impl ToCss for Color {
     fn to_css<W>(&self, dest: &mut W) -> fmt::Result where W: fmt::Write {
         match self {
@@ -86,23 +121,36 @@ impl ToCss for Color {
             &Color::RGBA(rgba) => rgba.to_css(dest),
         }
     }
+
+    fn to_css_with_options<W>(&self, options: &SerializationOptions, dest: &mut W)
+                              -> fmt::Result where W: fmt::Write {
+        match self {
+            &Color::CurrentColor => dest.write_str("currentColor"),
+            &Color::RGBA(rgba) => rgba.to_css_with_options(options, dest),
+        }
+    }
 }
 
 impl Color {
-    /// Parse a <color> value, per CSS Color Module Level 3.
+    /// Parse a <color> value, per CSS Color Module Level 4.
+    ///
+    /// Colors given with `hwb()`, `lab()`, `lch()`, or `color()` are converted to sRGB,
+    /// and clamped to its gamut like with `rgb()`.
     ///
     /// FIXME(#2) Deprecated CSS2 System Colors are not supported yet.
-    pub fn parse(input: &mut Parser) -> Result<Color, ()> {
-        match try!(input.next()) {
-            Token::Hash(value) | Token::IDHash(value) => parse_color_hash(&*value),
-            Token::Ident(value) => parse_color_keyword(&*value),
-            Token::Function(name) => {
-                input.parse_nested_block(|arguments| {
+    pub fn parse<'i, 't>(input: &mut Parser<'i, 't>) -> Result<Color, ParseError<'i>> {
+        let start = input.position();
+        let token = try!(input.next());
+        match token {
+            Token::Hash(ref value) | Token::IDHash(ref value) => parse_color_hash(&*value),
+            Token::Ident(ref value) => parse_color_keyword(&*value),
+            Token::Function(ref name) => {
+                return input.parse_nested_block(|arguments| {
                     parse_color_function(&*name, arguments)
                 })
             }
             _ => Err(())
-        }
+        }.map_err(|()| unexpected_token(token, start))
     }
 }
 
@@ -133,163 +181,174 @@ fn rgba(red: f32, green: f32, blue: f32, alpha: f32) -> Result<Color, ()> {
 /// Matching is case-insensitive in the ASCII range.
 /// CSS escaping (if relevant) should be resolved before calling this function.
 /// (For example, the value of an `Ident` token is fine.)
-#[inline]
 pub fn parse_color_keyword(ident: &str) -> Result<Color, ()> {
-    match_ignore_ascii_case! { ident,
-        "black" => rgb(0., 0., 0.),
-        "silver" => rgb(192., 192., 192.),
-        "gray" => rgb(128., 128., 128.),
-        "white" => rgb(255., 255., 255.),
-        "maroon" => rgb(128., 0., 0.),
-        "red" => rgb(255., 0., 0.),
-        "purple" => rgb(128., 0., 128.),
-        "fuchsia" => rgb(255., 0., 255.),
-        "green" => rgb(0., 128., 0.),
-        "lime" => rgb(0., 255., 0.),
-        "olive" => rgb(128., 128., 0.),
-        "yellow" => rgb(255., 255., 0.),
-        "navy" => rgb(0., 0., 128.),
-        "blue" => rgb(0., 0., 255.),
-        "teal" => rgb(0., 128., 128.),
-        "aqua" => rgb(0., 255., 255.),
-
-        "aliceblue" => rgb(240., 248., 255.),
-        "antiquewhite" => rgb(250., 235., 215.),
-        "aquamarine" => rgb(127., 255., 212.),
-        "azure" => rgb(240., 255., 255.),
-        "beige" => rgb(245., 245., 220.),
-        "bisque" => rgb(255., 228., 196.),
-        "blanchedalmond" => rgb(255., 235., 205.),
-        "blueviolet" => rgb(138., 43., 226.),
-        "brown" => rgb(165., 42., 42.),
-        "burlywood" => rgb(222., 184., 135.),
-        "cadetblue" => rgb(95., 158., 160.),
-        "chartreuse" => rgb(127., 255., 0.),
-        "chocolate" => rgb(210., 105., 30.),
-        "coral" => rgb(255., 127., 80.),
-        "cornflowerblue" => rgb(100., 149., 237.),
-        "cornsilk" => rgb(255., 248., 220.),
-        "crimson" => rgb(220., 20., 60.),
-        "cyan" => rgb(0., 255., 255.),
-        "darkblue" => rgb(0., 0., 139.),
-        "darkcyan" => rgb(0., 139., 139.),
-        "darkgoldenrod" => rgb(184., 134., 11.),
-        "darkgray" => rgb(169., 169., 169.),
-        "darkgreen" => rgb(0., 100., 0.),
-        "darkgrey" => rgb(169., 169., 169.),
-        "darkkhaki" => rgb(189., 183., 107.),
-        "darkmagenta" => rgb(139., 0., 139.),
-        "darkolivegreen" => rgb(85., 107., 47.),
-        "darkorange" => rgb(255., 140., 0.),
-        "darkorchid" => rgb(153., 50., 204.),
-        "darkred" => rgb(139., 0., 0.),
-        "darksalmon" => rgb(233., 150., 122.),
-        "darkseagreen" => rgb(143., 188., 143.),
-        "darkslateblue" => rgb(72., 61., 139.),
-        "darkslategray" => rgb(47., 79., 79.),
-        "darkslategrey" => rgb(47., 79., 79.),
-        "darkturquoise" => rgb(0., 206., 209.),
-        "darkviolet" => rgb(148., 0., 211.),
-        "deeppink" => rgb(255., 20., 147.),
-        "deepskyblue" => rgb(0., 191., 255.),
-        "dimgray" => rgb(105., 105., 105.),
-        "dimgrey" => rgb(105., 105., 105.),
-        "dodgerblue" => rgb(30., 144., 255.),
-        "firebrick" => rgb(178., 34., 34.),
-        "floralwhite" => rgb(255., 250., 240.),
-        "forestgreen" => rgb(34., 139., 34.),
-        "gainsboro" => rgb(220., 220., 220.),
-        "ghostwhite" => rgb(248., 248., 255.),
-        "gold" => rgb(255., 215., 0.),
-        "goldenrod" => rgb(218., 165., 32.),
-        "greenyellow" => rgb(173., 255., 47.),
-        "grey" => rgb(128., 128., 128.),
-        "honeydew" => rgb(240., 255., 240.),
-        "hotpink" => rgb(255., 105., 180.),
-        "indianred" => rgb(205., 92., 92.),
-        "indigo" => rgb(75., 0., 130.),
-        "ivory" => rgb(255., 255., 240.),
-        "khaki" => rgb(240., 230., 140.),
-        "lavender" => rgb(230., 230., 250.),
-        "lavenderblush" => rgb(255., 240., 245.),
-        "lawngreen" => rgb(124., 252., 0.),
-        "lemonchiffon" => rgb(255., 250., 205.),
-        "lightblue" => rgb(173., 216., 230.),
-        "lightcoral" => rgb(240., 128., 128.),
-        "lightcyan" => rgb(224., 255., 255.),
-        "lightgoldenrodyellow" => rgb(250., 250., 210.),
-        "lightgray" => rgb(211., 211., 211.),
-        "lightgreen" => rgb(144., 238., 144.),
-        "lightgrey" => rgb(211., 211., 211.),
-        "lightpink" => rgb(255., 182., 193.),
-        "lightsalmon" => rgb(255., 160., 122.),
-        "lightseagreen" => rgb(32., 178., 170.),
-        "lightskyblue" => rgb(135., 206., 250.),
-        "lightslategray" => rgb(119., 136., 153.),
-        "lightslategrey" => rgb(119., 136., 153.),
-        "lightsteelblue" => rgb(176., 196., 222.),
-        "lightyellow" => rgb(255., 255., 224.),
-        "limegreen" => rgb(50., 205., 50.),
-        "linen" => rgb(250., 240., 230.),
-        "magenta" => rgb(255., 0., 255.),
-        "mediumaquamarine" => rgb(102., 205., 170.),
-        "mediumblue" => rgb(0., 0., 205.),
-        "mediumorchid" => rgb(186., 85., 211.),
-        "mediumpurple" => rgb(147., 112., 219.),
-        "mediumseagreen" => rgb(60., 179., 113.),
-        "mediumslateblue" => rgb(123., 104., 238.),
-        "mediumspringgreen" => rgb(0., 250., 154.),
-        "mediumturquoise" => rgb(72., 209., 204.),
-        "mediumvioletred" => rgb(199., 21., 133.),
-        "midnightblue" => rgb(25., 25., 112.),
-        "mintcream" => rgb(245., 255., 250.),
-        "mistyrose" => rgb(255., 228., 225.),
-        "moccasin" => rgb(255., 228., 181.),
-        "navajowhite" => rgb(255., 222., 173.),
-        "oldlace" => rgb(253., 245., 230.),
-        "olivedrab" => rgb(107., 142., 35.),
-        "orange" => rgb(255., 165., 0.),
-        "orangered" => rgb(255., 69., 0.),
-        "orchid" => rgb(218., 112., 214.),
-        "palegoldenrod" => rgb(238., 232., 170.),
-        "palegreen" => rgb(152., 251., 152.),
-        "paleturquoise" => rgb(175., 238., 238.),
-        "palevioletred" => rgb(219., 112., 147.),
-        "papayawhip" => rgb(255., 239., 213.),
-        "peachpuff" => rgb(255., 218., 185.),
-        "peru" => rgb(205., 133., 63.),
-        "pink" => rgb(255., 192., 203.),
-        "plum" => rgb(221., 160., 221.),
-        "powderblue" => rgb(176., 224., 230.),
-        "rebeccapurple" => rgb(102., 51., 153.),
-        "rosybrown" => rgb(188., 143., 143.),
-        "royalblue" => rgb(65., 105., 225.),
-        "saddlebrown" => rgb(139., 69., 19.),
-        "salmon" => rgb(250., 128., 114.),
-        "sandybrown" => rgb(244., 164., 96.),
-        "seagreen" => rgb(46., 139., 87.),
-        "seashell" => rgb(255., 245., 238.),
-        "sienna" => rgb(160., 82., 45.),
-        "skyblue" => rgb(135., 206., 235.),
-        "slateblue" => rgb(106., 90., 205.),
-        "slategray" => rgb(112., 128., 144.),
-        "slategrey" => rgb(112., 128., 144.),
-        "snow" => rgb(255., 250., 250.),
-        "springgreen" => rgb(0., 255., 127.),
-        "steelblue" => rgb(70., 130., 180.),
-        "tan" => rgb(210., 180., 140.),
-        "thistle" => rgb(216., 191., 216.),
-        "tomato" => rgb(255., 99., 71.),
-        "turquoise" => rgb(64., 224., 208.),
-        "violet" => rgb(238., 130., 238.),
-        "wheat" => rgb(245., 222., 179.),
-        "whitesmoke" => rgb(245., 245., 245.),
-        "yellowgreen" => rgb(154., 205., 50.),
-
-        "transparent" => Ok(Color::RGBA(RGBA { red: 0., green: 0., blue: 0., alpha: 0. })),
-        "currentcolor" => Ok(Color::CurrentColor),
-        _ => Err(())
+    macro_rules! rgb {
+        ($red: expr, $green: expr, $blue: expr) => {
+            Color::RGBA(RGBA {
+                red: $red / 255.,
+                green: $green / 255.,
+                blue: $blue / 255.,
+                alpha: 1.,
+            })
+        }
     }
+    ascii_case_insensitive_phf_map! {
+        keyword -> Color = {
+            "black" => rgb!(0., 0., 0.),
+            "silver" => rgb!(192., 192., 192.),
+            "gray" => rgb!(128., 128., 128.),
+            "white" => rgb!(255., 255., 255.),
+            "maroon" => rgb!(128., 0., 0.),
+            "red" => rgb!(255., 0., 0.),
+            "purple" => rgb!(128., 0., 128.),
+            "fuchsia" => rgb!(255., 0., 255.),
+            "green" => rgb!(0., 128., 0.),
+            "lime" => rgb!(0., 255., 0.),
+            "olive" => rgb!(128., 128., 0.),
+            "yellow" => rgb!(255., 255., 0.),
+            "navy" => rgb!(0., 0., 128.),
+            "blue" => rgb!(0., 0., 255.),
+            "teal" => rgb!(0., 128., 128.),
+            "aqua" => rgb!(0., 255., 255.),
+
+            "aliceblue" => rgb!(240., 248., 255.),
+            "antiquewhite" => rgb!(250., 235., 215.),
+            "aquamarine" => rgb!(127., 255., 212.),
+            "azure" => rgb!(240., 255., 255.),
+            "beige" => rgb!(245., 245., 220.),
+            "bisque" => rgb!(255., 228., 196.),
+            "blanchedalmond" => rgb!(255., 235., 205.),
+            "blueviolet" => rgb!(138., 43., 226.),
+            "brown" => rgb!(165., 42., 42.),
+            "burlywood" => rgb!(222., 184., 135.),
+            "cadetblue" => rgb!(95., 158., 160.),
+            "chartreuse" => rgb!(127., 255., 0.),
+            "chocolate" => rgb!(210., 105., 30.),
+            "coral" => rgb!(255., 127., 80.),
+            "cornflowerblue" => rgb!(100., 149., 237.),
+            "cornsilk" => rgb!(255., 248., 220.),
+            "crimson" => rgb!(220., 20., 60.),
+            "cyan" => rgb!(0., 255., 255.),
+            "darkblue" => rgb!(0., 0., 139.),
+            "darkcyan" => rgb!(0., 139., 139.),
+            "darkgoldenrod" => rgb!(184., 134., 11.),
+            "darkgray" => rgb!(169., 169., 169.),
+            "darkgreen" => rgb!(0., 100., 0.),
+            "darkgrey" => rgb!(169., 169., 169.),
+            "darkkhaki" => rgb!(189., 183., 107.),
+            "darkmagenta" => rgb!(139., 0., 139.),
+            "darkolivegreen" => rgb!(85., 107., 47.),
+            "darkorange" => rgb!(255., 140., 0.),
+            "darkorchid" => rgb!(153., 50., 204.),
+            "darkred" => rgb!(139., 0., 0.),
+            "darksalmon" => rgb!(233., 150., 122.),
+            "darkseagreen" => rgb!(143., 188., 143.),
+            "darkslateblue" => rgb!(72., 61., 139.),
+            "darkslategray" => rgb!(47., 79., 79.),
+            "darkslategrey" => rgb!(47., 79., 79.),
+            "darkturquoise" => rgb!(0., 206., 209.),
+            "darkviolet" => rgb!(148., 0., 211.),
+            "deeppink" => rgb!(255., 20., 147.),
+            "deepskyblue" => rgb!(0., 191., 255.),
+            "dimgray" => rgb!(105., 105., 105.),
+            "dimgrey" => rgb!(105., 105., 105.),
+            "dodgerblue" => rgb!(30., 144., 255.),
+            "firebrick" => rgb!(178., 34., 34.),
+            "floralwhite" => rgb!(255., 250., 240.),
+            "forestgreen" => rgb!(34., 139., 34.),
+            "gainsboro" => rgb!(220., 220., 220.),
+            "ghostwhite" => rgb!(248., 248., 255.),
+            "gold" => rgb!(255., 215., 0.),
+            "goldenrod" => rgb!(218., 165., 32.),
+            "greenyellow" => rgb!(173., 255., 47.),
+            "grey" => rgb!(128., 128., 128.),
+            "honeydew" => rgb!(240., 255., 240.),
+            "hotpink" => rgb!(255., 105., 180.),
+            "indianred" => rgb!(205., 92., 92.),
+            "indigo" => rgb!(75., 0., 130.),
+            "ivory" => rgb!(255., 255., 240.),
+            "khaki" => rgb!(240., 230., 140.),
+            "lavender" => rgb!(230., 230., 250.),
+            "lavenderblush" => rgb!(255., 240., 245.),
+            "lawngreen" => rgb!(124., 252., 0.),
+            "lemonchiffon" => rgb!(255., 250., 205.),
+            "lightblue" => rgb!(173., 216., 230.),
+            "lightcoral" => rgb!(240., 128., 128.),
+            "lightcyan" => rgb!(224., 255., 255.),
+            "lightgoldenrodyellow" => rgb!(250., 250., 210.),
+            "lightgray" => rgb!(211., 211., 211.),
+            "lightgreen" => rgb!(144., 238., 144.),
+            "lightgrey" => rgb!(211., 211., 211.),
+            "lightpink" => rgb!(255., 182., 193.),
+            "lightsalmon" => rgb!(255., 160., 122.),
+            "lightseagreen" => rgb!(32., 178., 170.),
+            "lightskyblue" => rgb!(135., 206., 250.),
+            "lightslategray" => rgb!(119., 136., 153.),
+            "lightslategrey" => rgb!(119., 136., 153.),
+            "lightsteelblue" => rgb!(176., 196., 222.),
+            "lightyellow" => rgb!(255., 255., 224.),
+            "limegreen" => rgb!(50., 205., 50.),
+            "linen" => rgb!(250., 240., 230.),
+            "magenta" => rgb!(255., 0., 255.),
+            "mediumaquamarine" => rgb!(102., 205., 170.),
+            "mediumblue" => rgb!(0., 0., 205.),
+            "mediumorchid" => rgb!(186., 85., 211.),
+            "mediumpurple" => rgb!(147., 112., 219.),
+            "mediumseagreen" => rgb!(60., 179., 113.),
+            "mediumslateblue" => rgb!(123., 104., 238.),
+            "mediumspringgreen" => rgb!(0., 250., 154.),
+            "mediumturquoise" => rgb!(72., 209., 204.),
+            "mediumvioletred" => rgb!(199., 21., 133.),
+            "midnightblue" => rgb!(25., 25., 112.),
+            "mintcream" => rgb!(245., 255., 250.),
+            "mistyrose" => rgb!(255., 228., 225.),
+            "moccasin" => rgb!(255., 228., 181.),
+            "navajowhite" => rgb!(255., 222., 173.),
+            "oldlace" => rgb!(253., 245., 230.),
+            "olivedrab" => rgb!(107., 142., 35.),
+            "orange" => rgb!(255., 165., 0.),
+            "orangered" => rgb!(255., 69., 0.),
+            "orchid" => rgb!(218., 112., 214.),
+            "palegoldenrod" => rgb!(238., 232., 170.),
+            "palegreen" => rgb!(152., 251., 152.),
+            "paleturquoise" => rgb!(175., 238., 238.),
+            "palevioletred" => rgb!(219., 112., 147.),
+            "papayawhip" => rgb!(255., 239., 213.),
+            "peachpuff" => rgb!(255., 218., 185.),
+            "peru" => rgb!(205., 133., 63.),
+            "pink" => rgb!(255., 192., 203.),
+            "plum" => rgb!(221., 160., 221.),
+            "powderblue" => rgb!(176., 224., 230.),
+            "rebeccapurple" => rgb!(102., 51., 153.),
+            "rosybrown" => rgb!(188., 143., 143.),
+            "royalblue" => rgb!(65., 105., 225.),
+            "saddlebrown" => rgb!(139., 69., 19.),
+            "salmon" => rgb!(250., 128., 114.),
+            "sandybrown" => rgb!(244., 164., 96.),
+            "seagreen" => rgb!(46., 139., 87.),
+            "seashell" => rgb!(255., 245., 238.),
+            "sienna" => rgb!(160., 82., 45.),
+            "skyblue" => rgb!(135., 206., 235.),
+            "slateblue" => rgb!(106., 90., 205.),
+            "slategray" => rgb!(112., 128., 144.),
+            "slategrey" => rgb!(112., 128., 144.),
+            "snow" => rgb!(255., 250., 250.),
+            "springgreen" => rgb!(0., 255., 127.),
+            "steelblue" => rgb!(70., 130., 180.),
+            "tan" => rgb!(210., 180., 140.),
+            "thistle" => rgb!(216., 191., 216.),
+            "tomato" => rgb!(255., 99., 71.),
+            "turquoise" => rgb!(64., 224., 208.),
+            "violet" => rgb!(238., 130., 238.),
+            "wheat" => rgb!(245., 222., 179.),
+            "whitesmoke" => rgb!(245., 245., 245.),
+            "yellowgreen" => rgb!(154., 205., 50.),
+
+            "transparent" => Color::RGBA(RGBA { red: 0., green: 0., blue: 0., alpha: 0. }),
+            "currentcolor" => Color::CurrentColor,
+        }
+    }
+    keyword(ident).cloned().ok_or(())
 }
 
 
@@ -335,84 +394,341 @@ fn parse_color_hash(value: &str) -> Result<Color, ()> {
 }
 
 
-#[inline]
-fn parse_color_function(name: &str, arguments: &mut Parser) -> Result<Color, ()> {
-    let (is_rgb, has_alpha) = match_ignore_ascii_case! { name,
-        "rgba" => (true, true),
-        "rgb" => (true, false),
-        "hsl" => (false, false),
-        "hsla" => (false, true),
-        _ => return Err(())
-    };
+fn parse_color_function<'i, 't>(name: &str, arguments: &mut Parser<'i, 't>)
+                                -> Result<Color, ParseError<'i>> {
+    let rgba = try!(match_ignore_ascii_case! { name,
+        "rgb" => parse_rgb(arguments),
+        "rgba" => parse_rgb(arguments),
+        "hsl" => parse_hsl(arguments),
+        "hsla" => parse_hsl(arguments),
+        "hwb" => parse_hwb(arguments),
+        "lab" => parse_lab(arguments),
+        "lch" => parse_lch(arguments),
+        "color" => parse_predefined_color(arguments),
+        _ => Err(arguments.new_error(ParseErrorKind::Invalid))
+    });
+    try!(arguments.expect_exhausted());
+    Ok(Color::RGBA(rgba))
+}
+
+
+fn clamp(value: f32) -> f32 {
+    value.max(0.).min(1.)
+}
 
-    fn clamp(val: f32) -> f32 {
-        val.max(0.).min(1.)
-    }
 
-    let mut red: f32;
-    let mut green: f32;
-    let mut blue: f32;
-    if is_rgb {
+/// https://drafts.csswg.org/css-color-4/#rgb-functions
+fn parse_rgb<'i, 't>(arguments: &mut Parser<'i, 't>) -> Result<RGBA, ParseError<'i>> {
+    fn channel(value: NumberOrPercentage) -> f32 {
+        match value {
+            NumberOrPercentage::Number(value) => value / 255.,
+            NumberOrPercentage::Percentage(value) => value,
+        }
+    }
+    let first = try!(arguments.expect_number_or_percentage());
+    let legacy = arguments.try(|arguments| arguments.expect_comma()).is_ok();
+    let (red, green, blue) = if legacy {
         // Either integers or percentages, but all the same type.
-        match try!(arguments.next()) {
-            Token::Number(ref v) if v.int_value.is_some() => {
-                red = v.value / 255.;
-                try!(arguments.expect_comma());
-                green = try!(arguments.expect_integer()) as f32 / 255.;
+        match first {
+            NumberOrPercentage::Number(red) => {
+                let green = try!(arguments.expect_number());
                 try!(arguments.expect_comma());
-                blue = try!(arguments.expect_integer()) as f32 / 255.;
+                (red / 255., green / 255., try!(arguments.expect_number()) / 255.)
             }
-            Token::Percentage(ref v) => {
-                red = v.unit_value;
+            NumberOrPercentage::Percentage(red) => {
+                let green = try!(arguments.expect_percentage());
                 try!(arguments.expect_comma());
-                green = try!(arguments.expect_percentage());
-                try!(arguments.expect_comma());
-                blue = try!(arguments.expect_percentage());
+                (red, green, try!(arguments.expect_percentage()))
             }
-            _ => return Err(())
-        };
-        // The spec says to clamp to the device gamut which may be wider than 0% ... 100%,
-        // but moz2d doesn’t seem to have any support for this, so let’s not bother.
-        // https://drafts.csswg.org/css-color/#rgb-functions
-        // https://github.com/servo/rust-cssparser/issues/76
-        red = clamp(red);
-        green = clamp(green);
-        blue = clamp(blue);
+        }
     } else {
-        let hue = try!(arguments.expect_number()) / 360.;
-        let hue = hue - hue.floor();
-        // Saturation and lightness are clamped to 0% ... 100%
-        // regardless of device gamut:
-        // https://drafts.csswg.org/css-color/#the-hsl-notation
-        try!(arguments.expect_comma());
-        let saturation = clamp(try!(arguments.expect_percentage()));
+        let green = try!(arguments.expect_number_or_percentage());
+        (channel(first), channel(green), channel(try!(arguments.expect_number_or_percentage())))
+    };
+    // The spec says to clamp to the device gamut which may be wider than 0% ... 100%,
+    // but moz2d doesn’t seem to have any support for this, so let’s not bother.
+    // https://drafts.csswg.org/css-color/#rgb-functions
+    // https://github.com/servo/rust-cssparser/issues/76
+    Ok(RGBA {
+        red: clamp(red),
+        green: clamp(green),
+        blue: clamp(blue),
+        alpha: try!(parse_alpha(arguments, legacy)),
+    })
+}
+
+
+/// https://drafts.csswg.org/css-color-4/#the-hsl-notation
+fn parse_hsl<'i, 't>(arguments: &mut Parser<'i, 't>) -> Result<RGBA, ParseError<'i>> {
+    let hue = try!(parse_hue(arguments));
+    let legacy = arguments.try(|arguments| arguments.expect_comma()).is_ok();
+    // Saturation and lightness are clamped to 0% ... 100% regardless of device gamut.
+    let (saturation, lightness) = if legacy {
+        let saturation = try!(arguments.expect_percentage());
         try!(arguments.expect_comma());
-        let lightness = clamp(try!(arguments.expect_percentage()));
+        (saturation, try!(arguments.expect_percentage()))
+    } else {
+        let saturation = try!(parse_percentage_or_number(arguments, 100.)) / 100.;
+        (saturation, try!(parse_percentage_or_number(arguments, 100.)) / 100.)
+    };
+    let (red, green, blue) = hsl_to_rgb(hue, clamp(saturation), clamp(lightness));
+    Ok(RGBA {
+        red: red,
+        green: green,
+        blue: blue,
+        alpha: try!(parse_alpha(arguments, legacy)),
+    })
+}
 
-        // https://drafts.csswg.org/css-color/#hsl-color
-        fn hue_to_rgb(m1: f32, m2: f32, mut h: f32) -> f32 {
-            if h < 0. { h += 1. }
-            if h > 1. { h -= 1. }
 
-            if h * 6. < 1. { m1 + (m2 - m1) * h * 6. }
-            else if h * 2. < 1. { m2 }
-            else if h * 3. < 2. { m1 + (m2 - m1) * (2. / 3. - h) * 6. }
-            else { m1 }
-        }
-        let m2 = if lightness <= 0.5 { lightness * (saturation + 1.) }
-                 else { lightness + saturation - lightness * saturation };
-        let m1 = lightness * 2. - m2;
-        red = hue_to_rgb(m1, m2, hue + 1. / 3.);
-        green = hue_to_rgb(m1, m2, hue);
-        blue = hue_to_rgb(m1, m2, hue - 1. / 3.);
+/// https://drafts.csswg.org/css-color-4/#the-hwb-notation
+fn parse_hwb<'i, 't>(arguments: &mut Parser<'i, 't>) -> Result<RGBA, ParseError<'i>> {
+    let hue = try!(parse_hue(arguments));
+    let whiteness = clamp(try!(parse_percentage_or_number(arguments, 100.)) / 100.);
+    let blackness = clamp(try!(parse_percentage_or_number(arguments, 100.)) / 100.);
+    let (red, green, blue) = if whiteness + blackness >= 1. {
+        let gray = whiteness / (whiteness + blackness);
+        (gray, gray, gray)
+    } else {
+        let (red, green, blue) = hsl_to_rgb(hue, 1., 0.5);
+        let scale = 1. - whiteness - blackness;
+        (red * scale + whiteness, green * scale + whiteness, blue * scale + whiteness)
+    };
+    Ok(RGBA {
+        red: red,
+        green: green,
+        blue: blue,
+        alpha: try!(parse_alpha(arguments, false)),
+    })
+}
+
+
+/// https://drafts.csswg.org/css-color-4/#specifying-lab-lch
+fn parse_lab<'i, 't>(arguments: &mut Parser<'i, 't>) -> Result<RGBA, ParseError<'i>> {
+    let lightness = try!(parse_percentage_or_number(arguments, 100.)).max(0.).min(100.);
+    let a = try!(parse_percentage_or_number(arguments, 125.));
+    let b = try!(parse_percentage_or_number(arguments, 125.));
+    let alpha = try!(parse_alpha(arguments, false));
+    Ok(from_xyz_d50(lab_to_xyz_d50(lightness, a, b), alpha))
+}
+
+
+/// https://drafts.csswg.org/css-color-4/#specifying-lab-lch
+fn parse_lch<'i, 't>(arguments: &mut Parser<'i, 't>) -> Result<RGBA, ParseError<'i>> {
+    let lightness = try!(parse_percentage_or_number(arguments, 100.)).max(0.).min(100.);
+    let chroma = try!(parse_percentage_or_number(arguments, 150.)).max(0.);
+    let hue = (try!(parse_hue(arguments)) as f64).to_radians();
+    let alpha = try!(parse_alpha(arguments, false));
+    let a = chroma as f64 * hue.cos();
+    let b = chroma as f64 * hue.sin();
+    Ok(from_xyz_d50(lab_to_xyz_d50(lightness, a as f32, b as f32), alpha))
+}
+
+
+/// The `color()` function with a predefined color space.
+///
+/// https://drafts.csswg.org/css-color-4/#color-function
+fn parse_predefined_color<'i, 't>(arguments: &mut Parser<'i, 't>)
+                                  -> Result<RGBA, ParseError<'i>> {
+    let start = arguments.position();
+    let space = try!(arguments.expect_ident());
+    let mut channels = [0.; 3];
+    for channel in &mut channels {
+        *channel = try!(parse_percentage_or_number(arguments, 1.)) as f64
     }
+    let alpha = try!(parse_alpha(arguments, false));
+    let (r, g, b) = (channels[0], channels[1], channels[2]);
+    let linear = match_ignore_ascii_case! { &*space,
+        "srgb" => return Ok(from_srgb([r, g, b], alpha)),
+        "srgb-linear" => [r, g, b],
+        "display-p3" => {
+            let linear_p3 = [linearize_srgb(r), linearize_srgb(g), linearize_srgb(b)];
+            let xyz = multiply(&P3_TO_XYZ_D65, linear_p3);
+            multiply(&XYZ_D65_TO_SRGB, xyz)
+        },
+        "a98-rgb" => {
+            let linearize = |value: f64| value.signum() * value.abs().powf(563. / 256.);
+            let xyz = multiply(&A98_TO_XYZ_D65, [linearize(r), linearize(g), linearize(b)]);
+            multiply(&XYZ_D65_TO_SRGB, xyz)
+        },
+        "prophoto-rgb" => {
+            let linearize = |value: f64| {
+                if value.abs() <= 16. / 512. { value / 16. }
+                else { value.signum() * value.abs().powf(1.8) }
+            };
+            let xyz = multiply(&PROPHOTO_TO_XYZ_D50, [linearize(r), linearize(g), linearize(b)]);
+            return Ok(from_xyz_d50(xyz, alpha))
+        },
+        "rec2020" => {
+            const ALPHA: f64 = 1.09929682680944;
+            const BETA: f64 = 0.018053968510807;
+            let linearize = |value: f64| {
+                if value.abs() < BETA * 4.5 { value / 4.5 }
+                else { value.signum() * ((value.abs() + ALPHA - 1.) / ALPHA).powf(1. / 0.45) }
+            };
+            let xyz = multiply(&REC2020_TO_XYZ_D65, [linearize(r), linearize(g), linearize(b)]);
+            multiply(&XYZ_D65_TO_SRGB, xyz)
+        },
+        "xyz" => multiply(&XYZ_D65_TO_SRGB, [r, g, b]),
+        "xyz-d65" => multiply(&XYZ_D65_TO_SRGB, [r, g, b]),
+        "xyz-d50" => return Ok(from_xyz_d50([r, g, b], alpha)),
+        _ => return Err(unexpected_token(Token::Ident(space.clone()), start))
+    };
+    Ok(from_linear_srgb(linear, alpha))
+}
 
-    let alpha = if has_alpha {
-        try!(arguments.expect_comma());
-        clamp(try!(arguments.expect_number()))
+
+/// Parse the optional alpha value after the other components:
+/// after a comma in the legacy syntax, or after a `/` delimiter otherwise.
+fn parse_alpha<'i, 't>(arguments: &mut Parser<'i, 't>, legacy: bool)
+                       -> Result<f32, ParseError<'i>> {
+    let has_alpha = if legacy {
+        arguments.try(|arguments| arguments.expect_comma()).is_ok()
     } else {
-        1.
+        arguments.try(|arguments| arguments.expect_delim('/')).is_ok()
     };
-    try!(arguments.expect_exhausted());
-    Ok(Color::RGBA(RGBA { red: red, green: green, blue: blue, alpha: alpha }))
+    if !has_alpha {
+        return Ok(1.)
+    }
+    Ok(clamp(try!(parse_percentage_or_number(arguments, 1.))))
+}
+
+
+/// A `<number>` or a `<percentage>`, where `100%` is the given number.
+fn parse_percentage_or_number<'i, 't>(arguments: &mut Parser<'i, 't>, hundred_percent: f32)
+                                      -> Result<f32, ParseError<'i>> {
+    Ok(match try!(arguments.expect_number_or_percentage()) {
+        NumberOrPercentage::Number(value) => value,
+        NumberOrPercentage::Percentage(value) => value * hundred_percent,
+    })
+}
+
+
+/// A `<hue>`: a `<number>` of degrees or an `<angle>`. Return the number of degrees.
+fn parse_hue<'i, 't>(arguments: &mut Parser<'i, 't>) -> Result<f32, ParseError<'i>> {
+    let start = arguments.position();
+    let token = try!(arguments.next());
+    match token {
+        Token::Number(value) => Ok(value.value),
+        Token::Dimension(value, ref unit) => Angle::from_dimension(value.value, unit).map(|a| a.0),
+        _ => Err(())
+    }.map_err(|()| unexpected_token(token, start))
+}
+
+
+/// https://drafts.csswg.org/css-color/#hsl-color
+fn hsl_to_rgb(hue: f32, saturation: f32, lightness: f32) -> (f32, f32, f32) {
+    fn hue_to_rgb(m1: f32, m2: f32, mut h: f32) -> f32 {
+        if h < 0. { h += 1. }
+        if h > 1. { h -= 1. }
+
+        if h * 6. < 1. { m1 + (m2 - m1) * h * 6. }
+        else if h * 2. < 1. { m2 }
+        else if h * 3. < 2. { m1 + (m2 - m1) * (2. / 3. - h) * 6. }
+        else { m1 }
+    }
+    let hue = hue / 360.;
+    let hue = hue - hue.floor();
+    let m2 = if lightness <= 0.5 { lightness * (saturation + 1.) }
+             else { lightness + saturation - lightness * saturation };
+    let m1 = lightness * 2. - m2;
+    (hue_to_rgb(m1, m2, hue + 1. / 3.), hue_to_rgb(m1, m2, hue), hue_to_rgb(m1, m2, hue - 1. / 3.))
+}
+
+
+// Conversions to sRGB, from the sample code of CSS Color 4:
+// https://drafts.csswg.org/css-color-4/#color-conversion-code
+// Colors outside of the sRGB gamut are clamped, like in `rgb()`.
+
+type Matrix = [[f64; 3]; 3];
+
+const XYZ_D65_TO_SRGB: Matrix = [
+    [3.2409699419045226, -1.537383177570094, -0.4986107602930034],
+    [-0.9692436362808796, 1.8759675015077202, 0.04155505740717559],
+    [0.05563007969699366, -0.20397695888897652, 1.0569715142428786],
+];
+
+/// Bradford chromatic adaptation from the D50 white point to D65.
+const XYZ_D50_TO_XYZ_D65: Matrix = [
+    [0.9554734527042182, -0.023098536874261423, 0.0632593086610217],
+    [-0.028369706963208136, 1.0099954580058226, 0.021041398966943008],
+    [0.012314001688319899, -0.020507696433477912, 1.3303659366080753],
+];
+
+const P3_TO_XYZ_D65: Matrix = [
+    [0.4865709486482162, 0.26566769316909306, 0.1982172852343625],
+    [0.2289745640697488, 0.6917385218365064, 0.079286914093745],
+    [0.0, 0.04511338185890264, 1.043944368900976],
+];
+
+const A98_TO_XYZ_D65: Matrix = [
+    [0.5766690429101305, 0.1855582379065463, 0.1882286462349947],
+    [0.29734497525053605, 0.6273635662554661, 0.07529145849399788],
+    [0.02703136138641234, 0.07068885253582723, 0.9913375368376388],
+];
+
+const PROPHOTO_TO_XYZ_D50: Matrix = [
+    [0.7977604896723027, 0.13518583717574031, 0.0313493495815248],
+    [0.2880711282292934, 0.7118432178101014, 0.00008565396060525902],
+    [0.0, 0.0, 0.8251046025104601],
+];
+
+const REC2020_TO_XYZ_D65: Matrix = [
+    [0.6369580483012914, 0.14461690358620832, 0.1688809751641721],
+    [0.2627002120112671, 0.6779980715188708, 0.05930171646986196],
+    [0.0, 0.028072693049087428, 1.060985057710791],
+];
+
+fn multiply(matrix: &Matrix, vector: [f64; 3]) -> [f64; 3] {
+    let row = |i: usize| {
+        matrix[i][0] * vector[0] + matrix[i][1] * vector[1] + matrix[i][2] * vector[2]
+    };
+    [row(0), row(1), row(2)]
+}
+
+fn lab_to_xyz_d50(lightness: f32, a: f32, b: f32) -> [f64; 3] {
+    const KAPPA: f64 = 24389. / 27.;
+    const EPSILON: f64 = 216. / 24389.;
+    const WHITE: [f64; 3] = [0.3457 / 0.3585, 1., (1. - 0.3457 - 0.3585) / 0.3585];
+    let (lightness, a, b) = (lightness as f64, a as f64, b as f64);
+    let f1 = (lightness + 16.) / 116.;
+    let f0 = a / 500. + f1;
+    let f2 = f1 - b / 200.;
+    let component = |f: f64| {
+        if f * f * f > EPSILON { f * f * f } else { (116. * f - 16.) / KAPPA }
+    };
+    let y = if lightness > KAPPA * EPSILON { f1 * f1 * f1 } else { lightness / KAPPA };
+    [component(f0) * WHITE[0], y * WHITE[1], component(f2) * WHITE[2]]
+}
+
+fn linearize_srgb(value: f64) -> f64 {
+    if value.abs() <= 0.04045 {
+        value / 12.92
+    } else {
+        value.signum() * ((value.abs() + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn from_xyz_d50(xyz: [f64; 3], alpha: f32) -> RGBA {
+    from_linear_srgb(multiply(&XYZ_D65_TO_SRGB, multiply(&XYZ_D50_TO_XYZ_D65, xyz)), alpha)
+}
+
+fn from_linear_srgb(linear: [f64; 3], alpha: f32) -> RGBA {
+    let gamma = |value: f64| {
+        if value.abs() > 0.0031308 {
+            value.signum() * (1.055 * value.abs().powf(1. / 2.4) - 0.055)
+        } else {
+            12.92 * value
+        }
+    };
+    from_srgb([gamma(linear[0]), gamma(linear[1]), gamma(linear[2])], alpha)
+}
+
+fn from_srgb(rgb: [f64; 3], alpha: f32) -> RGBA {
+    RGBA {
+        red: clamp(rgb[0] as f32),
+        green: clamp(rgb[1] as f32),
+        blue: clamp(rgb[2] as f32),
+        alpha: alpha,
+    }
 }
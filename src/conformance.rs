@@ -0,0 +1,297 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+// https://github.com/SimonSapin/css-parsing-tests
+
+use rustc_serialize::json::{Json, ToJson};
+
+use super::{Token, Parser, ParseError, NumericValue, PercentageValue, ToCss};
+use super::{DeclarationParser, AtRuleParser, QualifiedRuleParser, DeclarationListParser};
+use super::{RuleListParser, parse_one_declaration, parse_one_rule};
+
+
+macro_rules! JArray {
+    ($($e: expr,)*) => { JArray![ $( $e ),* ] };
+    ($($e: expr),*) => { Json::Array(vec!( $( $e.to_json() ),* )) }
+}
+
+
+/// A file of the css-parsing-tests suite that exercises rule and declaration parsers.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Fixture {
+    /// `declaration_list.json`, parsed with `DeclarationListParser`.
+    DeclarationList,
+    /// `one_declaration.json`, parsed with `parse_one_declaration`.
+    OneDeclaration,
+    /// `rule_list.json`, parsed with `RuleListParser::new_for_nested_rule`.
+    RuleList,
+    /// `one_rule.json`, parsed with `parse_one_rule`.
+    OneRule,
+    /// `stylesheet.json`, parsed with `RuleListParser::new_for_stylesheet`.
+    Stylesheet,
+}
+
+
+impl Fixture {
+    /// All fixtures, in the order `run_conformance_tests` runs them.
+    pub fn all() -> [Fixture; 5] {
+        [
+            Fixture::DeclarationList,
+            Fixture::OneDeclaration,
+            Fixture::RuleList,
+            Fixture::OneRule,
+            Fixture::Stylesheet,
+        ]
+    }
+
+    /// The JSON content of this fixture: alternating inputs and expected results.
+    pub fn json(self) -> &'static str {
+        match self {
+            Fixture::DeclarationList => include_str!("css-parsing-tests/declaration_list.json"),
+            Fixture::OneDeclaration => include_str!("css-parsing-tests/one_declaration.json"),
+            Fixture::RuleList => include_str!("css-parsing-tests/rule_list.json"),
+            Fixture::OneRule => include_str!("css-parsing-tests/one_rule.json"),
+            Fixture::Stylesheet => include_str!("css-parsing-tests/stylesheet.json"),
+        }
+    }
+}
+
+
+/// A test case of the css-parsing-tests suite whose result was not the expected one.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Mismatch {
+    /// The fixture that the test case is from.
+    pub fixture: Fixture,
+
+    /// The CSS input of the test case.
+    pub input: String,
+
+    /// The expected result.
+    pub expected: Json,
+
+    /// The actual result.
+    pub result: Json,
+}
+
+
+/// Run the css-parsing-tests fixtures for rule and declaration parsing with the given parser,
+/// and return the test cases whose result is not the expected one.
+///
+/// The results of the parser are converted with `ToJson`,
+/// and must match the JSON representation used by the test suite:
+///
+/// * `["declaration", name, value, important]` for declarations,
+/// * `["at-rule", name, prelude, block]` for at-rules, with a `null` block
+///   for at-rules that end with a semicolon,
+/// * `["qualified rule", prelude, block]` for qualified rules,
+///
+/// where values, preludes, and blocks are lists of component values
+/// as returned by `component_values_to_json`.
+/// Errors are represented by the runner.
+///
+/// Numbers are compared with a tolerance of 10⁻⁶.
+pub fn run_conformance_tests<P, R>(parser: &P) -> Vec<Mismatch>
+where P: Clone + DeclarationParser<Declaration = R> + AtRuleParser<AtRule = R> +
+         QualifiedRuleParser<QualifiedRule = R>,
+      R: ToJson {
+    let mut mismatches = Vec::new();
+    for &fixture in Fixture::all().iter() {
+        let items = match Json::from_str(fixture.json()) {
+            Ok(Json::Array(items)) => items,
+            _ => unreachable!()
+        };
+        for pair in items.chunks(2) {
+            let css = match pair[0] {
+                Json::String(ref css) => css,
+                _ => unreachable!()
+            };
+            let input = &mut Parser::new(css);
+            let result = match fixture {
+                Fixture::DeclarationList => {
+                    Json::Array(DeclarationListParser::new(input, parser.clone()).map(to_json)
+                                                                               .collect())
+                }
+                Fixture::OneDeclaration => to_json(parse_one_declaration(input,
+                                                                         &mut parser.clone())),
+                Fixture::RuleList => {
+                    Json::Array(RuleListParser::new_for_nested_rule(input, parser.clone())
+                                .map(to_json).collect())
+                }
+                Fixture::OneRule => to_json(parse_one_rule(input, &mut parser.clone())),
+                Fixture::Stylesheet => {
+                    Json::Array(RuleListParser::new_for_stylesheet(input, parser.clone())
+                                .map(to_json).collect())
+                }
+            };
+            let mut expected = pair[1].clone();
+            normalize(&mut expected);
+            if !almost_equals(&result, &expected) {
+                mismatches.push(Mismatch {
+                    fixture: fixture,
+                    input: css.clone(),
+                    expected: expected,
+                    result: result,
+                })
+            }
+        }
+    }
+    mismatches
+}
+
+
+fn to_json<R, E>(result: Result<R, E>) -> Json where R: ToJson {
+    result.map(|value| value.to_json()).unwrap_or_else(|_| JArray!["error", "invalid"])
+}
+
+
+/// Whether two JSON values are equal, with a tolerance for numbers.
+/// (Not exported outside this crate.)
+pub fn almost_equals(a: &Json, b: &Json) -> bool {
+    match (a, b) {
+        (&Json::I64(a), _) => almost_equals(&Json::F64(a as f64), b),
+        (&Json::U64(a), _) => almost_equals(&Json::F64(a as f64), b),
+        (_, &Json::I64(b)) => almost_equals(a, &Json::F64(b as f64)),
+        (_, &Json::U64(b)) => almost_equals(a, &Json::F64(b as f64)),
+
+        (&Json::F64(a), &Json::F64(b)) => (a - b).abs() < 1e-6,
+
+        (&Json::Boolean(a), &Json::Boolean(b)) => a == b,
+        (&Json::String(ref a), &Json::String(ref b)) => a == b,
+        (&Json::Array(ref a), &Json::Array(ref b)) => {
+            a.len() == b.len() &&
+            a.iter().zip(b.iter()).all(|(ref a, ref b)| almost_equals(*a, *b))
+        },
+        (&Json::Object(ref a), &Json::Object(ref b)) => {
+            a.len() == b.len() &&
+            a.iter().all(|(key, a)| b.get(key).map_or(false, |b| almost_equals(a, b)))
+        },
+        (&Json::Null, &Json::Null) => true,
+        _ => false,
+    }
+}
+
+
+/// Replace the kinds of errors that this crate does not distinguish
+/// in an expected result. (Not exported outside this crate.)
+pub fn normalize(json: &mut Json) {
+    match *json {
+        Json::Array(ref mut list) => {
+            for item in list.iter_mut() {
+                normalize(item)
+            }
+        }
+        Json::String(ref mut s) => {
+            if *s == "extra-input" || *s == "empty" {
+                *s = "invalid".to_string()
+            }
+        }
+        _ => {}
+    }
+}
+
+
+/// Consume the rest of the input, and return the JSON representation of its component values
+/// used by the css-parsing-tests suite.
+pub fn component_values_to_json(input: &mut Parser) -> Vec<Json> {
+    let mut values = vec![];
+    while let Ok(token) = input.next_including_whitespace() {
+        values.push(one_component_value_to_json(token, input));
+    }
+    values
+}
+
+
+/// Return the JSON representation of a component value used by the css-parsing-tests suite,
+/// given its first token.
+///
+/// For functions and blocks, the content is consumed from `input`.
+pub fn one_component_value_to_json(token: Token, input: &mut Parser) -> Json {
+    fn numeric(value: NumericValue) -> Vec<Json> {
+        vec![
+            Token::Number(value).to_css_string().to_json(),
+            match value.int_value { Some(i) => i.to_json(), None => value.value.to_json() },
+            match value.int_value { Some(_) => "integer", None => "number" }.to_json()
+        ]
+    }
+
+    fn nested(input: &mut Parser) -> Vec<Json> {
+        input.parse_nested_block(|input| {
+            Ok::<_, ParseError>(component_values_to_json(input))
+        }).unwrap()
+    }
+
+    match token {
+        Token::Ident(value) => JArray!["ident", value],
+        Token::AtKeyword(value) => JArray!["at-keyword", value],
+        Token::Hash(value) => JArray!["hash", value, "unrestricted"],
+        Token::IDHash(value) => JArray!["hash", value, "id"],
+        Token::QuotedString(value) => JArray!["string", value],
+        Token::UnquotedUrl(value) => JArray!["url", value],
+        Token::Delim('\\') => "\\".to_json(),
+        Token::Delim(value) => value.to_string().to_json(),
+
+        Token::Number(value) => Json::Array({
+            let mut v = vec!["number".to_json()];
+            v.extend(numeric(value));
+            v
+        }),
+        Token::Percentage(PercentageValue { unit_value, int_value, has_sign }) => Json::Array({
+            let mut v = vec!["percentage".to_json()];
+            v.extend(numeric(NumericValue {
+                value: unit_value * 100.,
+                int_value: int_value,
+                has_sign: has_sign,
+            }));
+            v
+        }),
+        Token::Dimension(value, unit) => Json::Array({
+            let mut v = vec!["dimension".to_json()];
+            v.extend(numeric(value));
+            v.push(unit.to_json());
+            v
+        }),
+
+        Token::UnicodeRange(start, end) => JArray!["unicode-range", start, end],
+
+        Token::WhiteSpace(_) => " ".to_json(),
+        Token::Comment(_) => "/**/".to_json(),
+        Token::Colon => ":".to_json(),
+        Token::Semicolon => ";".to_json(),
+        Token::Comma => ",".to_json(),
+        Token::IncludeMatch => "~=".to_json(),
+        Token::DashMatch => "|=".to_json(),
+        Token::PrefixMatch => "^=".to_json(),
+        Token::SuffixMatch => "$=".to_json(),
+        Token::SubstringMatch => "*=".to_json(),
+        Token::Column => "||".to_json(),
+        Token::CDO => "<!--".to_json(),
+        Token::CDC => "-->".to_json(),
+
+        Token::Function(name) => Json::Array({
+            let mut v = vec!["function".to_json(), name.to_json()];
+            v.extend(nested(input));
+            v
+        }),
+        Token::ParenthesisBlock => Json::Array({
+            let mut v = vec!["()".to_json()];
+            v.extend(nested(input));
+            v
+        }),
+        Token::SquareBracketBlock => Json::Array({
+            let mut v = vec!["[]".to_json()];
+            v.extend(nested(input));
+            v
+        }),
+        Token::CurlyBracketBlock => Json::Array({
+            let mut v = vec!["{}".to_json()];
+            v.extend(nested(input));
+            v
+        }),
+        Token::BadUrl => JArray!["error", "bad-url"],
+        Token::BadString => JArray!["error", "bad-string"],
+        Token::CloseParenthesis => JArray!["error", ")"],
+        Token::CloseSquareBracket => JArray!["error", "]"],
+        Token::CloseCurlyBracket => JArray!["error", "}"],
+    }
+}
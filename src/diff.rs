@@ -0,0 +1,228 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::ascii::AsciiExt;
+
+use ast::{ComponentValue, Node, Rule, BlockItem, Declaration, is_whitespace};
+use super::{Token, SourceRange};
+
+
+/// A difference between two trees built by `parse_stylesheet` or `parse_block_contents`,
+/// as found by `diff_stylesheets` or `diff_block_contents`.
+///
+/// Ranges are spans in the old input for removed items,
+/// and in the new input for added items.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Change {
+    /// A rule that is only in the new tree.
+    AddedRule(SourceRange),
+
+    /// A rule that is only in the old tree.
+    RemovedRule(SourceRange),
+
+    /// A declaration that is only in the new tree.
+    AddedDeclaration(SourceRange),
+
+    /// A declaration that is only in the old tree.
+    RemovedDeclaration(SourceRange),
+
+    /// A declaration that is in both trees, with a different value or `!important` flag.
+    ChangedDeclaration {
+        /// The span of the declaration in the old input.
+        old: SourceRange,
+        /// The span of the declaration in the new input.
+        new: SourceRange,
+    },
+}
+
+
+/// Compare two stylesheets, and return the rules and declarations
+/// that were added, removed, or changed.
+///
+/// Rules are matched by their at-keyword and prelude, and declarations by their name.
+/// When several rules (or declarations in the same block) match,
+/// they are paired in order.
+/// Nested blocks of matching rules are compared recursively.
+///
+/// Component values are compared token by token, ignoring comments,
+/// the amount of whitespace, whitespace around commas, and the way tokens are written:
+/// `.5` and `0.50` are the same number, and `\61` is the same identifier as `a`.
+/// Changes in the order of rules or declarations are not reported.
+pub fn diff_stylesheets(old: &[Rule], new: &[Rule]) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_rules(old, new, &mut changes);
+    changes
+}
+
+
+/// Compare the contents of two blocks, such as `style` attributes.
+/// See `diff_stylesheets`.
+pub fn diff_block_contents(old: &[BlockItem], new: &[BlockItem]) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_items(old, new, &mut changes);
+    changes
+}
+
+
+fn diff_rules(old: &[Rule], new: &[Rule], changes: &mut Vec<Change>) {
+    let old: Vec<&Rule> = old.iter().collect();
+    let new: Vec<&Rule> = new.iter().collect();
+    diff_rule_refs(&old, &new, changes)
+}
+
+
+fn diff_rule_refs(old: &[&Rule], new: &[&Rule], changes: &mut Vec<Change>) {
+    let pairs = match_in_order(old, new, |a, b| same_rule(a, b));
+    for (old_rule, pair) in old.iter().zip(&pairs) {
+        match *pair {
+            Some(index) => {
+                if let (Some(old_block), Some(new_block)) = (block(old_rule), block(new[index])) {
+                    diff_items(old_block, new_block, changes)
+                }
+            }
+            None => changes.push(Change::RemovedRule(old_rule.span())),
+        }
+    }
+    for (index, new_rule) in new.iter().enumerate() {
+        if !pairs.contains(&Some(index)) {
+            changes.push(Change::AddedRule(new_rule.span()))
+        }
+    }
+}
+
+
+fn diff_items(old: &[BlockItem], new: &[BlockItem], changes: &mut Vec<Change>) {
+    let old_declarations = declarations(old);
+    let new_declarations = declarations(new);
+    let pairs = match_in_order(&old_declarations, &new_declarations, |a, b| {
+        a.name.eq_ignore_ascii_case(&b.name)
+    });
+    for (old_declaration, pair) in old_declarations.iter().zip(&pairs) {
+        match *pair {
+            Some(index) => {
+                let new_declaration = new_declarations[index];
+                if old_declaration.important != new_declaration.important ||
+                   !equivalent(&old_declaration.value, &new_declaration.value) {
+                    changes.push(Change::ChangedDeclaration {
+                        old: old_declaration.span.clone(),
+                        new: new_declaration.span.clone(),
+                    })
+                }
+            }
+            None => changes.push(Change::RemovedDeclaration(old_declaration.span.clone())),
+        }
+    }
+    for (index, new_declaration) in new_declarations.iter().enumerate() {
+        if !pairs.contains(&Some(index)) {
+            changes.push(Change::AddedDeclaration(new_declaration.span.clone()))
+        }
+    }
+    diff_rule_refs(&rules(old), &rules(new), changes)
+}
+
+
+/// For each old item, the index of the matching new item if any.
+/// The n-th old item with a given key is paired with the n-th new item with the same key.
+fn match_in_order<T, F>(old: &[T], new: &[T], same: F) -> Vec<Option<usize>>
+where F: Fn(&T, &T) -> bool {
+    let mut used = vec![false; new.len()];
+    old.iter().map(|old_item| {
+        let index = new.iter().enumerate().position(|(index, new_item)| {
+            !used[index] && same(old_item, new_item)
+        });
+        if let Some(index) = index {
+            used[index] = true
+        }
+        index
+    }).collect()
+}
+
+
+fn declarations<'a, 'i>(items: &'a [BlockItem<'i>]) -> Vec<&'a Declaration<'i>> {
+    items.iter().filter_map(|item| match *item {
+        BlockItem::Declaration(ref declaration) => Some(declaration),
+        BlockItem::Rule(_) => None,
+    }).collect()
+}
+
+
+fn rules<'a, 'i>(items: &'a [BlockItem<'i>]) -> Vec<&'a Rule<'i>> {
+    items.iter().filter_map(|item| match *item {
+        BlockItem::Declaration(_) => None,
+        BlockItem::Rule(ref rule) => Some(rule),
+    }).collect()
+}
+
+
+fn block<'a, 'i>(rule: &'a Rule<'i>) -> Option<&'a [BlockItem<'i>]> {
+    match *rule {
+        Rule::QualifiedRule(ref rule) => Some(&rule.block),
+        Rule::AtRule(ref rule) => rule.block.as_ref().map(|block| &**block),
+    }
+}
+
+
+fn same_rule(a: &Rule, b: &Rule) -> bool {
+    match (a, b) {
+        (&Rule::QualifiedRule(ref a), &Rule::QualifiedRule(ref b)) => {
+            equivalent(&a.prelude, &b.prelude)
+        }
+        (&Rule::AtRule(ref a), &Rule::AtRule(ref b)) => {
+            a.name.eq_ignore_ascii_case(&b.name) && a.block.is_some() == b.block.is_some() &&
+            equivalent(&a.prelude, &b.prelude)
+        }
+        _ => false
+    }
+}
+
+
+/// Whether two lists of component values have the same tokens,
+/// where any whitespace is equivalent to any other whitespace
+/// and whitespace around commas is ignored.
+fn equivalent(a: &[Node], b: &[Node]) -> bool {
+    let a = significant(a);
+    let b = significant(b);
+    a.len() == b.len() && a.iter().zip(&b).all(|(a, b)| {
+        match (&a.value, &b.value) {
+            (&ComponentValue::Function(ref a_name, ref a_arguments),
+             &ComponentValue::Function(ref b_name, ref b_arguments)) => {
+                a_name.eq_ignore_ascii_case(b_name) && equivalent(a_arguments, b_arguments)
+            }
+            (&ComponentValue::ParenthesisBlock(ref a), &ComponentValue::ParenthesisBlock(ref b)) |
+            (&ComponentValue::SquareBracketBlock(ref a),
+             &ComponentValue::SquareBracketBlock(ref b)) |
+            (&ComponentValue::CurlyBracketBlock(ref a),
+             &ComponentValue::CurlyBracketBlock(ref b)) => equivalent(a, b),
+            (&ComponentValue::PreservedToken(_), &ComponentValue::PreservedToken(_)) => {
+                (is_whitespace(a) && is_whitespace(b)) || a.value == b.value
+            }
+            _ => false
+        }
+    })
+}
+
+
+/// Remove whitespace at the start and end and around commas.
+fn significant<'a, 'i>(nodes: &'a [Node<'i>]) -> Vec<&'a Node<'i>> {
+    let is_comma = |node: &Node| node.value == ComponentValue::PreservedToken(Token::Comma);
+    let mut result: Vec<&Node> = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        if is_whitespace(node) {
+            if result.last().map_or(true, |&previous| {
+                is_whitespace(previous) || is_comma(previous)
+            }) {
+                continue
+            }
+        } else if is_comma(node) && result.last().map_or(false, |&previous| {
+            is_whitespace(previous)
+        }) {
+            result.pop();
+        }
+        result.push(node)
+    }
+    if result.last().map_or(false, |&last| is_whitespace(last)) {
+        result.pop();
+    }
+    result
+}
@@ -0,0 +1,296 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::fmt;
+
+use ast::{ComponentValue, Node, Rule, BlockItem, Declaration};
+use parser::byte_index;
+use tokenizer::{Tokenizer, token_end};
+use super::{Token, Parser, parse_stylesheet};
+
+
+/// Options for `format_stylesheet`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FormatOptions {
+    /// The indentation for each level of nesting. Defaults to two spaces.
+    pub indent: String,
+
+    /// Whether to put the `{` of a rule on its own line
+    /// instead of at the end of the prelude. Defaults to `false`.
+    pub brace_on_new_line: bool,
+
+    /// Whether to put each selector of a selector list on its own line. Defaults to `true`.
+    pub selector_per_line: bool,
+
+    /// Whether to write a space after the `:` of declarations. Defaults to `true`.
+    pub space_after_colon: bool,
+
+    /// Whether to write a space after commas in preludes and declaration values
+    /// (other than selector lists with `selector_per_line`). Defaults to `true`.
+    pub space_after_comma: bool,
+
+    /// Whether to separate top-level rules with an empty line. Defaults to `true`.
+    pub blank_line_between_rules: bool,
+}
+
+
+impl Default for FormatOptions {
+    fn default() -> FormatOptions {
+        FormatOptions {
+            indent: "  ".to_owned(),
+            brace_on_new_line: false,
+            selector_per_line: true,
+            space_after_colon: true,
+            space_after_comma: true,
+            blank_line_between_rules: true,
+        }
+    }
+}
+
+
+/// Write the given stylesheet with consistent indentation and spacing.
+///
+/// Each rule, declaration, and comment between them is written on its own line,
+/// nested rules and declarations are indented, and declarations end with a `;` semicolon.
+/// Comments are kept, and so is invalid input that is skipped when parsing.
+///
+/// Within preludes and declaration values, only whitespace around top-level commas changes:
+/// the rest (including comments) is copied verbatim.
+/// The values of custom properties are always copied verbatim.
+pub fn format_stylesheet<W>(css: &str, options: &FormatOptions, dest: &mut W) -> fmt::Result
+                            where W: fmt::Write {
+    let rules = parse_stylesheet(&mut Parser::new(css));
+    let mut formatter = Formatter {
+        input: css,
+        options: options,
+        dest: dest,
+    };
+    let mut position = 0;
+    let mut previous_was_rule = false;
+    for rule in &rules {
+        let span = rule.span();
+        for piece in gap_pieces(&css[position..byte_index(span.start)], false, false) {
+            if previous_was_rule && options.blank_line_between_rules {
+                try!(formatter.dest.write_str("\n"))
+            }
+            try!(formatter.write_line(0, piece));
+            previous_was_rule = false;
+        }
+        if previous_was_rule && options.blank_line_between_rules {
+            try!(formatter.dest.write_str("\n"))
+        }
+        try!(formatter.write_rule(0, rule));
+        previous_was_rule = true;
+        position = byte_index(span.end);
+    }
+    for piece in gap_pieces(&css[position..], false, false) {
+        if previous_was_rule && options.blank_line_between_rules {
+            try!(formatter.dest.write_str("\n"))
+        }
+        try!(formatter.write_line(0, piece));
+        previous_was_rule = false;
+    }
+    Ok(())
+}
+
+
+struct Formatter<'i, 'o, 'w, W: 'w> {
+    input: &'i str,
+    options: &'o FormatOptions,
+    dest: &'w mut W,
+}
+
+
+impl<'i, 'o, 'w, W> Formatter<'i, 'o, 'w, W> where W: fmt::Write {
+    fn write_indent(&mut self, depth: usize) -> fmt::Result {
+        for _ in 0..depth {
+            try!(self.dest.write_str(&self.options.indent))
+        }
+        Ok(())
+    }
+
+    fn write_line(&mut self, depth: usize, text: &str) -> fmt::Result {
+        try!(self.write_indent(depth));
+        try!(self.dest.write_str(text));
+        self.dest.write_str("\n")
+    }
+
+    fn write_rule(&mut self, depth: usize, rule: &Rule) -> fmt::Result {
+        try!(self.write_indent(depth));
+        let (prelude, block, end) = match *rule {
+            Rule::QualifiedRule(ref rule) => {
+                try!(self.write_list(depth, &rule.prelude, self.options.selector_per_line));
+                (&rule.prelude, Some(&rule.block), byte_index(rule.span.end))
+            }
+            Rule::AtRule(ref rule) => {
+                let start = byte_index(rule.span.start);
+                try!(self.dest.write_str(&self.input[start..token_end(self.input, start)]));
+                if !rule.prelude.is_empty() {
+                    try!(self.dest.write_str(" "));
+                    try!(self.write_list(depth, &rule.prelude, false));
+                }
+                (&rule.prelude, rule.block.as_ref(), byte_index(rule.span.end))
+            }
+        };
+        let items = match block {
+            Some(items) => items,
+            None => return self.dest.write_str(";\n"),
+        };
+        let mut position = match (prelude.last(), rule) {
+            (Some(node), _) => byte_index(node.span.end),
+            (None, &Rule::AtRule(_)) => token_end(self.input, byte_index(rule.span().start)),
+            (None, &Rule::QualifiedRule(_)) => byte_index(rule.span().start),
+        };
+        if self.options.brace_on_new_line {
+            try!(self.dest.write_str("\n"));
+            try!(self.write_indent(depth));
+            try!(self.dest.write_str("{"));
+        } else {
+            if !prelude.is_empty() || matches!(*rule, Rule::AtRule(_)) {
+                try!(self.dest.write_str(" "));
+            }
+            try!(self.dest.write_str("{"));
+        }
+        let mut empty = true;
+        let mut after_open_brace = true;
+        for item in items {
+            let span = match *item {
+                BlockItem::Declaration(ref declaration) => declaration.span.clone(),
+                BlockItem::Rule(ref rule) => rule.span(),
+            };
+            let gap = &self.input[position..byte_index(span.start)];
+            for piece in gap_pieces(gap, after_open_brace, false) {
+                try!(self.start_block_line(&mut empty));
+                try!(self.write_line(depth + 1, piece));
+            }
+            try!(self.start_block_line(&mut empty));
+            match *item {
+                BlockItem::Declaration(ref declaration) => {
+                    try!(self.write_declaration(depth + 1, declaration))
+                }
+                BlockItem::Rule(ref rule) => try!(self.write_rule(depth + 1, rule)),
+            }
+            position = byte_index(span.end);
+            after_open_brace = false;
+        }
+        for piece in gap_pieces(&self.input[position..end], after_open_brace, true) {
+            try!(self.start_block_line(&mut empty));
+            try!(self.write_line(depth + 1, piece));
+        }
+        if !empty {
+            try!(self.write_indent(depth));
+        }
+        self.dest.write_str("}\n")
+    }
+
+    /// Before the first line in a block, end the line of the `{`.
+    fn start_block_line(&mut self, empty: &mut bool) -> fmt::Result {
+        if *empty {
+            *empty = false;
+            self.dest.write_str("\n")
+        } else {
+            Ok(())
+        }
+    }
+
+    fn write_declaration(&mut self, depth: usize, declaration: &Declaration) -> fmt::Result {
+        try!(self.write_indent(depth));
+        let start = byte_index(declaration.span.start);
+        try!(self.dest.write_str(&self.input[start..token_end(self.input, start)]));
+        try!(self.dest.write_str(":"));
+        if let (Some(first), Some(last)) = (declaration.value.first(), declaration.value.last()) {
+            if self.options.space_after_colon {
+                try!(self.dest.write_str(" "));
+            }
+            if declaration.name.starts_with("--") {
+                let value = &self.input[byte_index(first.span.start)..byte_index(last.span.end)];
+                try!(self.dest.write_str(value));
+            } else {
+                try!(self.write_list(depth, &declaration.value, false));
+            }
+        }
+        if declaration.important {
+            try!(self.dest.write_str(" !important"));
+        }
+        self.dest.write_str(";\n")
+    }
+
+    /// Write the source text of a list of component values,
+    /// with the formatting options applied to whitespace around top-level commas.
+    fn write_list(&mut self, depth: usize, nodes: &[Node], comma_newline: bool) -> fmt::Result {
+        let (first, last) = match (nodes.first(), nodes.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Ok(())
+        };
+        let mut start = byte_index(first.span.start);
+        for node in nodes {
+            if node.value == ComponentValue::PreservedToken(Token::Comma) {
+                try!(self.dest.write_str(self.input[start..byte_index(node.span.start)].trim()));
+                try!(self.dest.write_str(","));
+                if comma_newline {
+                    try!(self.dest.write_str("\n"));
+                    try!(self.write_indent(depth));
+                } else if self.options.space_after_comma {
+                    try!(self.dest.write_str(" "));
+                }
+                start = byte_index(node.span.end);
+            }
+        }
+        self.dest.write_str(self.input[start..byte_index(last.span.end)].trim())
+    }
+}
+
+
+/// Split input that was skipped by the parser between rules or declarations
+/// into comments and runs of other tokens (invalid rules or declarations),
+/// ignoring whitespace and semicolons that don’t end invalid input.
+///
+/// If `open_brace` is true, ignore the first `{` token, that starts a block.
+/// If `close_brace` is true, ignore a `}` token at the end, that ends a block.
+fn gap_pieces(gap: &str, open_brace: bool, close_brace: bool) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut tokenizer = Tokenizer::new(gap);
+    loop {
+        let start = tokenizer.position().byte_index();
+        match tokenizer.next() {
+            Ok(token) => tokens.push((token, start, tokenizer.position().byte_index())),
+            Err(_) => break,
+        }
+    }
+    if open_brace {
+        if let Some(index) = tokens.iter().position(|&(ref token, _, _)| {
+            *token == Token::CurlyBracketBlock
+        }) {
+            tokens.remove(index);
+        }
+    }
+    if close_brace && tokens.last().map_or(false, |&(ref token, _, end)| {
+        *token == Token::CloseCurlyBracket && end == gap.len()
+    }) {
+        tokens.pop();
+    }
+
+    let mut pieces = Vec::new();
+    let mut run: Option<(usize, usize)> = None;
+    for (token, start, end) in tokens {
+        match token {
+            Token::Comment(_) => {
+                if let Some((run_start, run_end)) = run.take() {
+                    pieces.push(&gap[run_start..run_end])
+                }
+                pieces.push(&gap[start..end])
+            }
+            Token::WhiteSpace(_) => {}
+            Token::Semicolon if run.is_none() => {}
+            _ => {
+                let run_start = run.map_or(start, |(run_start, _)| run_start);
+                run = Some((run_start, end))
+            }
+        }
+    }
+    if let Some((run_start, run_end)) = run {
+        pieces.push(&gap[run_start..run_end])
+    }
+    pieces
+}
@@ -5,8 +5,8 @@
 use std::cmp;
 
 use encoding::label::encoding_from_whatwg_label;
-use encoding::all::UTF_8;
-use encoding::{EncodingRef, DecoderTrap, decode};
+use encoding::all::{UTF_8, UTF_16BE, UTF_16LE};
+use encoding::{EncodingRef, DecoderTrap, RawDecoder};
 
 
 /// Determine the character encoding of a CSS stylesheet and decode it.
@@ -25,43 +25,158 @@ pub fn decode_stylesheet_bytes(css: &[u8], protocol_encoding_label: Option<&str>
                                environment_encoding: Option<EncodingRef>)
                             -> (String, EncodingRef) {
     // https://drafts.csswg.org/css-syntax/#the-input-byte-stream
-    match protocol_encoding_label {
-        None => (),
-        Some(label) => match encoding_from_whatwg_label(label) {
-            None => (),
-            Some(fallback) => return decode_replace(css, fallback)
+    let protocol_encoding = protocol_encoding_label.and_then(encoding_from_whatwg_label);
+    // With `is_complete`, this is never `None`.
+    let (encoding, bom_length) = determine_encoding(css, protocol_encoding,
+                                                    environment_encoding, true).unwrap();
+    (encoding.decode(&css[bom_length..], DecoderTrap::Replace).unwrap(), encoding)
+}
+
+
+/// An incremental version of `decode_stylesheet_bytes`,
+/// for bytes that arrive in chunks such as from the network.
+///
+/// The decoded text can be given to a `StylesheetStream` as it is produced.
+pub struct StylesheetDecoder {
+    protocol_encoding: Option<EncodingRef>,
+    environment_encoding: Option<EncodingRef>,
+    /// The bytes received before the encoding is determined.
+    pending: Vec<u8>,
+    decoder: Option<(Box<RawDecoder>, EncodingRef)>,
+    output: String,
+}
+
+
+impl StylesheetDecoder {
+    /// Create a decoder. The arguments are as for `decode_stylesheet_bytes`.
+    pub fn new(protocol_encoding_label: Option<&str>, environment_encoding: Option<EncodingRef>)
+               -> StylesheetDecoder {
+        StylesheetDecoder {
+            protocol_encoding: protocol_encoding_label.and_then(encoding_from_whatwg_label),
+            environment_encoding: environment_encoding,
+            pending: Vec::new(),
+            decoder: None,
+            output: String::new(),
         }
     }
-    if css.starts_with("@charset \"".as_bytes()) {
-        // 10 is "@charset \"".len()
-        // 100 is arbitrary so that no encoding label is more than 100-10 bytes.
-        match css[10..cmp::min(css.len(), 100)].iter().position(|&b| b == b'"') {
-            None => (),
-            Some(label_length)
-            => if css[10 + label_length..].starts_with("\";".as_bytes()) {
-                let label = &css[10..10 + label_length];
-                let label = label.iter().map(|&b| b as char).collect::<String>();
-                match encoding_from_whatwg_label(&*label) {
-                    None => (),
-                    Some(fallback) => match fallback.name() {
-                        "utf-16be" | "utf-16le"
-                        => return decode_replace(css, UTF_8 as EncodingRef),
-                        _ => return decode_replace(css, fallback),
-                    }
-                }
+
+    /// The encoding that is used, once it is determined.
+    ///
+    /// This needs the first few bytes of the stylesheet (up to 100 if it starts with `@charset`),
+    /// so it can be `None` until enough bytes are fed or `finish` is called.
+    pub fn encoding(&self) -> Option<EncodingRef> {
+        self.decoder.as_ref().map(|&(_, encoding)| encoding)
+    }
+
+    /// Decode the next chunk of bytes, and return the corresponding text.
+    ///
+    /// The text can be empty while the encoding is not determined yet,
+    /// or if the chunk ends in the middle of a character.
+    pub fn feed(&mut self, bytes: &[u8]) -> &str {
+        self.output.clear();
+        if self.decoder.is_some() {
+            self.decode(bytes)
+        } else {
+            self.pending.extend_from_slice(bytes);
+            self.start_decoding(false)
+        }
+        &self.output
+    }
+
+    /// Signal the end of the input, and return the rest of the text.
+    pub fn finish(&mut self) -> &str {
+        self.output.clear();
+        if self.decoder.is_none() {
+            self.start_decoding(true)
+        }
+        if let Some((ref mut decoder, _)) = self.decoder {
+            if decoder.raw_finish(&mut self.output).is_some() {
+                DecoderTrap::Replace.trap(&mut **decoder, &[], &mut self.output);
             }
         }
+        &self.output
     }
-    match environment_encoding {
-        None => (),
-        Some(fallback) => return decode_replace(css, fallback)
+
+    fn start_decoding(&mut self, is_complete: bool) {
+        let bytes = ::std::mem::replace(&mut self.pending, Vec::new());
+        let (encoding, bom_length) = match determine_encoding(&bytes, self.protocol_encoding,
+                                                              self.environment_encoding,
+                                                              is_complete) {
+            Some(result) => result,
+            None => {
+                self.pending = bytes;
+                return
+            }
+        };
+        self.decoder = Some((encoding.raw_decoder(), encoding));
+        self.decode(&bytes[bom_length..])
+    }
+
+    fn decode(&mut self, bytes: &[u8]) {
+        let decoder = &mut self.decoder.as_mut().unwrap().0;
+        let mut remaining = 0;
+        while remaining < bytes.len() {
+            let (offset, error) = decoder.raw_feed(&bytes[remaining..], &mut self.output);
+            let unprocessed = remaining + offset;
+            match error {
+                Some(error) => {
+                    remaining = cmp::max(remaining as isize + error.upto, unprocessed as isize)
+                                as usize;
+                    DecoderTrap::Replace.trap(&mut **decoder, &bytes[unprocessed..remaining],
+                                              &mut self.output);
+                }
+                None => break
+            }
+        }
     }
-    return decode_replace(css, UTF_8 as EncodingRef)
 }
 
 
-#[inline]
-fn decode_replace(input: &[u8], fallback_encoding: EncodingRef)-> (String, EncodingRef) {
-    let (result, used_encoding) = decode(input, DecoderTrap::Replace, fallback_encoding);
-    (result.unwrap(), used_encoding)
+/// Same as `decode_stylesheet_bytes`, but only determine the encoding
+/// and the length of the BOM to skip.
+///
+/// Return `None` if more bytes are needed, which is never the case when `is_complete`.
+fn determine_encoding(css: &[u8], protocol_encoding: Option<EncodingRef>,
+                      environment_encoding: Option<EncodingRef>, is_complete: bool)
+                      -> Option<(EncodingRef, usize)> {
+    // Like `encoding::decode`, a BOM takes precedence.
+    if css.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        return Some((UTF_8, 3))
+    } else if css.starts_with(&[0xFE, 0xFF]) {
+        return Some((UTF_16BE, 2))
+    } else if css.starts_with(&[0xFF, 0xFE]) {
+        return Some((UTF_16LE, 2))
+    } else if css.len() < 3 && !is_complete {
+        return None
+    }
+    if let Some(encoding) = protocol_encoding {
+        return Some((encoding, 0))
+    }
+    let prefix = b"@charset \"";
+    if css.starts_with(prefix) {
+        let search_end = cmp::min(css.len(), 100);
+        match css[10..search_end].iter().position(|&b| b == b'"') {
+            Some(label_length) => {
+                if css.len() < 10 + label_length + 2 && !is_complete {
+                    return None
+                }
+                if css[10 + label_length..].starts_with(b"\";") {
+                    let label = &css[10..10 + label_length];
+                    let label = label.iter().map(|&b| b as char).collect::<String>();
+                    if let Some(encoding) = encoding_from_whatwg_label(&*label) {
+                        return Some(match encoding.name() {
+                            "utf-16be" | "utf-16le" => (UTF_8 as EncodingRef, 0),
+                            _ => (encoding, 0)
+                        })
+                    }
+                }
+            }
+            None => if search_end < 100 && !is_complete {
+                return None
+            }
+        }
+    } else if prefix.starts_with(css) && !is_complete {
+        return None
+    }
+    Some((environment_encoding.unwrap_or(UTF_8), 0))
 }
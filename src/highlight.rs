@@ -0,0 +1,91 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::ops::Range;
+
+use tokenizer::Token;
+use tokens::{tokenize, Tokens};
+
+
+/// The highlighting class of a token, as returned by `highlight`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum HighlightClass {
+    /// Whitespace.
+    WhiteSpace,
+
+    /// A `/* … */` comment.
+    Comment,
+
+    /// A quoted string, including the quotes.
+    String,
+
+    /// A number, percentage, dimension (including the unit), or unicode-range.
+    Number,
+
+    /// An identifier, a hash (including the `#` marker),
+    /// or a function name (including the `(` marker).
+    Ident,
+
+    /// An at-keyword, including the `@` marker.
+    AtKeyword,
+
+    /// An unquoted `url(…)`, including the `url(` and `)` markers.
+    Url,
+
+    /// Delimiters, brackets, and other punctuation.
+    Punctuation,
+
+    /// A bad string or bad URL, which always indicates a parse error.
+    Error,
+}
+
+
+/// Split the input into tokens, for syntax highlighting.
+///
+/// The returned iterator yields consecutive byte ranges that together cover the whole input,
+/// each with its highlighting class.
+/// Since this is based on the tokenizer alone,
+/// unmatched closing brackets are classified as punctuation, not as errors.
+pub fn highlight(input: &str) -> Highlighter {
+    Highlighter {
+        tokens: tokenize(input),
+    }
+}
+
+
+/// An iterator of byte ranges and highlighting classes. See the `highlight` function.
+pub struct Highlighter<'i> {
+    tokens: Tokens<'i>,
+}
+
+
+impl<'i> Iterator for Highlighter<'i> {
+    type Item = (Range<usize>, HighlightClass);
+
+    fn next(&mut self) -> Option<(Range<usize>, HighlightClass)> {
+        self.tokens.next().map(|(token, range)| (range, classify(&token)))
+    }
+}
+
+
+fn classify(token: &Token) -> HighlightClass {
+    match *token {
+        Token::WhiteSpace(_) => HighlightClass::WhiteSpace,
+        Token::Comment(_) => HighlightClass::Comment,
+        Token::QuotedString(_) => HighlightClass::String,
+        Token::Number(_) | Token::Percentage(_) | Token::Dimension(..) |
+        Token::UnicodeRange(..) => HighlightClass::Number,
+        Token::Ident(_) | Token::Hash(_) | Token::IDHash(_) |
+        Token::Function(_) => HighlightClass::Ident,
+        Token::AtKeyword(_) => HighlightClass::AtKeyword,
+        Token::UnquotedUrl(_) => HighlightClass::Url,
+        Token::BadString | Token::BadUrl => HighlightClass::Error,
+        Token::Delim(_) | Token::Colon | Token::Semicolon | Token::Comma |
+        Token::IncludeMatch | Token::DashMatch | Token::PrefixMatch | Token::SuffixMatch |
+        Token::SubstringMatch | Token::Column | Token::CDO | Token::CDC |
+        Token::ParenthesisBlock | Token::SquareBracketBlock | Token::CurlyBracketBlock |
+        Token::CloseParenthesis | Token::CloseSquareBracket |
+        Token::CloseCurlyBracket => HighlightClass::Punctuation,
+    }
+}
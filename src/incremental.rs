@@ -0,0 +1,123 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use ast::{next_stylesheet_rule, Node, ComponentValue, Rule, BlockItem};
+use parser::{byte_index, offset_position};
+use super::{Parser, SourceRange};
+
+
+/// Update a tree built by `parse_stylesheet` after its input was edited,
+/// re-parsing only the rules affected by the edit.
+///
+/// `rules` is the result of parsing the input before the edit,
+/// in which the `edited` range was then replaced with `replacement` to give `new_input`.
+/// The result is the same as that of `parse_stylesheet` on `new_input`:
+/// rules before the edit are kept as-is,
+/// rules after it are kept with their spans moved to match the new input,
+/// and only the rules in-between are parsed again.
+///
+/// Since kept rules may borrow from the old input, it needs to live as long as the new one.
+///
+/// ```{rust,ignore}
+/// let rules = parse_stylesheet(&mut Parser::new(old_css));
+/// // In `old_css`, replace `range` with `text` to give `new_css`, then:
+/// let rules = reparse_stylesheet(rules, range, text, new_css);
+/// ```
+pub fn reparse_stylesheet<'i>(mut rules: Vec<Rule<'i>>, edited: SourceRange,
+                              replacement: &str, new_input: &'i str) -> Vec<Rule<'i>> {
+    let edit_start = byte_index(edited.start);
+    let edit_end = byte_index(edited.end);
+    debug_assert!(new_input[edit_start..].starts_with(replacement));
+    let delta = replacement.len() as isize - (edit_end - edit_start) as isize;
+
+    // A rule that ends right where the edit starts might continue into it,
+    // e.g. an at-rule without a semicolon at the end of the input.
+    let first_affected = rules.iter().position(|rule| byte_index(rule.span().end) >= edit_start)
+                              .unwrap_or(rules.len());
+    let mut old_rules = rules.split_off(first_affected).into_iter().peekable();
+
+    // Whitespace and comments between rules could be affected too,
+    // so start parsing right after the last unaffected rule.
+    let mut input = Parser::new(new_input);
+    let start_position = rules.last().map_or(input.position(), |rule| rule.span().end);
+    input.reset(start_position);
+    let mut diagnostics = Vec::new();
+    while let Some(rule) = next_stylesheet_rule(&mut input, &mut diagnostics) {
+        rules.push(rule);
+        let position = byte_index(input.position()) as isize;
+        // Old rules that end before this position have been replaced.
+        // If one that is entirely after the edit ends exactly there,
+        // the rest of the input is unchanged and would be parsed the same way.
+        loop {
+            let old_end = match old_rules.peek() {
+                Some(old_rule) => byte_index(old_rule.span().end),
+                None => break
+            };
+            if old_end < edit_end || (old_end as isize + delta) < position {
+                old_rules.next();
+                continue
+            }
+            if old_end as isize + delta == position {
+                old_rules.next();
+                for mut old_rule in old_rules {
+                    shift_rule(&mut old_rule, delta);
+                    rules.push(old_rule)
+                }
+                return rules
+            }
+            break
+        }
+    }
+    rules
+}
+
+
+fn shift_span(span: &mut SourceRange, delta: isize) {
+    *span = offset_position(span.start, delta)..offset_position(span.end, delta)
+}
+
+
+fn shift_rule(rule: &mut Rule, delta: isize) {
+    match *rule {
+        Rule::QualifiedRule(ref mut rule) => {
+            shift_nodes(&mut rule.prelude, delta);
+            shift_block(&mut rule.block, delta);
+            shift_span(&mut rule.span, delta);
+        }
+        Rule::AtRule(ref mut rule) => {
+            shift_nodes(&mut rule.prelude, delta);
+            if let Some(ref mut block) = rule.block {
+                shift_block(block, delta);
+            }
+            shift_span(&mut rule.span, delta);
+        }
+    }
+}
+
+
+fn shift_block(items: &mut [BlockItem], delta: isize) {
+    for item in items {
+        match *item {
+            BlockItem::Declaration(ref mut declaration) => {
+                shift_nodes(&mut declaration.value, delta);
+                shift_span(&mut declaration.span, delta);
+            }
+            BlockItem::Rule(ref mut rule) => shift_rule(rule, delta),
+        }
+    }
+}
+
+
+fn shift_nodes(nodes: &mut [Node], delta: isize) {
+    for node in nodes {
+        match node.value {
+            ComponentValue::PreservedToken(_) => {}
+            ComponentValue::Function(_, ref mut content) |
+            ComponentValue::ParenthesisBlock(ref mut content) |
+            ComponentValue::SquareBracketBlock(ref mut content) |
+            ComponentValue::CurlyBracketBlock(ref mut content) => shift_nodes(content, delta),
+        }
+        shift_span(&mut node.span, delta);
+    }
+}
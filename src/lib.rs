@@ -17,17 +17,48 @@ Everything is based on `Parser` objects, which borrow a `&str` input.
 If you have bytes (from a file, the network, or something),
 see the `decode_stylesheet_bytes` function.
 
+# Cargo features
+
+The `color` feature (for `Color`, `RGBA`, and `SyntaxDescriptor`)
+and the `nth` feature (for `parse_nth`) are enabled by default.
+Users that only need the tokenizer and parser can disable them
+with `default-features = false`.
+
+The `cache` feature enables `encode_tokens`, `encode_component_values`,
+and the corresponding decoding functions,
+for caching parsed stylesheets in a compact binary format.
+The `minify` feature enables the `minify` function.
+The `conformance` feature enables `run_conformance_tests`,
+which checks implementations of the rule and declaration parser traits
+against the [css-parsing-tests](https://github.com/SimonSapin/css-parsing-tests) suite.
+The `arena` feature enables `TokenArena`,
+for tokens that borrow their strings from a bump allocator.
+The `log` feature makes `DeclarationListParser`, `RuleListParser` and `RuleBodyParser`
+log a warning through the [`log`](https://crates.io/crates/log) crate
+for each invalid declaration or rule that they skip.
+The `render` feature enables `render_syntax_error` and `render_diagnostic`,
+which format errors with the relevant line of the source for command-line tools.
+The `wasm` feature enables the `wasm` module,
+with JavaScript bindings generated by [`wasm-bindgen`](https://crates.io/crates/wasm-bindgen).
+The `properties` feature enables `lookup_property` and `standard_properties`,
+a table of the names of standard CSS properties.
+The `invariants` feature enables internal consistency checks
+(tokens are non-empty, positions are on `char` boundaries,
+nested blocks end with their closing token)
+that panic when the parser state is corrupted, for example when fuzzing.
+
 # Conventions for parsing functions
 
-* Take (at least) a `input: &mut cssparser::Parser` parameter
-* Return `Result<_, ()>`
+* Take (at least) a `input: &mut cssparser::Parser<'i, 't>` parameter
+* Return `Result<_, cssparser::ParseError<'i>>`
 * When returning `Ok(_)`,
   the function must have consume exactly the amount of input that represents the parsed value.
-* When returning `Err(())`, any amount of input may have been consumed.
+* When returning `Err(_)`, any amount of input may have been consumed.
+  `Parser::new_error` creates an error at the current position.
 
 As a consequence, when calling another parsing function, either:
 
-* Any `Err(())` return value must be propagated.
+* Any `Err(_)` return value must be propagated.
   This happens by definition for tail calls,
   and can otherwise be done with the `try!` macro.
 * Or the call must be wrapped in a `Parser::try` call.
@@ -41,8 +72,8 @@ Examples:
 
 ```{rust,ignore}
 // 'none' | <image>
-fn parse_background_image(context: &ParserContext, input: &mut Parser)
-                                    -> Result<Option<Image>, ()> {
+fn parse_background_image<'i, 't>(context: &ParserContext, input: &mut Parser<'i, 't>)
+                                  -> Result<Option<Image>, ParseError<'i>> {
     if input.try(|input| input.expect_ident_matching("none")).is_ok() {
         Ok(None)
     } else {
@@ -53,8 +84,8 @@ fn parse_background_image(context: &ParserContext, input: &mut Parser)
 
 ```{rust,ignore}
 // [ <length> | <percentage> ] [ <length> | <percentage> ]?
-fn parse_border_spacing(_context: &ParserContext, input: &mut Parser)
-                          -> Result<(LengthOrPercentage, LengthOrPercentage), ()> {
+fn parse_border_spacing<'i, 't>(_context: &ParserContext, input: &mut Parser<'i, 't>)
+                                -> Result<(LengthOrPercentage, LengthOrPercentage), ParseError<'i>> {
     let first = try!(LengthOrPercentage::parse);
     let second = input.try(LengthOrPercentage::parse).unwrap_or(first);
     (first, second)
@@ -63,25 +94,72 @@ fn parse_border_spacing(_context: &ParserContext, input: &mut Parser)
 
 */
 
-#![recursion_limit="200"]  // For color::parse_color_keyword
-
 extern crate encoding;
 #[macro_use] extern crate matches;
+extern crate cssparser_macros;
+extern crate cssparser_phf_shared;
 #[cfg(test)] extern crate tempdir;
-#[cfg(test)] extern crate rustc_serialize;
+#[cfg(any(test, feature = "conformance"))] extern crate rustc_serialize;
 #[cfg(feature = "serde")] extern crate serde;
+#[cfg(all(test, feature = "serde"))] extern crate serde_json;
 #[cfg(feature = "heapsize")] #[macro_use] extern crate heapsize;
+#[cfg(feature = "log")] #[macro_use] extern crate log;
+#[cfg(feature = "wasm")] extern crate wasm_bindgen;
 
-pub use tokenizer::{Token, NumericValue, PercentageValue, SourceLocation};
-pub use rules_and_declarations::{parse_important};
+pub use tokenizer::{Token, NumericValue, PercentageValue, SourceLocation, would_start_identifier};
+pub use tokenizer::{SyntaxError, SyntaxErrorCode, UnknownFunction};
+pub use rules_and_declarations::{parse_important, CssWideKeyword};
 pub use rules_and_declarations::{DeclarationParser, DeclarationListParser, parse_one_declaration};
-pub use rules_and_declarations::{RuleListParser, parse_one_rule};
-pub use rules_and_declarations::{AtRuleType, QualifiedRuleParser, AtRuleParser};
-pub use from_bytes::decode_stylesheet_bytes;
-pub use color::{RGBA, Color, parse_color_keyword};
-pub use nth::parse_nth;
+pub use rules_and_declarations::{parse_one_declaration_str, parse_one_rule_str};
+pub use rules_and_declarations::{RuleListParser, RuleBodyParser, parse_one_rule};
+pub use rules_and_declarations::{AtRuleType, QualifiedRuleParser, AtRuleParser, InvalidItem};
+pub use rules_and_declarations::ItemError;
+pub use rules_and_declarations::{parse_one_declaration_with_context, parse_one_rule_with_context};
+pub use from_bytes::{decode_stylesheet_bytes, StylesheetDecoder};
+#[cfg(feature = "color")] pub use color::{RGBA, Color, parse_color_keyword};
+#[cfg(feature = "nth")] pub use nth::{Nth, parse_nth, parse_nth_str};
+pub use phf::PhfMap;
+#[doc(hidden)] pub use cssparser_macros::_cssparser_internal_phf_map;
 pub use serializer::{ToCss, CssStringWriter, serialize_identifier, serialize_string, TokenSerializationType};
-pub use parser::{Parser, Delimiter, Delimiters, SourcePosition};
+pub use serializer::{is_css_identifier, escape_identifier, assert_roundtrips, SerializationOptions};
+pub use parser::{Parser, Delimiter, Delimiters, SourcePosition, SourceRange, LineIndex};
+pub use parser::{ParserOptions, NumberOrPercentage, ParseError, ParseErrorKind};
+pub use ast::{ComponentValue, Node, parse_component_value_list, parse_one_component_value};
+pub use ast::{Rule, BlockItem, QualifiedRule, AtRule, Declaration};
+pub use ast::{parse_stylesheet, parse_block_contents};
+pub use ast::{Diagnostic, DiagnosticKind};
+pub use ast::{parse_stylesheet_with_diagnostics, parse_block_contents_with_diagnostics};
+pub use visit::{Visit, Walk};
+pub use rewrite::Rewriter;
+pub use incremental::reparse_stylesheet;
+pub use tokens::{tokenize, Tokens};
+pub use highlight::{highlight, Highlighter, HighlightClass};
+pub use calc::{CalcNode, parse_math_function};
+pub use variables::{substitute_var_functions, find_var_references, UnparsedValue, VarReference};
+pub use unicode_range::{UnicodeRange, parse_unicode_range_list};
+pub use supports::SupportsCondition;
+#[cfg(feature = "color")]
+pub use property_syntax::{SyntaxDescriptor, SyntaxComponent, SyntaxComponentName, SyntaxMultiplier};
+#[cfg(feature = "color")] pub use property_syntax::DataType;
+pub use numeric::{Number, Integer, Percentage};
+pub use units::{Unit, UnitKind, Angle, Time, Frequency, Resolution};
+pub use chunked::{tokenize_chunks, StylesheetStream};
+#[cfg(feature = "cache")]
+pub use cache::{encode_tokens, decode_tokens, encode_component_values, decode_component_values};
+#[cfg(feature = "minify")] pub use minify::minify;
+pub use format::{FormatOptions, format_stylesheet};
+pub use transform::{Transform, DeclarationTokens, RuleTokens};
+pub use diff::{Change, diff_stylesheets, diff_block_contents};
+pub use urls::{UrlReference, UrlContext, find_urls, rewrite_urls};
+pub use stats::{StylesheetStats, stylesheet_stats};
+#[cfg(feature = "arena")] pub use arena::TokenArena;
+#[cfg(feature = "render")] pub use render::{render_syntax_error, render_diagnostic};
+#[cfg(feature = "properties")]
+pub use properties::{PropertyInfo, lookup_property, standard_properties};
+#[cfg(feature = "conformance")]
+pub use conformance::{Fixture, Mismatch, run_conformance_tests};
+#[cfg(feature = "conformance")]
+pub use conformance::{component_values_to_json, one_component_value_to_json};
 
 
 /**
@@ -135,13 +213,95 @@ macro_rules! match_ignore_ascii_case {
     };
 }
 
+/**
+
+Define a function `$name(&str) -> Option<&'static $ValueType>`
+that looks up a key in a static map, ASCII case-insensitively.
+
+The map is generated at compile-time with a perfect hash function,
+so lookups take the same time for any number of keys,
+unlike with `match_ignore_ascii_case!`.
+Keys must be string literals without escapes,
+and values must be constant expressions.
+
+Usage example:
+
+```{rust,ignore}
+ascii_case_insensitive_phf_map! {
+    keyword -> Keyword = {
+        "foo" => Keyword::Foo,
+        "bar" => Keyword::Bar,
+    }
+}
+
+assert_eq!(keyword("FOO"), Some(&Keyword::Foo));
+```
+
+*/
+#[macro_export]
+macro_rules! ascii_case_insensitive_phf_map {
+    ($name: ident -> $ValueType: ty = { $( $key: tt => $value: expr ),+ }) => {
+        ascii_case_insensitive_phf_map!($name -> $ValueType = { $( $key => $value, )+ })
+    };
+    ($name: ident -> $ValueType: ty = { $( $key: tt => $value: expr, )+ }) => {
+        fn $name(input: &str) -> Option<&'static $ValueType> {
+            static MAP: $crate::PhfMap<$ValueType> = $crate::_cssparser_internal_phf_map! {
+                $crate; $( $key => $value, )+
+            };
+            MAP.get(input)
+        }
+    };
+}
+
+/// Panic if the given internal invariant does not hold,
+/// only when the `invariants` Cargo feature is enabled.
+macro_rules! check_invariant {
+    ($($assert_args: tt)+) => {
+        if cfg!(feature = "invariants") {
+            assert!($($assert_args)+)
+        }
+    }
+}
+
 mod rules_and_declarations;
 mod tokenizer;
 mod parser;
 mod from_bytes;
-mod color;
-mod nth;
+#[cfg(feature = "color")] mod color;
+#[cfg(feature = "nth")] mod nth;
 mod serializer;
+mod phf;
+mod ast;
+mod visit;
+mod rewrite;
+mod incremental;
+mod tokens;
+mod highlight;
+mod calc;
+mod variables;
+#[cfg(feature = "color")] mod property_syntax;
+mod numeric;
+mod units;
+mod chunked;
+#[cfg(feature = "cache")] mod cache;
+#[cfg(feature = "minify")] mod minify;
+mod format;
+mod transform;
+mod diff;
+mod urls;
+mod stats;
+mod unicode_range;
+mod supports;
+#[cfg(any(test, feature = "conformance"))] mod conformance;
+#[cfg(feature = "arena")] mod arena;
+#[cfg(feature = "render")] mod render;
+#[cfg(feature = "properties")] mod properties;
+
+#[cfg(feature = "capi")]
+pub mod capi;
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 #[cfg(test)]
 mod tests;
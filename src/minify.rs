@@ -0,0 +1,278 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::fmt;
+
+use ast::{ComponentValue, Node, Rule, BlockItem, Declaration, is_whitespace};
+use parser::byte_index;
+use super::{Token, Parser, ToCss, SerializationOptions, TokenSerializationType, parse_stylesheet};
+use super::serialize_identifier;
+
+
+/// Write a minified version of the given stylesheet.
+///
+/// * Comments are removed, and whitespace is removed or collapsed to a single space
+///   where it does not change how the stylesheet is parsed.
+/// * Invalid rules and declarations are removed, as well as style rules with an empty block
+///   and conditional group rules (`@media`, `@supports`, …) with an empty block.
+/// * In declaration values (except for custom properties, which are preserved as-is),
+///   numbers and hex colors are written in their shortest form,
+///   as with `SerializationOptions::minified`.
+///
+/// Other tokens are copied verbatim.
+/// Parsing the result gives the same rules and declarations as the input,
+/// except for what was removed.
+pub fn minify<W>(css: &str, dest: &mut W) -> fmt::Result where W: fmt::Write {
+    let rules = parse_stylesheet(&mut Parser::new(css));
+    let mut writer = Writer {
+        input: css,
+        dest: dest,
+        previous: TokenSerializationType::nothing(),
+        space: Space::None,
+        mode: Mode::Value,
+    };
+    writer.write_rules(&rules)
+}
+
+
+#[derive(Copy, Clone, PartialEq)]
+enum Space {
+    None,
+    /// Only written if the tokens would otherwise be parsed differently.
+    Optional,
+    /// Whitespace that was in the input and might be significant.
+    Significant,
+}
+
+
+#[derive(Copy, Clone, PartialEq)]
+enum Mode {
+    /// In a selector: whitespace around combinators can be removed.
+    Selector,
+    /// In a declaration value: numbers and colors can be shortened.
+    Value,
+    /// In an at-rule prelude.
+    Prelude,
+}
+
+
+struct Writer<'i, 'w, W: 'w> {
+    input: &'i str,
+    dest: &'w mut W,
+    previous: TokenSerializationType,
+    space: Space,
+    mode: Mode,
+}
+
+
+impl<'i, 'w, W> Writer<'i, 'w, W> where W: fmt::Write {
+    fn write_rules(&mut self, rules: &[Rule]) -> fmt::Result {
+        for rule in rules {
+            try!(self.write_rule(rule))
+        }
+        Ok(())
+    }
+
+    fn write_rule(&mut self, rule: &Rule) -> fmt::Result {
+        if is_removable(rule) {
+            return Ok(())
+        }
+        match *rule {
+            Rule::QualifiedRule(ref rule) => {
+                self.mode = Mode::Selector;
+                try!(self.write_nodes(&rule.prelude));
+                self.write_block(&rule.block)
+            }
+            Rule::AtRule(ref rule) => {
+                try!(self.write_token(&Token::AtKeyword(rule.name.clone())));
+                self.space = Space::Optional;
+                self.mode = Mode::Prelude;
+                try!(self.write_nodes(&rule.prelude));
+                match rule.block {
+                    Some(ref block) => self.write_block(block),
+                    None => self.write_structural(";"),
+                }
+            }
+        }
+    }
+
+    fn write_block(&mut self, items: &[BlockItem]) -> fmt::Result {
+        try!(self.write_structural("{"));
+        let mut needs_semicolon = false;
+        for item in items {
+            if let BlockItem::Rule(ref rule) = *item {
+                if is_removable(rule) {
+                    continue
+                }
+            }
+            if needs_semicolon {
+                try!(self.write_structural(";"))
+            }
+            match *item {
+                BlockItem::Declaration(ref declaration) => {
+                    try!(self.write_declaration(declaration));
+                    needs_semicolon = true;
+                }
+                BlockItem::Rule(ref rule) => {
+                    try!(self.write_rule(rule));
+                    needs_semicolon = false;
+                }
+            }
+        }
+        self.write_structural("}")
+    }
+
+    fn write_declaration(&mut self, declaration: &Declaration) -> fmt::Result {
+        try!(serialize_identifier(&declaration.name, self.dest));
+        try!(self.write_structural(":"));
+        if declaration.name.starts_with("--") {
+            if let (Some(first), Some(last)) = (declaration.value.first(),
+                                                declaration.value.last()) {
+                let value = &self.input[byte_index(first.span.start)..byte_index(last.span.end)];
+                try!(self.dest.write_str(value));
+                self.previous = TokenSerializationType::nothing();
+            }
+        } else {
+            self.mode = Mode::Value;
+            try!(self.write_nodes(&declaration.value));
+        }
+        if declaration.important {
+            try!(self.write_token(&Token::Delim('!')));
+            try!(self.write_token(&Token::Ident("important".into())));
+        }
+        Ok(())
+    }
+
+    /// Write a `{`, `}`, `;`, or `:` that is part of the structure of rules and declarations.
+    /// Whitespace around it is never needed.
+    fn write_structural(&mut self, text: &str) -> fmt::Result {
+        self.space = Space::None;
+        self.previous = TokenSerializationType::nothing();
+        self.dest.write_str(text)
+    }
+
+    fn write_nodes(&mut self, nodes: &[Node]) -> fmt::Result {
+        for node in nodes {
+            if is_whitespace(node) {
+                if self.space == Space::None && self.previous != TokenSerializationType::nothing() {
+                    self.space = Space::Significant
+                }
+                continue
+            }
+            match node.value {
+                ComponentValue::PreservedToken(ref token) => {
+                    try!(self.write_preserved_token(token, self.slice(node)))
+                }
+                ComponentValue::Function(ref name, ref arguments) => {
+                    try!(self.write_token(&Token::Function(name.clone())));
+                    try!(self.write_nested(arguments, ")"));
+                }
+                ComponentValue::ParenthesisBlock(ref content) => {
+                    try!(self.write_token(&Token::ParenthesisBlock));
+                    try!(self.write_nested(content, ")"));
+                }
+                ComponentValue::SquareBracketBlock(ref content) => {
+                    try!(self.write_token(&Token::SquareBracketBlock));
+                    try!(self.write_nested(content, "]"));
+                }
+                ComponentValue::CurlyBracketBlock(ref content) => {
+                    try!(self.write_token(&Token::CurlyBracketBlock));
+                    try!(self.write_nested(content, "}"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_nested(&mut self, content: &[Node], closing: &str) -> fmt::Result {
+        // Whitespace after an opening token is never needed.
+        self.previous = TokenSerializationType::nothing();
+        try!(self.write_nodes(content));
+        self.space = Space::None;
+        self.previous = Token::CloseParenthesis.serialization_type();
+        self.dest.write_str(closing)
+    }
+
+    fn slice(&self, node: &Node) -> &'i str {
+        &self.input[byte_index(node.span.start)..byte_index(node.span.end)]
+    }
+
+    /// Write a token that is not a function or block, given its source text.
+    fn write_preserved_token(&mut self, token: &Token, source: &str) -> fmt::Result {
+        let is_separator = match *token {
+            Token::Comma => true,
+            Token::Delim('>') | Token::Delim('+') | Token::Delim('~') => {
+                self.mode == Mode::Selector
+            }
+            _ => false
+        };
+        if is_separator && self.space == Space::Significant {
+            self.space = Space::Optional
+        }
+        try!(self.write_separator(token.serialization_type()));
+        let is_shortened = self.mode == Mode::Value && match *token {
+            Token::Number(_) | Token::Percentage(_) | Token::Dimension(..) |
+            Token::Hash(_) | Token::IDHash(_) => true,
+            _ => false
+        };
+        if is_shortened {
+            try!(token.to_css_with_options(&SerializationOptions::minified(), self.dest))
+        } else {
+            try!(self.dest.write_str(source))
+        }
+        if is_separator {
+            // Whitespace after the separator is not needed either.
+            self.space = Space::Optional
+        }
+        Ok(())
+    }
+
+    fn write_token(&mut self, token: &Token) -> fmt::Result {
+        try!(self.write_separator(token.serialization_type()));
+        token.to_css(self.dest)
+    }
+
+    /// Write whatever is needed between the previous token and the next one.
+    fn write_separator(&mut self, next: TokenSerializationType) -> fmt::Result {
+        let needs_separator = self.previous.needs_separator_when_before(next);
+        let space = self.space;
+        self.space = Space::None;
+        self.previous = next;
+        if space == Space::Significant || (space == Space::Optional && needs_separator) {
+            self.dest.write_str(" ")
+        } else if needs_separator {
+            // There was a comment in the input.
+            self.dest.write_str("/**/")
+        } else {
+            Ok(())
+        }
+    }
+}
+
+
+/// Whether a rule has no effect because its block is empty, ignoring removable nested rules.
+/// Only style rules and conditional group rules are considered.
+fn is_removable(rule: &Rule) -> bool {
+    let block = match *rule {
+        Rule::QualifiedRule(ref rule) => &rule.block,
+        Rule::AtRule(ref rule) => {
+            let is_conditional = match_ignore_ascii_case! { rule.name,
+                "media" => true,
+                "supports" => true,
+                "container" => true,
+                "document" => true,
+                _ => false
+            };
+            match rule.block {
+                Some(ref block) if is_conditional => block,
+                _ => return false
+            }
+        }
+    };
+    block.iter().all(|item| match *item {
+        BlockItem::Declaration(_) => false,
+        BlockItem::Rule(ref rule) => is_removable(rule),
+    })
+}
+
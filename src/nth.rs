@@ -3,62 +3,153 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use std::ascii::AsciiExt;
+use std::fmt;
 
-use super::{Token, Parser};
+use super::{Token, Parser, ParseError, ToCss};
+use parser::unexpected_token;
+
+
+/// An *An+B* value, as in the argument of the `:nth-child()` selector.
+///
+/// It converts from and into the `(A, B)` tuple returned by `parse_nth`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Nth {
+    /// The step, *A*.
+    pub a: i32,
+
+    /// The offset, *B*.
+    pub b: i32,
+}
+
+
+impl Nth {
+    /// Parse the *An+B* notation. See `parse_nth`.
+    pub fn parse<'i, 't>(input: &mut Parser<'i, 't>) -> Result<Nth, ParseError<'i>> {
+        parse_nth(input).map(Nth::from)
+    }
+}
+
+
+impl From<(i32, i32)> for Nth {
+    fn from((a, b): (i32, i32)) -> Nth {
+        Nth { a: a, b: b }
+    }
+}
+
+
+impl From<Nth> for (i32, i32) {
+    fn from(nth: Nth) -> (i32, i32) {
+        (nth.a, nth.b)
+    }
+}
+
+
+/// Serialize per https://drafts.csswg.org/css-syntax/#serializing-anb,
+/// except that `2n` and `2n+1` are written as the shorter `even` and `odd`.
+impl ToCss for Nth {
+    fn to_css<W>(&self, dest: &mut W) -> fmt::Result where W: fmt::Write {
+        match (self.a, self.b) {
+            (2, 0) => return dest.write_str("even"),
+            (2, 1) => return dest.write_str("odd"),
+            (0, b) => return write!(dest, "{}", b),
+            (1, _) => try!(dest.write_str("n")),
+            (-1, _) => try!(dest.write_str("-n")),
+            (a, _) => try!(write!(dest, "{}n", a)),
+        }
+        if self.b > 0 {
+            write!(dest, "+{}", self.b)
+        } else if self.b < 0 {
+            write!(dest, "{}", self.b)
+        } else {
+            Ok(())
+        }
+    }
+}
 
 
 /// Parse the *An+B* notation, as found in the `:nth-child()` selector.
 /// The input is typically the arguments of a function,
 /// in which case the caller needs to check if the arguments’ parser is exhausted.
-/// Return `Ok((A, B))`, or `Err(())` for a syntax error.
-pub fn parse_nth(input: &mut Parser) -> Result<(i32, i32), ()> {
-    match try!(input.next()) {
-        Token::Number(value) => Ok((0, try!(value.int_value.ok_or(())) as i32)),
-        Token::Dimension(value, unit) => {
-            let a = try!(value.int_value.ok_or(())) as i32;
-            match_ignore_ascii_case! { unit,
-                "n" => parse_b(input, a),
-                "n-" => parse_signless_b(input, a, -1),
-                _ => Ok((a, try!(parse_n_dash_digits(&*unit))))
+/// Return `Ok((A, B))`, or an error for a syntax error.
+pub fn parse_nth<'i, 't>(input: &mut Parser<'i, 't>) -> Result<(i32, i32), ParseError<'i>> {
+    let start = input.position();
+    let token = try!(input.next());
+    match token {
+        Token::Number(value) => {
+            if let Some(b) = value.int_value {
+                return Ok((0, b as i32))
             }
         }
-        Token::Ident(value) => {
+        Token::Dimension(value, ref unit) => {
+            if let Some(a) = value.int_value {
+                let a = a as i32;
+                match_ignore_ascii_case! { unit,
+                    "n" => return parse_b(input, a),
+                    "n-" => return parse_signless_b(input, a, -1),
+                    _ => if let Ok(b) = parse_n_dash_digits(&*unit) {
+                        return Ok((a, b))
+                    }
+                }
+            }
+        }
+        Token::Ident(ref value) => {
             match_ignore_ascii_case! { value,
-                "even" => Ok((2, 0)),
-                "odd" => Ok((2, 1)),
-                "n" => parse_b(input, 1),
-                "-n" => parse_b(input, -1),
-                "n-" => parse_signless_b(input, 1, -1),
-                "-n-" => parse_signless_b(input, -1, -1),
-                _ => if value.starts_with("-") {
-                    Ok((-1, try!(parse_n_dash_digits(&value[1..]))))
-                } else {
-                    Ok((1, try!(parse_n_dash_digits(&*value))))
+                "even" => return Ok((2, 0)),
+                "odd" => return Ok((2, 1)),
+                "n" => return parse_b(input, 1),
+                "-n" => return parse_b(input, -1),
+                "n-" => return parse_signless_b(input, 1, -1),
+                "-n-" => return parse_signless_b(input, -1, -1),
+                _ => {
+                    let (a, rest) = if value.starts_with("-") {
+                        (-1, &value[1..])
+                    } else {
+                        (1, &**value)
+                    };
+                    if let Ok(b) = parse_n_dash_digits(rest) {
+                        return Ok((a, b))
+                    }
                 }
             }
         }
-        Token::Delim('+') => match try!(input.next_including_whitespace()) {
-            Token::Ident(value) => {
+        Token::Delim('+') => {
+            let start = input.position();
+            let token = try!(input.next_including_whitespace());
+            if let Token::Ident(ref value) = token {
                 match_ignore_ascii_case! { value,
-                    "n" => parse_b(input, 1),
-                    "n-" => parse_signless_b(input, 1, -1),
-                    _ => Ok((1, try!(parse_n_dash_digits(&*value))))
+                    "n" => return parse_b(input, 1),
+                    "n-" => return parse_signless_b(input, 1, -1),
+                    _ => if let Ok(b) = parse_n_dash_digits(&*value) {
+                        return Ok((1, b))
+                    }
                 }
             }
-            _ => Err(())
-        },
-        _ => Err(())
+            return Err(unexpected_token(token, start))
+        }
+        _ => {}
     }
+    Err(unexpected_token(token, start))
 }
 
 
-fn parse_b(input: &mut Parser, a: i32) -> Result<(i32, i32), ()> {
+/// Parse the *An+B* notation from the given string,
+/// such as the arguments of `:nth-child()` without `of S`.
+/// Return an error if the string has other tokens after *An+B*.
+pub fn parse_nth_str(css: &str) -> Result<(i32, i32), ParseError> {
+    Parser::new(css).parse_entirely(parse_nth)
+}
+
+
+fn parse_b<'i, 't>(input: &mut Parser<'i, 't>, a: i32) -> Result<(i32, i32), ParseError<'i>> {
     let start_position = input.position();
     match input.next() {
         Ok(Token::Delim('+')) => parse_signless_b(input, a, 1),
         Ok(Token::Delim('-')) => parse_signless_b(input, a, -1),
-        Ok(Token::Number(ref value)) if value.has_sign => {
-            Ok((a, try!(value.int_value.ok_or(())) as i32))
+        Ok(Token::Number(value)) if value.has_sign => {
+            match value.int_value {
+                Some(b) => Ok((a, b as i32)),
+                None => Err(unexpected_token(Token::Number(value), start_position)),
+            }
         }
         _ => {
             input.reset(start_position);
@@ -67,13 +158,19 @@ fn parse_b(input: &mut Parser, a: i32) -> Result<(i32, i32), ()> {
     }
 }
 
-fn parse_signless_b(input: &mut Parser, a: i32, b_sign: i32) -> Result<(i32, i32), ()> {
-    match try!(input.next()) {
+fn parse_signless_b<'i, 't>(input: &mut Parser<'i, 't>, a: i32, b_sign: i32)
+                            -> Result<(i32, i32), ParseError<'i>> {
+    let start = input.position();
+    let token = try!(input.next());
+    match token {
         Token::Number(ref value) if !value.has_sign => {
-            Ok((a, b_sign * (try!(value.int_value.ok_or(())) as i32)))
+            if let Some(b) = value.int_value {
+                return Ok((a, b_sign * b as i32))
+            }
         }
-        _ => Err(())
+        _ => {}
     }
+    Err(unexpected_token(token, start))
 }
 
 fn parse_n_dash_digits(string: &str) -> Result<i32, ()> {
@@ -0,0 +1,178 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+// https://drafts.csswg.org/css-values/#numeric-types
+
+use std::fmt;
+
+use super::{Token, Parser, ParseError, NumericValue, PercentageValue, ToCss};
+use super::SerializationOptions;
+use parser::unexpected_token;
+
+
+/// A `<number>` value, parsed from a `Number` token.
+///
+/// The value is kept as in the token, so that it serializes like the source
+/// (`2` and `2.0` are different, as are `1` and `+1`).
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Number(pub NumericValue);
+
+
+impl Number {
+    /// Parse a `Number` token.
+    ///
+    /// Math functions are not supported.
+    pub fn parse<'i, 't>(input: &mut Parser<'i, 't>) -> Result<Number, ParseError<'i>> {
+        let start = input.position();
+        match try!(input.next()) {
+            Token::Number(value) => Ok(Number(value)),
+            token => Err(unexpected_token(token, start))
+        }
+    }
+
+    /// The value as a float.
+    #[inline]
+    pub fn value(self) -> f32 {
+        self.0.value
+    }
+
+    /// The value, if it is not negative.
+    pub fn non_negative(self) -> Result<f32, ()> {
+        if self.0.value >= 0. { Ok(self.0.value) } else { Err(()) }
+    }
+
+    /// The value, if it is in the given inclusive range.
+    pub fn in_range(self, min: f32, max: f32) -> Result<f32, ()> {
+        if min <= self.0.value && self.0.value <= max { Ok(self.0.value) } else { Err(()) }
+    }
+
+    /// Convert to an `Integer` if the source did not include a fractional part.
+    ///
+    /// Per spec, `2.0` or `2e0` is a `<number>` but not an `<integer>`.
+    pub fn to_integer(self) -> Result<Integer, ()> {
+        match self.0.int_value {
+            Some(_) => Ok(Integer(self.0)),
+            None => Err(())
+        }
+    }
+}
+
+
+impl ToCss for Number {
+    fn to_css<W>(&self, dest: &mut W) -> fmt::Result where W: fmt::Write {
+        Token::Number(self.0).to_css(dest)
+    }
+
+    fn to_css_with_options<W>(&self, options: &SerializationOptions, dest: &mut W)
+                              -> fmt::Result where W: fmt::Write {
+        Token::Number(self.0).to_css_with_options(options, dest)
+    }
+}
+
+
+/// An `<integer>` value, parsed from a `Number` token without a fractional part.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Integer(NumericValue);
+
+
+impl Integer {
+    /// Parse a `Number` token that does not have a fractional part.
+    pub fn parse<'i, 't>(input: &mut Parser<'i, 't>) -> Result<Integer, ParseError<'i>> {
+        let start = input.position();
+        match try!(input.next()) {
+            Token::Number(value) if value.int_value.is_some() => Ok(Integer(value)),
+            token => Err(unexpected_token(token, start))
+        }
+    }
+
+    /// The value.
+    #[inline]
+    pub fn value(self) -> i32 {
+        // `Integer` is only ever created with `Some`.
+        self.0.int_value.unwrap()
+    }
+
+    /// The value, if it is not negative.
+    pub fn non_negative(self) -> Result<i32, ()> {
+        if self.value() >= 0 { Ok(self.value()) } else { Err(()) }
+    }
+
+    /// The value, if it is greater than zero.
+    pub fn positive(self) -> Result<i32, ()> {
+        if self.value() > 0 { Ok(self.value()) } else { Err(()) }
+    }
+
+    /// The value, if it is in the given inclusive range.
+    pub fn in_range(self, min: i32, max: i32) -> Result<i32, ()> {
+        if min <= self.value() && self.value() <= max { Ok(self.value()) } else { Err(()) }
+    }
+
+    /// Convert to a `Number`. Integers are valid everywhere numbers are.
+    #[inline]
+    pub fn to_number(self) -> Number {
+        Number(self.0)
+    }
+}
+
+
+impl ToCss for Integer {
+    fn to_css<W>(&self, dest: &mut W) -> fmt::Result where W: fmt::Write {
+        Token::Number(self.0).to_css(dest)
+    }
+
+    fn to_css_with_options<W>(&self, options: &SerializationOptions, dest: &mut W)
+                              -> fmt::Result where W: fmt::Write {
+        Token::Number(self.0).to_css_with_options(options, dest)
+    }
+}
+
+
+/// A `<percentage>` value, parsed from a `Percentage` token.
+///
+/// As in the token, the value is kept divided by 100 with the original representation.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Percentage(pub PercentageValue);
+
+
+impl Percentage {
+    /// Parse a `Percentage` token.
+    ///
+    /// Math functions are not supported.
+    pub fn parse<'i, 't>(input: &mut Parser<'i, 't>) -> Result<Percentage, ParseError<'i>> {
+        let start = input.position();
+        match try!(input.next()) {
+            Token::Percentage(value) => Ok(Percentage(value)),
+            token => Err(unexpected_token(token, start))
+        }
+    }
+
+    /// The value divided by 100: `0%` and `100%` are `0.0` and `1.0`, respectively.
+    #[inline]
+    pub fn unit_value(self) -> f32 {
+        self.0.unit_value
+    }
+
+    /// The unit value, if it is not negative.
+    pub fn non_negative(self) -> Result<f32, ()> {
+        if self.0.unit_value >= 0. { Ok(self.0.unit_value) } else { Err(()) }
+    }
+
+    /// The unit value, if it is between `0%` and `100%` inclusive.
+    pub fn fraction(self) -> Result<f32, ()> {
+        let value = self.0.unit_value;
+        if 0. <= value && value <= 1. { Ok(value) } else { Err(()) }
+    }
+}
+
+
+impl ToCss for Percentage {
+    fn to_css<W>(&self, dest: &mut W) -> fmt::Result where W: fmt::Write {
+        Token::Percentage(self.0).to_css(dest)
+    }
+
+    fn to_css_with_options<W>(&self, options: &SerializationOptions, dest: &mut W)
+                              -> fmt::Result where W: fmt::Write {
+        Token::Percentage(self.0).to_css_with_options(options, dest)
+    }
+}
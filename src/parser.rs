@@ -6,8 +6,13 @@ use std::ops::Range;
 use std::ascii::AsciiExt;
 use std::ops::BitOr;
 use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
 use std::ops;
 use tokenizer::{self, Token, NumericValue, PercentageValue, Tokenizer, SourceLocation};
+use tokenizer::{SyntaxError, UnknownFunction};
+use rules_and_declarations::CssWideKeyword;
+use serializer::ToCss;
 
 
 /// A capture of the internal state of a `Parser` (including the position within the input),
@@ -15,18 +20,244 @@ use tokenizer::{self, Token, NumericValue, PercentageValue, Tokenizer, SourceLoc
 ///
 /// Can be used with the `Parser::reset` method to restore that state.
 /// Should only be used with the `Parser` instance it came from.
-#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+///
+/// Positions are ordered by their byte offset in the input.
+/// Subtracting two positions gives the number of bytes between them.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
 pub struct SourcePosition {
     position: tokenizer::SourcePosition,
     at_start_of: Option<BlockType>,
 }
 
+impl SourcePosition {
+    /// The offset of this position from the start of the input,
+    /// counted in bytes (not code points).
+    ///
+    /// This can be used to slice the input string.
+    #[inline]
+    pub fn byte_index(self) -> usize {
+        self.position.byte_index()
+    }
+}
+
+impl ops::Sub for SourcePosition {
+    type Output = isize;
+
+    /// The number of bytes from `other` to `self`, negative if `self` is before `other`.
+    #[inline]
+    fn sub(self, other: SourcePosition) -> isize {
+        self.byte_index() as isize - other.byte_index() as isize
+    }
+}
+
+/// A part of the input, from `start` (inclusive) to `end` (exclusive).
+///
+/// This is used for the spans of component values, rules, and declarations,
+/// for the extent of errors, and for edits.
+/// The corresponding source text can be obtained with `Parser::slice`,
+/// and line and column numbers with `Parser::source_location`.
+pub type SourceRange = Range<SourcePosition>;
+
+/// The start of each line of an input, to find the line and column numbers
+/// of many positions in any order.
+///
+/// `Parser::source_location` is fast for increasing positions,
+/// but counts newlines from the start of the input again for each position
+/// before the last one it was given.
+/// A `LineIndex` is built once, after which each lookup is a binary search.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    /// Index the lines of the given input.
+    /// Newlines are `\n`, `\r\n`, `\r` and `\x0C`, per CSS Syntax.
+    pub fn new(input: &str) -> LineIndex {
+        let mut line_starts = vec![0];
+        let mut bytes = input.bytes().enumerate().peekable();
+        while let Some((index, byte)) = bytes.next() {
+            match byte {
+                b'\r' if bytes.peek().map(|&(_, byte)| byte) == Some(b'\n') => {}
+                b'\n' | b'\r' | b'\x0C' => line_starts.push(index + 1),
+                _ => {}
+            }
+        }
+        LineIndex { line_starts: line_starts }
+    }
+
+    /// The number of lines, at least 1.
+    #[inline]
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// The position of the start of the given line, numbered from 1,
+    /// or `None` if there is no such line.
+    pub fn line_start(&self, line: usize) -> Option<SourcePosition> {
+        if line == 0 {
+            return None
+        }
+        self.line_starts.get(line - 1).map(|&index| position_from_byte_index(index))
+    }
+
+    /// Return the line and column number of the given position,
+    /// which must be within the indexed input.
+    ///
+    /// This is the same as what `Parser::source_location` returns for that input.
+    pub fn source_location(&self, position: SourcePosition) -> SourceLocation {
+        let index = position.byte_index();
+        let line = match self.line_starts.binary_search(&index) {
+            Ok(line) => line,
+            Err(line) => line - 1,
+        };
+        SourceLocation {
+            line: line + 1,
+            column: index - self.line_starts[line] + 1,
+        }
+    }
+}
+
+/// The byte offset of a position within the input. (Not exported outside this crate.)
+#[inline]
+pub fn byte_index(position: SourcePosition) -> usize {
+    position.byte_index()
+}
+
+/// The position at the given byte offset, outside of any block start.
+/// (Not exported outside this crate.)
+#[inline]
+pub fn position_from_byte_index(index: usize) -> SourcePosition {
+    SourcePosition {
+        position: tokenizer::SourcePosition::from_byte_index(index),
+        at_start_of: None,
+    }
+}
+
+/// The same position, moved by `delta` bytes. (Not exported outside this crate.)
+#[inline]
+pub fn offset_position(position: SourcePosition, delta: isize) -> SourcePosition {
+    let index = position.position.byte_index() as isize + delta;
+    SourcePosition {
+        position: tokenizer::SourcePosition::from_byte_index(index as usize),
+        at_start_of: position.at_start_of,
+    }
+}
+
+
+/// The reason why parsing failed, in a `ParseError`.
+#[derive(Clone, PartialEq, Debug)]
+pub enum ParseErrorKind<'i> {
+    /// A token was not one that was expected here,
+    /// or it was of the expected type but with an invalid value
+    /// (such as a `Number` with a fractional part for `expect_integer`).
+    UnexpectedToken(Token<'i>),
+
+    /// The end of the input, or of the current block or delimited part of the input,
+    /// was reached while more was expected.
+    EndOfInput,
+
+    /// The input was rejected by a parsing function for another reason.
+    /// The position of the error is chosen by that function.
+    Invalid,
+}
+
+
+/// An error returned by the `Parser::next*` and `Parser::expect_*` methods,
+/// and by `parse_one_declaration` and `parse_one_rule`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ParseError<'i> {
+    /// Why parsing failed.
+    pub kind: ParseErrorKind<'i>,
+
+    /// Where parsing failed: the start of the unexpected token,
+    /// or the position where the end of the input was reached.
+    ///
+    /// Line and column numbers can be obtained with `Parser::source_location`.
+    pub position: SourcePosition,
+}
+
+
+impl<'i> ParseErrorKind<'i> {
+    fn message(&self) -> &'static str {
+        match *self {
+            ParseErrorKind::UnexpectedToken(_) => "unexpected token",
+            ParseErrorKind::EndOfInput => "unexpected end of input",
+            ParseErrorKind::Invalid => "invalid input",
+        }
+    }
+
+    /// Return a kind that owns its token, and so does not borrow the input.
+    pub fn into_owned(self) -> ParseErrorKind<'static> {
+        match self {
+            ParseErrorKind::UnexpectedToken(token) => {
+                ParseErrorKind::UnexpectedToken(token.into_owned())
+            }
+            ParseErrorKind::EndOfInput => ParseErrorKind::EndOfInput,
+            ParseErrorKind::Invalid => ParseErrorKind::Invalid,
+        }
+    }
+}
+
+
+impl<'i> fmt::Display for ParseErrorKind<'i> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseErrorKind::UnexpectedToken(ref token) => {
+                write!(f, "unexpected token `{}`", token.to_css_string())
+            }
+            _ => f.write_str(self.message()),
+        }
+    }
+}
+
+
+impl<'i> Error for ParseErrorKind<'i> {
+    fn description(&self) -> &str {
+        self.message()
+    }
+}
+
+
+impl<'i> ParseError<'i> {
+    /// Return an error that owns its token, and so does not borrow the input.
+    ///
+    /// This is needed to keep the error after the input is dropped,
+    /// or to convert it to an error type that requires `'static`.
+    pub fn into_owned(self) -> ParseError<'static> {
+        ParseError {
+            kind: self.kind.into_owned(),
+            position: self.position,
+        }
+    }
+}
+
+
+impl<'i> fmt::Display for ParseError<'i> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.kind.fmt(f)
+    }
+}
+
+
+impl<'i> Error for ParseError<'i> {
+    fn description(&self) -> &str {
+        self.kind.message()
+    }
+
+    fn cause(&self) -> Option<&dyn Error> {
+        Some(&self.kind)
+    }
+}
+
 
 /// Like std::borrow::Cow, except:
 ///
 /// * The Owned variant is boxed
 /// * The Borrowed variant contains a mutable reference.
-enum MaybeOwned<'a, T: 'a> {
+///
+/// (Not exported outside this crate.)
+pub enum MaybeOwned<'a, T: 'a> {
     Owned(Box<T>),
     Borrowed(&'a mut T),
 }
@@ -71,7 +302,7 @@ pub struct Parser<'i: 't, 't> {
 }
 
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
 enum BlockType {
     Parenthesis,
     SquareBracket,
@@ -166,6 +397,55 @@ impl Delimiters {
     }
 }
 
+
+/// The result of `Parser::expect_number_or_percentage`.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum NumberOrPercentage {
+    /// The value of a <number-token>.
+    Number(f32),
+
+    /// The value of a <percentage-token>, divided by 100:
+    /// `0%` and `100%` map to `0.0` and `1.0`, respectively.
+    Percentage(f32),
+}
+
+
+/// Configuration for `Parser::new_with_options`.
+///
+/// The default options are those of `Parser::new`.
+///
+/// ```{rust,ignore}
+/// let mut input = Parser::new_with_options(css, ParserOptions::new()
+///                                                   .record_syntax_errors(true)
+///                                                   .record_unknown_functions(&["calc", "rgb"]));
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct ParserOptions {
+    record_syntax_errors: bool,
+    known_function_names: Option<Vec<String>>,
+}
+
+impl ParserOptions {
+    /// Create the default options.
+    pub fn new() -> ParserOptions {
+        ParserOptions::default()
+    }
+
+    /// Whether to record recoverable parse errors from the start of the input.
+    /// (See the `Parser::record_syntax_errors` method.)
+    pub fn record_syntax_errors(&mut self, record: bool) -> &mut ParserOptions {
+        self.record_syntax_errors = record;
+        self
+    }
+
+    /// Record functions whose name is not in `known_names` from the start of the input.
+    /// (See the `Parser::record_unknown_functions` method.)
+    pub fn record_unknown_functions(&mut self, known_names: &[&str]) -> &mut ParserOptions {
+        self.known_function_names = Some(known_names.iter().map(|&name| name.to_owned()).collect());
+        self
+    }
+}
+
 impl<'i, 't> Parser<'i, 't> {
     /// Create a new parser
     #[inline]
@@ -177,6 +457,19 @@ impl<'i, 't> Parser<'i, 't> {
         }
     }
 
+    /// Create a new parser with the given options.
+    pub fn new_with_options(input: &'i str, options: &ParserOptions) -> Parser<'i, 'i> {
+        let mut parser = Parser::new(input);
+        if options.record_syntax_errors {
+            parser.record_syntax_errors()
+        }
+        if let Some(ref names) = options.known_function_names {
+            let names: Vec<&str> = names.iter().map(|name| &**name).collect();
+            parser.record_unknown_functions(&names)
+        }
+        parser
+    }
+
     /// Check whether the input is exhausted. That is, if `.next()` would return a token.
     ///
     /// This ignores whitespace and comments.
@@ -189,19 +482,30 @@ impl<'i, 't> Parser<'i, 't> {
     /// Return a `Result` so that the `try!` macro can be used: `try!(input.expect_exhausted())`
     ///
     /// This ignores whitespace and comments.
+    /// The error is `ParseErrorKind::UnexpectedToken` with the next token.
     #[inline]
-    pub fn expect_exhausted(&mut self) -> Result<(), ()> {
+    pub fn expect_exhausted(&mut self) -> Result<(), ParseError<'i>> {
         let start_position = self.position();
-        let result = match self.next() {
-            Err(()) => Ok(()),
-            Ok(_) => {
-                Err(())
-            }
+        let result = match self.next_with_start() {
+            Err(_) => Ok(()),
+            Ok((start, token)) => Err(unexpected_token(token, start)),
         };
         self.reset(start_position);
         result
     }
 
+    /// Create an error of the given kind at the current position.
+    ///
+    /// For example, a parsing function can return
+    /// `Err(input.new_error(ParseErrorKind::Invalid))` for a value that is out of range.
+    #[inline]
+    pub fn new_error(&self, kind: ParseErrorKind<'i>) -> ParseError<'i> {
+        ParseError {
+            kind: kind,
+            position: self.position(),
+        }
+    }
+
     /// Return the current internal state of the parser (including position within the input).
     ///
     /// This state can later be restored with the `Parser::reset` method.
@@ -250,6 +554,41 @@ impl<'i, 't> Parser<'i, 't> {
         self.tokenizer.seen_viewport_percentages()
     }
 
+    /// Start recording recoverable parse errors. (See the `take_syntax_errors` method.)
+    ///
+    /// Parsing is not otherwise affected.
+    /// Errors in input that is parsed again after `reset` are only recorded once.
+    #[inline]
+    pub fn record_syntax_errors(&mut self) {
+        self.tokenizer.record_syntax_errors()
+    }
+
+    /// Return the errors recorded since `record_syntax_errors` was called
+    /// or since the last call to this method, in input order.
+    #[inline]
+    pub fn take_syntax_errors(&mut self) -> Vec<SyntaxError> {
+        self.tokenizer.take_syntax_errors()
+    }
+
+    /// Start recording functions whose name is not in `known_names`
+    /// (compared ASCII case-insensitively), such as functions not supported by some browsers.
+    /// (See the `take_unknown_functions` method.)
+    ///
+    /// Parsing is not otherwise affected.
+    /// Functions in input that is parsed again after `reset` are only recorded once.
+    /// Calling this method again replaces the known names.
+    #[inline]
+    pub fn record_unknown_functions(&mut self, known_names: &[&str]) {
+        self.tokenizer.record_unknown_functions(known_names)
+    }
+
+    /// Return the unknown functions recorded since `record_unknown_functions` was called
+    /// or since the last call to this method, in input order.
+    #[inline]
+    pub fn take_unknown_functions(&mut self) -> Vec<UnknownFunction> {
+        self.tokenizer.take_unknown_functions()
+    }
+
     /// Execute the given closure, passing it the parser.
     /// If the result (returned unchanged) is `Err`,
     /// the internal state of the parser  (including position within the input)
@@ -267,7 +606,7 @@ impl<'i, 't> Parser<'i, 't> {
 
     /// Return a slice of the CSS input
     #[inline]
-    pub fn slice(&self, range: Range<SourcePosition>) -> &'i str {
+    pub fn slice(&self, range: SourceRange) -> &'i str {
         self.tokenizer.slice(range.start.position..range.end.position)
     }
 
@@ -284,6 +623,8 @@ impl<'i, 't> Parser<'i, 't> {
     }
 
     /// Return the line and column number within the input for the given position.
+    ///
+    /// To look up many positions that are not in increasing order, use a `LineIndex`.
     #[inline]
     pub fn source_location(&self, target: SourcePosition) -> SourceLocation {
         self.tokenizer.source_location(target.position)
@@ -300,17 +641,24 @@ impl<'i, 't> Parser<'i, 't> {
     /// See the `Parser::parse_nested_block` method to parse the content of functions or blocks.
     ///
     /// This only returns a closing token when it is unmatched (and therefore an error).
-    pub fn next(&mut self) -> Result<Token<'i>, ()> {
+    ///
+    /// The error is always `ParseErrorKind::EndOfInput`.
+    pub fn next(&mut self) -> Result<Token<'i>, ParseError<'i>> {
+        self.next_with_start().map(|(_, token)| token)
+    }
+
+    /// Same as `Parser::next`, but also return the position of the start of the token.
+    fn next_with_start(&mut self) -> Result<(SourcePosition, Token<'i>), ParseError<'i>> {
         loop {
-            match self.next_including_whitespace_and_comments() {
-                Ok(Token::WhiteSpace(_)) | Ok(Token::Comment(_)) => {},
-                result => return result
+            match try!(self.next_including_whitespace_and_comments_with_start()) {
+                (_, Token::WhiteSpace(_)) | (_, Token::Comment(_)) => {},
+                result => return Ok(result)
             }
         }
     }
 
     /// Same as `Parser::next`, but does not skip whitespace tokens.
-    pub fn next_including_whitespace(&mut self) -> Result<Token<'i>, ()> {
+    pub fn next_including_whitespace(&mut self) -> Result<Token<'i>, ParseError<'i>> {
         loop {
             match self.next_including_whitespace_and_comments() {
                 Ok(Token::Comment(_)) => {},
@@ -325,27 +673,39 @@ impl<'i, 't> Parser<'i, 't> {
     /// where comments are preserved.
     /// When parsing higher-level values, per the CSS Syntax specification,
     /// comments should always be ignored between tokens.
-    pub fn next_including_whitespace_and_comments(&mut self) -> Result<Token<'i>, ()> {
+    pub fn next_including_whitespace_and_comments(&mut self) -> Result<Token<'i>, ParseError<'i>> {
+        self.next_including_whitespace_and_comments_with_start().map(|(_, token)| token)
+    }
+
+    /// Same as `Parser::next_including_whitespace_and_comments`,
+    /// but also return the position of the start of the token.
+    fn next_including_whitespace_and_comments_with_start(&mut self)
+                                                         -> Result<(SourcePosition, Token<'i>),
+                                                                   ParseError<'i>> {
         if let Some(block_type) = self.at_start_of.take() {
             consume_until_end_of_block(block_type, &mut *self.tokenizer);
         }
+        let start = self.position();
         if self.stop_before.contains(Delimiters::from_byte(self.tokenizer.next_byte())) {
-            return Err(())
+            return Err(self.new_error(ParseErrorKind::EndOfInput))
         }
-        let token = try!(self.tokenizer.next());
+        let token = match self.tokenizer.next() {
+            Ok(token) => token,
+            Err(_) => return Err(self.new_error(ParseErrorKind::EndOfInput)),
+        };
         if let Some(block_type) = BlockType::opening(&token) {
             self.at_start_of = Some(block_type);
         }
-        Ok(token)
+        Ok((start, token))
     }
 
     /// Have the given closure parse something, then check the the input is exhausted.
-    /// The result is overridden to `Err(())` if some input remains.
+    /// The result is overridden to an error if some input remains.
     ///
     /// This can help tell e.g. `color: green;` from `color: green 4px;`
     #[inline]
-    pub fn parse_entirely<F, T>(&mut self, parse: F) -> Result<T, ()>
-    where F: FnOnce(&mut Parser<'i, 't>) -> Result<T, ()> {
+    pub fn parse_entirely<F, T, E>(&mut self, parse: F) -> Result<T, E>
+    where F: FnOnce(&mut Parser<'i, 't>) -> Result<T, E>, E: From<ParseError<'i>> {
         let result = parse(self);
         try!(self.expect_exhausted());
         result
@@ -359,16 +719,47 @@ impl<'i, 't> Parser<'i, 't> {
     ///
     /// Successful results are accumulated in a vector.
     ///
-    /// This method retuns `Err(())` the first time that a closure call does,
+    /// This method retuns an error the first time that a closure call does,
     /// or if a closure call leaves some input before the next comma or the end of the input.
     #[inline]
-    pub fn parse_comma_separated<F, T>(&mut self, mut parse_one: F) -> Result<Vec<T>, ()>
-    where F: FnMut(&mut Parser) -> Result<T, ()> {
+    pub fn parse_comma_separated<F, T, E>(&mut self, mut parse_one: F) -> Result<Vec<T>, E>
+    where F: for<'tt> FnMut(&mut Parser<'i, 'tt>) -> Result<T, E>, E: From<ParseError<'i>> {
         let mut values = vec![];
         loop {
             values.push(try!(self.parse_until_before(Delimiter::Comma, |parser| parse_one(parser))));
             match self.next() {
-                Err(()) => return Ok(values),
+                Err(_) => return Ok(values),
+                Ok(Token::Comma) => continue,
+                Ok(_) => unreachable!(),
+            }
+        }
+    }
+
+    /// Split the rest of the input (or of the current block or function) at commas
+    /// at this block/function nesting level, and return a parser for each item.
+    ///
+    /// The returned parsers are independent of this one and of each other,
+    /// so that items can be parsed later, in any order, or on other threads.
+    /// Their positions are the same as in this parser,
+    /// but they do not record syntax errors or unknown functions.
+    ///
+    /// There is always at least one item, which may be empty.
+    /// Splitting only skips over the input, without decoding escapes,
+    /// so this is cheaper than `parse_comma_separated` for long lists of complex values
+    /// when only some of them are needed.
+    pub fn split_comma_separated(&mut self) -> Vec<Parser<'i, 'i>> {
+        let mut parsers = vec![];
+        loop {
+            let start = self.tokenizer.position();
+            let _ = self.parse_until_before(Delimiter::Comma, |_| Ok::<(), ParseError>(()));
+            let end = self.tokenizer.position();
+            parsers.push(Parser {
+                tokenizer: MaybeOwned::Owned(Box::new(self.tokenizer.sub_tokenizer(start..end))),
+                at_start_of: None,
+                stop_before: Delimiter::None,
+            });
+            match self.next() {
+                Err(_) => return parsers,
                 Ok(Token::Comma) => continue,
                 Ok(_) => unreachable!(),
             }
@@ -385,10 +776,10 @@ impl<'i, 't> Parser<'i, 't> {
     /// The given closure is called with a "delimited" parser
     /// that stops at the end of the block or function (at the matching closing token).
     ///
-    /// The result is overridden to `Err(())` if the closure leaves some input before that point.
+    /// The result is overridden to an error if the closure leaves some input before that point.
     #[inline]
-    pub fn parse_nested_block<F, T>(&mut self, parse: F) -> Result <T, ()>
-    where F: for<'tt> FnOnce(&mut Parser<'i, 'tt>) -> Result<T, ()> {
+    pub fn parse_nested_block<F, T, E>(&mut self, parse: F) -> Result <T, E>
+    where F: for<'tt> FnOnce(&mut Parser<'i, 'tt>) -> Result<T, E>, E: From<ParseError<'i>> {
         let block_type = self.at_start_of.take().expect("\
             A nested parser can only be created when a Function, \
             ParenthesisBlock, SquareBracketBlock, or CurlyBracketBlock \
@@ -422,11 +813,11 @@ impl<'i, 't> Parser<'i, 't> {
     /// that stops before the first character at this block/function nesting level
     /// that matches the given set of delimiters.
     ///
-    /// The result is overridden to `Err(())` if the closure leaves some input before that point.
+    /// The result is overridden to an error if the closure leaves some input before that point.
     #[inline]
-    pub fn parse_until_before<F, T>(&mut self, delimiters: Delimiters, parse: F)
-                                    -> Result <T, ()>
-    where F: for<'tt> FnOnce(&mut Parser<'i, 'tt>) -> Result<T, ()> {
+    pub fn parse_until_before<F, T, E>(&mut self, delimiters: Delimiters, parse: F)
+                                       -> Result <T, E>
+    where F: for<'tt> FnOnce(&mut Parser<'i, 'tt>) -> Result<T, E>, E: From<ParseError<'i>> {
         let delimiters = self.stop_before | delimiters;
         let result;
         // Introduce a new scope to limit duration of nested_parser’s borrow
@@ -441,7 +832,7 @@ impl<'i, 't> Parser<'i, 't> {
                 consume_until_end_of_block(block_type, &mut *delimited_parser.tokenizer);
             }
         }
-        // FIXME: have a special-purpose tokenizer method for this that does less work.
+        let was_skipping = self.tokenizer.skipping(true);
         loop {
             if delimiters.contains(Delimiters::from_byte(self.tokenizer.next_byte())) {
                 break
@@ -454,6 +845,7 @@ impl<'i, 't> Parser<'i, 't> {
                 break
             }
         }
+        self.tokenizer.skipping(was_skipping);
         result
     }
 
@@ -463,14 +855,16 @@ impl<'i, 't> Parser<'i, 't> {
     /// (e.g. if these is only one in the given set)
     /// or if it was there at all (as opposed to reaching the end of the input).
     #[inline]
-    pub fn parse_until_after<F, T>(&mut self, delimiters: Delimiters, parse: F)
-                                   -> Result <T, ()>
-    where F: for<'tt> FnOnce(&mut Parser<'i, 'tt>) -> Result<T, ()> {
+    pub fn parse_until_after<F, T, E>(&mut self, delimiters: Delimiters, parse: F)
+                                      -> Result <T, E>
+    where F: for<'tt> FnOnce(&mut Parser<'i, 'tt>) -> Result<T, E>, E: From<ParseError<'i>> {
         let result = self.parse_until_before(delimiters, parse);
         let next_byte = self.tokenizer.next_byte();
         if next_byte.is_some() && !self.stop_before.contains(Delimiters::from_byte(next_byte)) {
             debug_assert!(delimiters.contains(Delimiters::from_byte(next_byte)));
-            self.tokenizer.advance(1);
+            // Tokenize the delimiter rather than skipping its byte,
+            // so that a `{` is seen by the tokenizer’s tracking of unmatched brackets.
+            let _ = self.tokenizer.next();
             if next_byte == Some(b'{') {
                 consume_until_end_of_block(BlockType::CurlyBracket, &mut *self.tokenizer);
             }
@@ -480,136 +874,202 @@ impl<'i, 't> Parser<'i, 't> {
 
     /// Parse a <whitespace-token> and return its value.
     #[inline]
-    pub fn expect_whitespace(&mut self) -> Result<&'i str, ()> {
-        match try!(self.next_including_whitespace()) {
-            Token::WhiteSpace(value) => Ok(value),
-            _ => Err(())
+    pub fn expect_whitespace(&mut self) -> Result<&'i str, ParseError<'i>> {
+        loop {
+            match try!(self.next_including_whitespace_and_comments_with_start()) {
+                (_, Token::Comment(_)) => {},
+                (start, Token::WhiteSpace(_)) => return Ok(self.slice_from(start)),
+                (start, token) => return Err(unexpected_token(token, start))
+            }
         }
     }
 
     /// Parse a <ident-token> and return the unescaped value.
     #[inline]
-    pub fn expect_ident(&mut self) -> Result<Cow<'i, str>, ()> {
-        match try!(self.next()) {
+    pub fn expect_ident(&mut self) -> Result<Cow<'i, str>, ParseError<'i>> {
+        let (start, token) = try!(self.next_with_start());
+        match token {
             Token::Ident(value) => Ok(value),
-            _ => Err(())
+            token => Err(unexpected_token(token, start))
         }
     }
 
     /// Parse a <ident-token> whose unescaped value is an ASCII-insensitive match for the given value.
     #[inline]
-    pub fn expect_ident_matching(&mut self, expected_value: &str) -> Result<(), ()> {
-        match try!(self.next()) {
+    pub fn expect_ident_matching(&mut self, expected_value: &str) -> Result<(), ParseError<'i>> {
+        let (start, token) = try!(self.next_with_start());
+        match token {
             Token::Ident(ref value) if value.eq_ignore_ascii_case(expected_value) => Ok(()),
-            _ => Err(())
+            token => Err(unexpected_token(token, start))
+        }
+    }
+
+    /// Parse a [`<custom-ident>`](https://drafts.csswg.org/css-values/#custom-idents),
+    /// a user-defined identifier, and return the unescaped value.
+    ///
+    /// CSS-wide keywords (such as `inherit`), `default`, and the given `excluded` keywords
+    /// (typically the keywords of the property’s own grammar) are rejected,
+    /// ASCII case-insensitively.
+    pub fn expect_custom_ident(&mut self, excluded: &[&str])
+                               -> Result<Cow<'i, str>, ParseError<'i>> {
+        let (start, token) = try!(self.next_with_start());
+        match token {
+            Token::Ident(value) => {
+                if CssWideKeyword::from_ident(&value).is_ok() ||
+                   value.eq_ignore_ascii_case("default") ||
+                   excluded.iter().any(|keyword| value.eq_ignore_ascii_case(keyword)) {
+                    return Err(unexpected_token(Token::Ident(value), start))
+                }
+                Ok(value)
+            }
+            token => Err(unexpected_token(token, start))
+        }
+    }
+
+    /// Parse a [`<dashed-ident>`](https://drafts.csswg.org/css-values/#dashed-idents),
+    /// an identifier that starts with two dashes such as a custom property name,
+    /// and return the unescaped value (including the dashes).
+    #[inline]
+    pub fn expect_dashed_ident(&mut self) -> Result<Cow<'i, str>, ParseError<'i>> {
+        let (start, token) = try!(self.next_with_start());
+        match token {
+            Token::Ident(value) if value.starts_with("--") => Ok(value),
+            token => Err(unexpected_token(token, start))
         }
     }
 
     /// Parse a <string-token> and return the unescaped value.
     #[inline]
-    pub fn expect_string(&mut self) -> Result<Cow<'i, str>, ()> {
-        match try!(self.next()) {
+    pub fn expect_string(&mut self) -> Result<Cow<'i, str>, ParseError<'i>> {
+        let (start, token) = try!(self.next_with_start());
+        match token {
             Token::QuotedString(value) => Ok(value),
-            _ => Err(())
+            token => Err(unexpected_token(token, start))
         }
     }
 
     /// Parse either a <ident-token> or a <string-token>, and return the unescaped value.
     #[inline]
-    pub fn expect_ident_or_string(&mut self) -> Result<Cow<'i, str>, ()> {
-        match try!(self.next()) {
+    pub fn expect_ident_or_string(&mut self) -> Result<Cow<'i, str>, ParseError<'i>> {
+        let (start, token) = try!(self.next_with_start());
+        match token {
             Token::Ident(value) => Ok(value),
             Token::QuotedString(value) => Ok(value),
-            _ => Err(())
+            token => Err(unexpected_token(token, start))
         }
     }
 
     /// Parse a <url-token> and return the unescaped value.
     #[inline]
-    pub fn expect_url(&mut self) -> Result<Cow<'i, str>, ()> {
-        match try!(self.next()) {
+    pub fn expect_url(&mut self) -> Result<Cow<'i, str>, ParseError<'i>> {
+        let (start, token) = try!(self.next_with_start());
+        match token {
             Token::UnquotedUrl(value) => Ok(value),
             Token::Function(ref name) if name.eq_ignore_ascii_case("url") => {
                 self.parse_nested_block(|input| input.expect_string())
             },
-            _ => Err(())
+            token => Err(unexpected_token(token, start))
         }
     }
 
     /// Parse either a <url-token> or a <string-token>, and return the unescaped value.
     #[inline]
-    pub fn expect_url_or_string(&mut self) -> Result<Cow<'i, str>, ()> {
-        match try!(self.next()) {
+    pub fn expect_url_or_string(&mut self) -> Result<Cow<'i, str>, ParseError<'i>> {
+        let (start, token) = try!(self.next_with_start());
+        match token {
             Token::UnquotedUrl(value) => Ok(value),
             Token::QuotedString(value) => Ok(value),
             Token::Function(ref name) if name.eq_ignore_ascii_case("url") => {
                 self.parse_nested_block(|input| input.expect_string())
             },
-            _ => Err(())
+            token => Err(unexpected_token(token, start))
         }
     }
 
     /// Parse a <number-token> and return the integer value.
     #[inline]
-    pub fn expect_number(&mut self) -> Result<f32, ()> {
-        match try!(self.next()) {
+    pub fn expect_number(&mut self) -> Result<f32, ParseError<'i>> {
+        let (start, token) = try!(self.next_with_start());
+        match token {
             Token::Number(NumericValue { value, .. }) => Ok(value),
-            _ => Err(())
+            token => Err(unexpected_token(token, start))
         }
     }
 
     /// Parse a <number-token> that does not have a fractional part, and return the integer value.
     #[inline]
-    pub fn expect_integer(&mut self) -> Result<i32, ()> {
-        match try!(self.next()) {
-            Token::Number(NumericValue { int_value, .. }) => int_value.ok_or(()),
-            _ => Err(())
+    pub fn expect_integer(&mut self) -> Result<i32, ParseError<'i>> {
+        let (start, token) = try!(self.next_with_start());
+        match token {
+            Token::Number(NumericValue { int_value: Some(value), .. }) => Ok(value),
+            token => Err(unexpected_token(token, start))
         }
     }
 
     /// Parse a <percentage-token> and return the value.
     /// `0%` and `100%` map to `0.0` and `1.0` (not `100.0`), respectively.
     #[inline]
-    pub fn expect_percentage(&mut self) -> Result<f32, ()> {
-        match try!(self.next()) {
+    pub fn expect_percentage(&mut self) -> Result<f32, ParseError<'i>> {
+        let (start, token) = try!(self.next_with_start());
+        match token {
             Token::Percentage(PercentageValue { unit_value, .. }) => Ok(unit_value),
-            _ => Err(())
+            token => Err(unexpected_token(token, start))
+        }
+    }
+
+    /// Parse either a <number-token> or a <percentage-token>, and return the value.
+    ///
+    /// This is the `<number> | <percentage>` alternation found in many grammars,
+    /// such as `opacity` or color components.
+    #[inline]
+    pub fn expect_number_or_percentage(&mut self) -> Result<NumberOrPercentage, ParseError<'i>> {
+        let (start, token) = try!(self.next_with_start());
+        match token {
+            Token::Number(NumericValue { value, .. }) => Ok(NumberOrPercentage::Number(value)),
+            Token::Percentage(PercentageValue { unit_value, .. }) => {
+                Ok(NumberOrPercentage::Percentage(unit_value))
+            }
+            token => Err(unexpected_token(token, start))
         }
     }
 
     /// Parse a `:` <colon-token>.
     #[inline]
-    pub fn expect_colon(&mut self) -> Result<(), ()> {
-        match try!(self.next()) {
+    pub fn expect_colon(&mut self) -> Result<(), ParseError<'i>> {
+        let (start, token) = try!(self.next_with_start());
+        match token {
             Token::Colon => Ok(()),
-            _ => Err(())
+            token => Err(unexpected_token(token, start))
         }
     }
 
     /// Parse a `;` <semicolon-token>.
     #[inline]
-    pub fn expect_semicolon(&mut self) -> Result<(), ()> {
-        match try!(self.next()) {
+    pub fn expect_semicolon(&mut self) -> Result<(), ParseError<'i>> {
+        let (start, token) = try!(self.next_with_start());
+        match token {
             Token::Semicolon => Ok(()),
-            _ => Err(())
+            token => Err(unexpected_token(token, start))
         }
     }
 
     /// Parse a `,` <comma-token>.
     #[inline]
-    pub fn expect_comma(&mut self) -> Result<(), ()> {
-        match try!(self.next()) {
+    pub fn expect_comma(&mut self) -> Result<(), ParseError<'i>> {
+        let (start, token) = try!(self.next_with_start());
+        match token {
             Token::Comma => Ok(()),
-            _ => Err(())
+            token => Err(unexpected_token(token, start))
         }
     }
 
     /// Parse a <delim-token> with the given value.
     #[inline]
-    pub fn expect_delim(&mut self, expected_value: char) -> Result<(), ()> {
-        match try!(self.next()) {
+    pub fn expect_delim(&mut self, expected_value: char) -> Result<(), ParseError<'i>> {
+        let (start, token) = try!(self.next_with_start());
+        match token {
             Token::Delim(value) if value == expected_value => Ok(()),
-            _ => Err(())
+            token => Err(unexpected_token(token, start))
         }
     }
 
@@ -617,10 +1077,11 @@ impl<'i, 't> Parser<'i, 't> {
     ///
     /// If the result is `Ok`, you can then call the `Parser::parse_nested_block` method.
     #[inline]
-    pub fn expect_curly_bracket_block(&mut self) -> Result<(), ()> {
-        match try!(self.next()) {
+    pub fn expect_curly_bracket_block(&mut self) -> Result<(), ParseError<'i>> {
+        let (start, token) = try!(self.next_with_start());
+        match token {
             Token::CurlyBracketBlock => Ok(()),
-            _ => Err(())
+            token => Err(unexpected_token(token, start))
         }
     }
 
@@ -628,10 +1089,11 @@ impl<'i, 't> Parser<'i, 't> {
     ///
     /// If the result is `Ok`, you can then call the `Parser::parse_nested_block` method.
     #[inline]
-    pub fn expect_square_bracket_block(&mut self) -> Result<(), ()> {
-        match try!(self.next()) {
+    pub fn expect_square_bracket_block(&mut self) -> Result<(), ParseError<'i>> {
+        let (start, token) = try!(self.next_with_start());
+        match token {
             Token::SquareBracketBlock => Ok(()),
-            _ => Err(())
+            token => Err(unexpected_token(token, start))
         }
     }
 
@@ -639,10 +1101,11 @@ impl<'i, 't> Parser<'i, 't> {
     ///
     /// If the result is `Ok`, you can then call the `Parser::parse_nested_block` method.
     #[inline]
-    pub fn expect_parenthesis_block(&mut self) -> Result<(), ()> {
-        match try!(self.next()) {
+    pub fn expect_parenthesis_block(&mut self) -> Result<(), ParseError<'i>> {
+        let (start, token) = try!(self.next_with_start());
+        match token {
             Token::ParenthesisBlock => Ok(()),
-            _ => Err(())
+            token => Err(unexpected_token(token, start))
         }
     }
 
@@ -650,10 +1113,11 @@ impl<'i, 't> Parser<'i, 't> {
     ///
     /// If the result is `Ok`, you can then call the `Parser::parse_nested_block` method.
     #[inline]
-    pub fn expect_function(&mut self) -> Result<Cow<'i, str>, ()> {
-        match try!(self.next()) {
+    pub fn expect_function(&mut self) -> Result<Cow<'i, str>, ParseError<'i>> {
+        let (start, token) = try!(self.next_with_start());
+        match token {
             Token::Function(name) => Ok(name),
-            _ => Err(())
+            token => Err(unexpected_token(token, start))
         }
     }
 
@@ -661,10 +1125,11 @@ impl<'i, 't> Parser<'i, 't> {
     ///
     /// If the result is `Ok`, you can then call the `Parser::parse_nested_block` method.
     #[inline]
-    pub fn expect_function_matching(&mut self, expected_name: &str) -> Result<(), ()> {
-        match try!(self.next()) {
+    pub fn expect_function_matching(&mut self, expected_name: &str) -> Result<(), ParseError<'i>> {
+        let (start, token) = try!(self.next_with_start());
+        match token {
             Token::Function(ref name) if name.eq_ignore_ascii_case(expected_name) => Ok(()),
-            _ => Err(())
+            token => Err(unexpected_token(token, start))
         }
     }
 
@@ -672,8 +1137,9 @@ impl<'i, 't> Parser<'i, 't> {
     ///
     /// See `Token::is_parse_error`. This also checks nested blocks and functions recursively.
     #[inline]
-    pub fn expect_no_error_token(&mut self) -> Result<(), ()> {
+    pub fn expect_no_error_token(&mut self) -> Result<(), ParseError<'i>> {
         loop {
+            let start = self.position();
             match self.next_including_whitespace_and_comments() {
                 Ok(Token::Function(_)) | Ok(Token::ParenthesisBlock) |
                 Ok(Token::SquareBracketBlock) | Ok(Token::CurlyBracketBlock) => {
@@ -681,25 +1147,44 @@ impl<'i, 't> Parser<'i, 't> {
                 }
                 Ok(token) => {
                     if token.is_parse_error() {
-                        return Err(())
+                        return Err(unexpected_token(token, start))
                     }
                 }
-                Err(()) => return Ok(())
+                Err(_) => return Ok(())
             }
         }
     }
 }
 
 
+/// An `UnexpectedToken` error for the given token, which starts at `start`.
+pub fn unexpected_token(token: Token, start: SourcePosition) -> ParseError {
+    ParseError {
+        kind: ParseErrorKind::UnexpectedToken(token),
+        position: start,
+    }
+}
+
+
 /// Return value indicates whether the end of the input was reached.
 fn consume_until_end_of_block(block_type: BlockType, tokenizer: &mut Tokenizer) {
-    // FIXME: have a special-purpose tokenizer method for this that does less work.
+    // Skipped tokens are not returned, so their escapes don’t need to be decoded.
+    let was_skipping = tokenizer.skipping(true);
     while let Ok(ref token) = tokenizer.next() {
         if BlockType::closing(token) == Some(block_type) {
-            return
+            break
         }
         if let Some(block_type) = BlockType::opening(token) {
             consume_until_end_of_block(block_type, tokenizer);
         }
     }
+    tokenizer.skipping(was_skipping);
+    let closing_byte = match block_type {
+        BlockType::Parenthesis => b')',
+        BlockType::SquareBracket => b']',
+        BlockType::CurlyBracket => b'}',
+    };
+    check_invariant!(tokenizer.next_byte().is_none() ||
+                     tokenizer.previous_byte() == Some(closing_byte),
+                     "{:?} block not closed at {}", block_type, tokenizer.position().byte_index());
 }
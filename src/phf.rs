@@ -0,0 +1,46 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::ascii::AsciiExt;
+
+use cssparser_phf_shared::{hash, displace};
+
+
+/// A static map with ASCII case-insensitive string keys,
+/// generated at compile-time by `ascii_case_insensitive_phf_map!`.
+///
+/// Keys are found with a perfect hash function:
+/// a lookup hashes the input once and compares it with at most one key.
+pub struct PhfMap<V: 'static> {
+    #[doc(hidden)]
+    pub key: u64,
+
+    #[doc(hidden)]
+    pub disps: &'static [(u32, u32)],
+
+    #[doc(hidden)]
+    pub entries: &'static [(&'static str, V)],
+}
+
+
+impl<V> PhfMap<V> {
+    /// Return the value for the given key, compared ASCII case-insensitively.
+    pub fn get(&self, key: &str) -> Option<&V> {
+        let (g, f1, f2) = hash(self.key, key);
+        let (d1, d2) = self.disps[(g % self.disps.len() as u32) as usize];
+        let index = displace(f1, f2, d1, d2) % self.entries.len() as u32;
+        let &(entry_key, ref value) = &self.entries[index as usize];
+        if entry_key.eq_ignore_ascii_case(key) {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    /// Return the keys (in ASCII lower case) and values of the map, in unspecified order.
+    pub fn entries(&self) -> &'static [(&'static str, V)] {
+        self.entries
+    }
+}
+
@@ -0,0 +1,491 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::ascii::AsciiExt;
+use std::cmp::Ordering;
+
+
+/// A standard CSS property, as found by `lookup_property`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct PropertyInfo {
+    /// The name of the property, in ASCII lower case.
+    pub name: &'static str,
+
+    /// Whether the property is a shorthand for other properties.
+    pub is_shorthand: bool,
+}
+
+
+/// Return the standard CSS property with the given name, compared ASCII case-insensitively,
+/// or `None` for unknown properties (including vendor-prefixed and custom properties).
+pub fn lookup_property(name: &str) -> Option<&'static PropertyInfo> {
+    STANDARD_PROPERTIES.binary_search_by(|property| {
+        compare_ignoring_ascii_case(property.name, name)
+    }).ok().map(|index| &STANDARD_PROPERTIES[index])
+}
+
+
+/// Return all standard CSS properties known to this crate, sorted by name.
+///
+/// These are the properties of CSS specifications that are implemented by at least one browser,
+/// excluding vendor-prefixed properties.
+pub fn standard_properties() -> &'static [PropertyInfo] {
+    STANDARD_PROPERTIES
+}
+
+
+/// Compare `lower_case` with `name` as if `name` was converted to ASCII lower case.
+fn compare_ignoring_ascii_case(lower_case: &str, name: &str) -> Ordering {
+    lower_case.bytes().cmp(name.bytes().map(|b| b.to_ascii_lowercase()))
+}
+
+
+/// Sorted by name, for `binary_search_by`.
+static STANDARD_PROPERTIES: &'static [PropertyInfo] = &[
+    PropertyInfo { name: "accent-color", is_shorthand: false },
+    PropertyInfo { name: "align-content", is_shorthand: false },
+    PropertyInfo { name: "align-items", is_shorthand: false },
+    PropertyInfo { name: "align-self", is_shorthand: false },
+    PropertyInfo { name: "alignment-baseline", is_shorthand: false },
+    PropertyInfo { name: "all", is_shorthand: true },
+    PropertyInfo { name: "animation", is_shorthand: true },
+    PropertyInfo { name: "animation-composition", is_shorthand: false },
+    PropertyInfo { name: "animation-delay", is_shorthand: false },
+    PropertyInfo { name: "animation-direction", is_shorthand: false },
+    PropertyInfo { name: "animation-duration", is_shorthand: false },
+    PropertyInfo { name: "animation-fill-mode", is_shorthand: false },
+    PropertyInfo { name: "animation-iteration-count", is_shorthand: false },
+    PropertyInfo { name: "animation-name", is_shorthand: false },
+    PropertyInfo { name: "animation-play-state", is_shorthand: false },
+    PropertyInfo { name: "animation-timeline", is_shorthand: false },
+    PropertyInfo { name: "animation-timing-function", is_shorthand: false },
+    PropertyInfo { name: "appearance", is_shorthand: false },
+    PropertyInfo { name: "aspect-ratio", is_shorthand: false },
+    PropertyInfo { name: "backdrop-filter", is_shorthand: false },
+    PropertyInfo { name: "backface-visibility", is_shorthand: false },
+    PropertyInfo { name: "background", is_shorthand: true },
+    PropertyInfo { name: "background-attachment", is_shorthand: false },
+    PropertyInfo { name: "background-blend-mode", is_shorthand: false },
+    PropertyInfo { name: "background-clip", is_shorthand: false },
+    PropertyInfo { name: "background-color", is_shorthand: false },
+    PropertyInfo { name: "background-image", is_shorthand: false },
+    PropertyInfo { name: "background-origin", is_shorthand: false },
+    PropertyInfo { name: "background-position", is_shorthand: false },
+    PropertyInfo { name: "background-position-x", is_shorthand: false },
+    PropertyInfo { name: "background-position-y", is_shorthand: false },
+    PropertyInfo { name: "background-repeat", is_shorthand: false },
+    PropertyInfo { name: "background-size", is_shorthand: false },
+    PropertyInfo { name: "baseline-shift", is_shorthand: false },
+    PropertyInfo { name: "block-size", is_shorthand: false },
+    PropertyInfo { name: "border", is_shorthand: true },
+    PropertyInfo { name: "border-block", is_shorthand: true },
+    PropertyInfo { name: "border-block-color", is_shorthand: true },
+    PropertyInfo { name: "border-block-end", is_shorthand: true },
+    PropertyInfo { name: "border-block-end-color", is_shorthand: false },
+    PropertyInfo { name: "border-block-end-style", is_shorthand: false },
+    PropertyInfo { name: "border-block-end-width", is_shorthand: false },
+    PropertyInfo { name: "border-block-start", is_shorthand: true },
+    PropertyInfo { name: "border-block-start-color", is_shorthand: false },
+    PropertyInfo { name: "border-block-start-style", is_shorthand: false },
+    PropertyInfo { name: "border-block-start-width", is_shorthand: false },
+    PropertyInfo { name: "border-block-style", is_shorthand: true },
+    PropertyInfo { name: "border-block-width", is_shorthand: true },
+    PropertyInfo { name: "border-bottom", is_shorthand: true },
+    PropertyInfo { name: "border-bottom-color", is_shorthand: false },
+    PropertyInfo { name: "border-bottom-left-radius", is_shorthand: false },
+    PropertyInfo { name: "border-bottom-right-radius", is_shorthand: false },
+    PropertyInfo { name: "border-bottom-style", is_shorthand: false },
+    PropertyInfo { name: "border-bottom-width", is_shorthand: false },
+    PropertyInfo { name: "border-collapse", is_shorthand: false },
+    PropertyInfo { name: "border-color", is_shorthand: true },
+    PropertyInfo { name: "border-end-end-radius", is_shorthand: false },
+    PropertyInfo { name: "border-end-start-radius", is_shorthand: false },
+    PropertyInfo { name: "border-image", is_shorthand: true },
+    PropertyInfo { name: "border-image-outset", is_shorthand: false },
+    PropertyInfo { name: "border-image-repeat", is_shorthand: false },
+    PropertyInfo { name: "border-image-slice", is_shorthand: false },
+    PropertyInfo { name: "border-image-source", is_shorthand: false },
+    PropertyInfo { name: "border-image-width", is_shorthand: false },
+    PropertyInfo { name: "border-inline", is_shorthand: true },
+    PropertyInfo { name: "border-inline-color", is_shorthand: true },
+    PropertyInfo { name: "border-inline-end", is_shorthand: true },
+    PropertyInfo { name: "border-inline-end-color", is_shorthand: false },
+    PropertyInfo { name: "border-inline-end-style", is_shorthand: false },
+    PropertyInfo { name: "border-inline-end-width", is_shorthand: false },
+    PropertyInfo { name: "border-inline-start", is_shorthand: true },
+    PropertyInfo { name: "border-inline-start-color", is_shorthand: false },
+    PropertyInfo { name: "border-inline-start-style", is_shorthand: false },
+    PropertyInfo { name: "border-inline-start-width", is_shorthand: false },
+    PropertyInfo { name: "border-inline-style", is_shorthand: true },
+    PropertyInfo { name: "border-inline-width", is_shorthand: true },
+    PropertyInfo { name: "border-left", is_shorthand: true },
+    PropertyInfo { name: "border-left-color", is_shorthand: false },
+    PropertyInfo { name: "border-left-style", is_shorthand: false },
+    PropertyInfo { name: "border-left-width", is_shorthand: false },
+    PropertyInfo { name: "border-radius", is_shorthand: true },
+    PropertyInfo { name: "border-right", is_shorthand: true },
+    PropertyInfo { name: "border-right-color", is_shorthand: false },
+    PropertyInfo { name: "border-right-style", is_shorthand: false },
+    PropertyInfo { name: "border-right-width", is_shorthand: false },
+    PropertyInfo { name: "border-spacing", is_shorthand: false },
+    PropertyInfo { name: "border-start-end-radius", is_shorthand: false },
+    PropertyInfo { name: "border-start-start-radius", is_shorthand: false },
+    PropertyInfo { name: "border-style", is_shorthand: true },
+    PropertyInfo { name: "border-top", is_shorthand: true },
+    PropertyInfo { name: "border-top-color", is_shorthand: false },
+    PropertyInfo { name: "border-top-left-radius", is_shorthand: false },
+    PropertyInfo { name: "border-top-right-radius", is_shorthand: false },
+    PropertyInfo { name: "border-top-style", is_shorthand: false },
+    PropertyInfo { name: "border-top-width", is_shorthand: false },
+    PropertyInfo { name: "border-width", is_shorthand: true },
+    PropertyInfo { name: "bottom", is_shorthand: false },
+    PropertyInfo { name: "box-decoration-break", is_shorthand: false },
+    PropertyInfo { name: "box-shadow", is_shorthand: false },
+    PropertyInfo { name: "box-sizing", is_shorthand: false },
+    PropertyInfo { name: "break-after", is_shorthand: false },
+    PropertyInfo { name: "break-before", is_shorthand: false },
+    PropertyInfo { name: "break-inside", is_shorthand: false },
+    PropertyInfo { name: "caption-side", is_shorthand: false },
+    PropertyInfo { name: "caret-color", is_shorthand: false },
+    PropertyInfo { name: "clear", is_shorthand: false },
+    PropertyInfo { name: "clip", is_shorthand: false },
+    PropertyInfo { name: "clip-path", is_shorthand: false },
+    PropertyInfo { name: "clip-rule", is_shorthand: false },
+    PropertyInfo { name: "color", is_shorthand: false },
+    PropertyInfo { name: "color-interpolation", is_shorthand: false },
+    PropertyInfo { name: "color-interpolation-filters", is_shorthand: false },
+    PropertyInfo { name: "color-scheme", is_shorthand: false },
+    PropertyInfo { name: "column-count", is_shorthand: false },
+    PropertyInfo { name: "column-fill", is_shorthand: false },
+    PropertyInfo { name: "column-gap", is_shorthand: false },
+    PropertyInfo { name: "column-rule", is_shorthand: true },
+    PropertyInfo { name: "column-rule-color", is_shorthand: false },
+    PropertyInfo { name: "column-rule-style", is_shorthand: false },
+    PropertyInfo { name: "column-rule-width", is_shorthand: false },
+    PropertyInfo { name: "column-span", is_shorthand: false },
+    PropertyInfo { name: "column-width", is_shorthand: false },
+    PropertyInfo { name: "columns", is_shorthand: true },
+    PropertyInfo { name: "contain", is_shorthand: false },
+    PropertyInfo { name: "contain-intrinsic-block-size", is_shorthand: false },
+    PropertyInfo { name: "contain-intrinsic-height", is_shorthand: false },
+    PropertyInfo { name: "contain-intrinsic-inline-size", is_shorthand: false },
+    PropertyInfo { name: "contain-intrinsic-size", is_shorthand: true },
+    PropertyInfo { name: "contain-intrinsic-width", is_shorthand: false },
+    PropertyInfo { name: "container", is_shorthand: true },
+    PropertyInfo { name: "container-name", is_shorthand: false },
+    PropertyInfo { name: "container-type", is_shorthand: false },
+    PropertyInfo { name: "content", is_shorthand: false },
+    PropertyInfo { name: "content-visibility", is_shorthand: false },
+    PropertyInfo { name: "counter-increment", is_shorthand: false },
+    PropertyInfo { name: "counter-reset", is_shorthand: false },
+    PropertyInfo { name: "counter-set", is_shorthand: false },
+    PropertyInfo { name: "cursor", is_shorthand: false },
+    PropertyInfo { name: "cx", is_shorthand: false },
+    PropertyInfo { name: "cy", is_shorthand: false },
+    PropertyInfo { name: "d", is_shorthand: false },
+    PropertyInfo { name: "direction", is_shorthand: false },
+    PropertyInfo { name: "display", is_shorthand: false },
+    PropertyInfo { name: "dominant-baseline", is_shorthand: false },
+    PropertyInfo { name: "empty-cells", is_shorthand: false },
+    PropertyInfo { name: "fill", is_shorthand: false },
+    PropertyInfo { name: "fill-opacity", is_shorthand: false },
+    PropertyInfo { name: "fill-rule", is_shorthand: false },
+    PropertyInfo { name: "filter", is_shorthand: false },
+    PropertyInfo { name: "flex", is_shorthand: true },
+    PropertyInfo { name: "flex-basis", is_shorthand: false },
+    PropertyInfo { name: "flex-direction", is_shorthand: false },
+    PropertyInfo { name: "flex-flow", is_shorthand: true },
+    PropertyInfo { name: "flex-grow", is_shorthand: false },
+    PropertyInfo { name: "flex-shrink", is_shorthand: false },
+    PropertyInfo { name: "flex-wrap", is_shorthand: false },
+    PropertyInfo { name: "float", is_shorthand: false },
+    PropertyInfo { name: "flood-color", is_shorthand: false },
+    PropertyInfo { name: "flood-opacity", is_shorthand: false },
+    PropertyInfo { name: "font", is_shorthand: true },
+    PropertyInfo { name: "font-family", is_shorthand: false },
+    PropertyInfo { name: "font-feature-settings", is_shorthand: false },
+    PropertyInfo { name: "font-kerning", is_shorthand: false },
+    PropertyInfo { name: "font-language-override", is_shorthand: false },
+    PropertyInfo { name: "font-optical-sizing", is_shorthand: false },
+    PropertyInfo { name: "font-palette", is_shorthand: false },
+    PropertyInfo { name: "font-size", is_shorthand: false },
+    PropertyInfo { name: "font-size-adjust", is_shorthand: false },
+    PropertyInfo { name: "font-stretch", is_shorthand: false },
+    PropertyInfo { name: "font-style", is_shorthand: false },
+    PropertyInfo { name: "font-synthesis", is_shorthand: true },
+    PropertyInfo { name: "font-synthesis-small-caps", is_shorthand: false },
+    PropertyInfo { name: "font-synthesis-style", is_shorthand: false },
+    PropertyInfo { name: "font-synthesis-weight", is_shorthand: false },
+    PropertyInfo { name: "font-variant", is_shorthand: true },
+    PropertyInfo { name: "font-variant-alternates", is_shorthand: false },
+    PropertyInfo { name: "font-variant-caps", is_shorthand: false },
+    PropertyInfo { name: "font-variant-east-asian", is_shorthand: false },
+    PropertyInfo { name: "font-variant-emoji", is_shorthand: false },
+    PropertyInfo { name: "font-variant-ligatures", is_shorthand: false },
+    PropertyInfo { name: "font-variant-numeric", is_shorthand: false },
+    PropertyInfo { name: "font-variant-position", is_shorthand: false },
+    PropertyInfo { name: "font-variation-settings", is_shorthand: false },
+    PropertyInfo { name: "font-weight", is_shorthand: false },
+    PropertyInfo { name: "forced-color-adjust", is_shorthand: false },
+    PropertyInfo { name: "gap", is_shorthand: true },
+    PropertyInfo { name: "grid", is_shorthand: true },
+    PropertyInfo { name: "grid-area", is_shorthand: true },
+    PropertyInfo { name: "grid-auto-columns", is_shorthand: false },
+    PropertyInfo { name: "grid-auto-flow", is_shorthand: false },
+    PropertyInfo { name: "grid-auto-rows", is_shorthand: false },
+    PropertyInfo { name: "grid-column", is_shorthand: true },
+    PropertyInfo { name: "grid-column-end", is_shorthand: false },
+    PropertyInfo { name: "grid-column-start", is_shorthand: false },
+    PropertyInfo { name: "grid-row", is_shorthand: true },
+    PropertyInfo { name: "grid-row-end", is_shorthand: false },
+    PropertyInfo { name: "grid-row-start", is_shorthand: false },
+    PropertyInfo { name: "grid-template", is_shorthand: true },
+    PropertyInfo { name: "grid-template-areas", is_shorthand: false },
+    PropertyInfo { name: "grid-template-columns", is_shorthand: false },
+    PropertyInfo { name: "grid-template-rows", is_shorthand: false },
+    PropertyInfo { name: "hanging-punctuation", is_shorthand: false },
+    PropertyInfo { name: "height", is_shorthand: false },
+    PropertyInfo { name: "hyphenate-character", is_shorthand: false },
+    PropertyInfo { name: "hyphens", is_shorthand: false },
+    PropertyInfo { name: "image-orientation", is_shorthand: false },
+    PropertyInfo { name: "image-rendering", is_shorthand: false },
+    PropertyInfo { name: "inline-size", is_shorthand: false },
+    PropertyInfo { name: "inset", is_shorthand: true },
+    PropertyInfo { name: "inset-block", is_shorthand: true },
+    PropertyInfo { name: "inset-block-end", is_shorthand: false },
+    PropertyInfo { name: "inset-block-start", is_shorthand: false },
+    PropertyInfo { name: "inset-inline", is_shorthand: true },
+    PropertyInfo { name: "inset-inline-end", is_shorthand: false },
+    PropertyInfo { name: "inset-inline-start", is_shorthand: false },
+    PropertyInfo { name: "isolation", is_shorthand: false },
+    PropertyInfo { name: "justify-content", is_shorthand: false },
+    PropertyInfo { name: "justify-items", is_shorthand: false },
+    PropertyInfo { name: "justify-self", is_shorthand: false },
+    PropertyInfo { name: "left", is_shorthand: false },
+    PropertyInfo { name: "letter-spacing", is_shorthand: false },
+    PropertyInfo { name: "lighting-color", is_shorthand: false },
+    PropertyInfo { name: "line-break", is_shorthand: false },
+    PropertyInfo { name: "line-height", is_shorthand: false },
+    PropertyInfo { name: "list-style", is_shorthand: true },
+    PropertyInfo { name: "list-style-image", is_shorthand: false },
+    PropertyInfo { name: "list-style-position", is_shorthand: false },
+    PropertyInfo { name: "list-style-type", is_shorthand: false },
+    PropertyInfo { name: "margin", is_shorthand: true },
+    PropertyInfo { name: "margin-block", is_shorthand: true },
+    PropertyInfo { name: "margin-block-end", is_shorthand: false },
+    PropertyInfo { name: "margin-block-start", is_shorthand: false },
+    PropertyInfo { name: "margin-bottom", is_shorthand: false },
+    PropertyInfo { name: "margin-inline", is_shorthand: true },
+    PropertyInfo { name: "margin-inline-end", is_shorthand: false },
+    PropertyInfo { name: "margin-inline-start", is_shorthand: false },
+    PropertyInfo { name: "margin-left", is_shorthand: false },
+    PropertyInfo { name: "margin-right", is_shorthand: false },
+    PropertyInfo { name: "margin-top", is_shorthand: false },
+    PropertyInfo { name: "marker", is_shorthand: true },
+    PropertyInfo { name: "marker-end", is_shorthand: false },
+    PropertyInfo { name: "marker-mid", is_shorthand: false },
+    PropertyInfo { name: "marker-start", is_shorthand: false },
+    PropertyInfo { name: "mask", is_shorthand: true },
+    PropertyInfo { name: "mask-border", is_shorthand: true },
+    PropertyInfo { name: "mask-border-mode", is_shorthand: false },
+    PropertyInfo { name: "mask-border-outset", is_shorthand: false },
+    PropertyInfo { name: "mask-border-repeat", is_shorthand: false },
+    PropertyInfo { name: "mask-border-slice", is_shorthand: false },
+    PropertyInfo { name: "mask-border-source", is_shorthand: false },
+    PropertyInfo { name: "mask-border-width", is_shorthand: false },
+    PropertyInfo { name: "mask-clip", is_shorthand: false },
+    PropertyInfo { name: "mask-composite", is_shorthand: false },
+    PropertyInfo { name: "mask-image", is_shorthand: false },
+    PropertyInfo { name: "mask-mode", is_shorthand: false },
+    PropertyInfo { name: "mask-origin", is_shorthand: false },
+    PropertyInfo { name: "mask-position", is_shorthand: false },
+    PropertyInfo { name: "mask-repeat", is_shorthand: false },
+    PropertyInfo { name: "mask-size", is_shorthand: false },
+    PropertyInfo { name: "mask-type", is_shorthand: false },
+    PropertyInfo { name: "math-depth", is_shorthand: false },
+    PropertyInfo { name: "math-shift", is_shorthand: false },
+    PropertyInfo { name: "math-style", is_shorthand: false },
+    PropertyInfo { name: "max-block-size", is_shorthand: false },
+    PropertyInfo { name: "max-height", is_shorthand: false },
+    PropertyInfo { name: "max-inline-size", is_shorthand: false },
+    PropertyInfo { name: "max-width", is_shorthand: false },
+    PropertyInfo { name: "min-block-size", is_shorthand: false },
+    PropertyInfo { name: "min-height", is_shorthand: false },
+    PropertyInfo { name: "min-inline-size", is_shorthand: false },
+    PropertyInfo { name: "min-width", is_shorthand: false },
+    PropertyInfo { name: "mix-blend-mode", is_shorthand: false },
+    PropertyInfo { name: "object-fit", is_shorthand: false },
+    PropertyInfo { name: "object-position", is_shorthand: false },
+    PropertyInfo { name: "offset", is_shorthand: true },
+    PropertyInfo { name: "offset-anchor", is_shorthand: false },
+    PropertyInfo { name: "offset-distance", is_shorthand: false },
+    PropertyInfo { name: "offset-path", is_shorthand: false },
+    PropertyInfo { name: "offset-position", is_shorthand: false },
+    PropertyInfo { name: "offset-rotate", is_shorthand: false },
+    PropertyInfo { name: "opacity", is_shorthand: false },
+    PropertyInfo { name: "order", is_shorthand: false },
+    PropertyInfo { name: "orphans", is_shorthand: false },
+    PropertyInfo { name: "outline", is_shorthand: true },
+    PropertyInfo { name: "outline-color", is_shorthand: false },
+    PropertyInfo { name: "outline-offset", is_shorthand: false },
+    PropertyInfo { name: "outline-style", is_shorthand: false },
+    PropertyInfo { name: "outline-width", is_shorthand: false },
+    PropertyInfo { name: "overflow", is_shorthand: true },
+    PropertyInfo { name: "overflow-anchor", is_shorthand: false },
+    PropertyInfo { name: "overflow-block", is_shorthand: false },
+    PropertyInfo { name: "overflow-clip-margin", is_shorthand: false },
+    PropertyInfo { name: "overflow-inline", is_shorthand: false },
+    PropertyInfo { name: "overflow-wrap", is_shorthand: false },
+    PropertyInfo { name: "overflow-x", is_shorthand: false },
+    PropertyInfo { name: "overflow-y", is_shorthand: false },
+    PropertyInfo { name: "overscroll-behavior", is_shorthand: true },
+    PropertyInfo { name: "overscroll-behavior-block", is_shorthand: false },
+    PropertyInfo { name: "overscroll-behavior-inline", is_shorthand: false },
+    PropertyInfo { name: "overscroll-behavior-x", is_shorthand: false },
+    PropertyInfo { name: "overscroll-behavior-y", is_shorthand: false },
+    PropertyInfo { name: "padding", is_shorthand: true },
+    PropertyInfo { name: "padding-block", is_shorthand: true },
+    PropertyInfo { name: "padding-block-end", is_shorthand: false },
+    PropertyInfo { name: "padding-block-start", is_shorthand: false },
+    PropertyInfo { name: "padding-bottom", is_shorthand: false },
+    PropertyInfo { name: "padding-inline", is_shorthand: true },
+    PropertyInfo { name: "padding-inline-end", is_shorthand: false },
+    PropertyInfo { name: "padding-inline-start", is_shorthand: false },
+    PropertyInfo { name: "padding-left", is_shorthand: false },
+    PropertyInfo { name: "padding-right", is_shorthand: false },
+    PropertyInfo { name: "padding-top", is_shorthand: false },
+    PropertyInfo { name: "page", is_shorthand: false },
+    PropertyInfo { name: "page-break-after", is_shorthand: false },
+    PropertyInfo { name: "page-break-before", is_shorthand: false },
+    PropertyInfo { name: "page-break-inside", is_shorthand: false },
+    PropertyInfo { name: "paint-order", is_shorthand: false },
+    PropertyInfo { name: "perspective", is_shorthand: false },
+    PropertyInfo { name: "perspective-origin", is_shorthand: false },
+    PropertyInfo { name: "place-content", is_shorthand: true },
+    PropertyInfo { name: "place-items", is_shorthand: true },
+    PropertyInfo { name: "place-self", is_shorthand: true },
+    PropertyInfo { name: "pointer-events", is_shorthand: false },
+    PropertyInfo { name: "position", is_shorthand: false },
+    PropertyInfo { name: "print-color-adjust", is_shorthand: false },
+    PropertyInfo { name: "quotes", is_shorthand: false },
+    PropertyInfo { name: "r", is_shorthand: false },
+    PropertyInfo { name: "resize", is_shorthand: false },
+    PropertyInfo { name: "right", is_shorthand: false },
+    PropertyInfo { name: "rotate", is_shorthand: false },
+    PropertyInfo { name: "row-gap", is_shorthand: false },
+    PropertyInfo { name: "ruby-align", is_shorthand: false },
+    PropertyInfo { name: "ruby-position", is_shorthand: false },
+    PropertyInfo { name: "rx", is_shorthand: false },
+    PropertyInfo { name: "ry", is_shorthand: false },
+    PropertyInfo { name: "scale", is_shorthand: false },
+    PropertyInfo { name: "scroll-behavior", is_shorthand: false },
+    PropertyInfo { name: "scroll-margin", is_shorthand: true },
+    PropertyInfo { name: "scroll-margin-block", is_shorthand: true },
+    PropertyInfo { name: "scroll-margin-block-end", is_shorthand: false },
+    PropertyInfo { name: "scroll-margin-block-start", is_shorthand: false },
+    PropertyInfo { name: "scroll-margin-bottom", is_shorthand: false },
+    PropertyInfo { name: "scroll-margin-inline", is_shorthand: true },
+    PropertyInfo { name: "scroll-margin-inline-end", is_shorthand: false },
+    PropertyInfo { name: "scroll-margin-inline-start", is_shorthand: false },
+    PropertyInfo { name: "scroll-margin-left", is_shorthand: false },
+    PropertyInfo { name: "scroll-margin-right", is_shorthand: false },
+    PropertyInfo { name: "scroll-margin-top", is_shorthand: false },
+    PropertyInfo { name: "scroll-padding", is_shorthand: true },
+    PropertyInfo { name: "scroll-padding-block", is_shorthand: true },
+    PropertyInfo { name: "scroll-padding-block-end", is_shorthand: false },
+    PropertyInfo { name: "scroll-padding-block-start", is_shorthand: false },
+    PropertyInfo { name: "scroll-padding-bottom", is_shorthand: false },
+    PropertyInfo { name: "scroll-padding-inline", is_shorthand: true },
+    PropertyInfo { name: "scroll-padding-inline-end", is_shorthand: false },
+    PropertyInfo { name: "scroll-padding-inline-start", is_shorthand: false },
+    PropertyInfo { name: "scroll-padding-left", is_shorthand: false },
+    PropertyInfo { name: "scroll-padding-right", is_shorthand: false },
+    PropertyInfo { name: "scroll-padding-top", is_shorthand: false },
+    PropertyInfo { name: "scroll-snap-align", is_shorthand: false },
+    PropertyInfo { name: "scroll-snap-stop", is_shorthand: false },
+    PropertyInfo { name: "scroll-snap-type", is_shorthand: false },
+    PropertyInfo { name: "scroll-timeline", is_shorthand: true },
+    PropertyInfo { name: "scroll-timeline-axis", is_shorthand: false },
+    PropertyInfo { name: "scroll-timeline-name", is_shorthand: false },
+    PropertyInfo { name: "scrollbar-color", is_shorthand: false },
+    PropertyInfo { name: "scrollbar-gutter", is_shorthand: false },
+    PropertyInfo { name: "scrollbar-width", is_shorthand: false },
+    PropertyInfo { name: "shape-image-threshold", is_shorthand: false },
+    PropertyInfo { name: "shape-margin", is_shorthand: false },
+    PropertyInfo { name: "shape-outside", is_shorthand: false },
+    PropertyInfo { name: "shape-rendering", is_shorthand: false },
+    PropertyInfo { name: "stop-color", is_shorthand: false },
+    PropertyInfo { name: "stop-opacity", is_shorthand: false },
+    PropertyInfo { name: "stroke", is_shorthand: false },
+    PropertyInfo { name: "stroke-dasharray", is_shorthand: false },
+    PropertyInfo { name: "stroke-dashoffset", is_shorthand: false },
+    PropertyInfo { name: "stroke-linecap", is_shorthand: false },
+    PropertyInfo { name: "stroke-linejoin", is_shorthand: false },
+    PropertyInfo { name: "stroke-miterlimit", is_shorthand: false },
+    PropertyInfo { name: "stroke-opacity", is_shorthand: false },
+    PropertyInfo { name: "stroke-width", is_shorthand: false },
+    PropertyInfo { name: "tab-size", is_shorthand: false },
+    PropertyInfo { name: "table-layout", is_shorthand: false },
+    PropertyInfo { name: "text-align", is_shorthand: false },
+    PropertyInfo { name: "text-align-last", is_shorthand: false },
+    PropertyInfo { name: "text-anchor", is_shorthand: false },
+    PropertyInfo { name: "text-combine-upright", is_shorthand: false },
+    PropertyInfo { name: "text-decoration", is_shorthand: true },
+    PropertyInfo { name: "text-decoration-color", is_shorthand: false },
+    PropertyInfo { name: "text-decoration-line", is_shorthand: false },
+    PropertyInfo { name: "text-decoration-skip-ink", is_shorthand: false },
+    PropertyInfo { name: "text-decoration-style", is_shorthand: false },
+    PropertyInfo { name: "text-decoration-thickness", is_shorthand: false },
+    PropertyInfo { name: "text-emphasis", is_shorthand: true },
+    PropertyInfo { name: "text-emphasis-color", is_shorthand: false },
+    PropertyInfo { name: "text-emphasis-position", is_shorthand: false },
+    PropertyInfo { name: "text-emphasis-style", is_shorthand: false },
+    PropertyInfo { name: "text-indent", is_shorthand: false },
+    PropertyInfo { name: "text-justify", is_shorthand: false },
+    PropertyInfo { name: "text-orientation", is_shorthand: false },
+    PropertyInfo { name: "text-overflow", is_shorthand: false },
+    PropertyInfo { name: "text-rendering", is_shorthand: false },
+    PropertyInfo { name: "text-shadow", is_shorthand: false },
+    PropertyInfo { name: "text-transform", is_shorthand: false },
+    PropertyInfo { name: "text-underline-offset", is_shorthand: false },
+    PropertyInfo { name: "text-underline-position", is_shorthand: false },
+    PropertyInfo { name: "text-wrap", is_shorthand: true },
+    PropertyInfo { name: "text-wrap-mode", is_shorthand: false },
+    PropertyInfo { name: "text-wrap-style", is_shorthand: false },
+    PropertyInfo { name: "top", is_shorthand: false },
+    PropertyInfo { name: "touch-action", is_shorthand: false },
+    PropertyInfo { name: "transform", is_shorthand: false },
+    PropertyInfo { name: "transform-box", is_shorthand: false },
+    PropertyInfo { name: "transform-origin", is_shorthand: false },
+    PropertyInfo { name: "transform-style", is_shorthand: false },
+    PropertyInfo { name: "transition", is_shorthand: true },
+    PropertyInfo { name: "transition-behavior", is_shorthand: false },
+    PropertyInfo { name: "transition-delay", is_shorthand: false },
+    PropertyInfo { name: "transition-duration", is_shorthand: false },
+    PropertyInfo { name: "transition-property", is_shorthand: false },
+    PropertyInfo { name: "transition-timing-function", is_shorthand: false },
+    PropertyInfo { name: "translate", is_shorthand: false },
+    PropertyInfo { name: "unicode-bidi", is_shorthand: false },
+    PropertyInfo { name: "user-select", is_shorthand: false },
+    PropertyInfo { name: "vector-effect", is_shorthand: false },
+    PropertyInfo { name: "vertical-align", is_shorthand: false },
+    PropertyInfo { name: "view-timeline", is_shorthand: true },
+    PropertyInfo { name: "view-timeline-axis", is_shorthand: false },
+    PropertyInfo { name: "view-timeline-inset", is_shorthand: false },
+    PropertyInfo { name: "view-timeline-name", is_shorthand: false },
+    PropertyInfo { name: "view-transition-name", is_shorthand: false },
+    PropertyInfo { name: "visibility", is_shorthand: false },
+    PropertyInfo { name: "white-space", is_shorthand: true },
+    PropertyInfo { name: "white-space-collapse", is_shorthand: false },
+    PropertyInfo { name: "widows", is_shorthand: false },
+    PropertyInfo { name: "width", is_shorthand: false },
+    PropertyInfo { name: "will-change", is_shorthand: false },
+    PropertyInfo { name: "word-break", is_shorthand: false },
+    PropertyInfo { name: "word-spacing", is_shorthand: false },
+    PropertyInfo { name: "writing-mode", is_shorthand: false },
+    PropertyInfo { name: "x", is_shorthand: false },
+    PropertyInfo { name: "y", is_shorthand: false },
+    PropertyInfo { name: "z-index", is_shorthand: false },
+    PropertyInfo { name: "zoom", is_shorthand: false },
+];
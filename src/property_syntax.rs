@@ -0,0 +1,349 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+// https://drafts.css-houdini.org/css-properties-values-api/#syntax-strings
+
+use std::ascii::AsciiExt;
+
+use super::{Token, Parser, ParseError, ParseErrorKind, Color, UnitKind, parse_math_function};
+use parser::unexpected_token;
+
+
+/// The value of the `syntax` descriptor of an `@property` rule,
+/// such as `"<length> | <color>#"`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SyntaxDescriptor {
+    /// `"*"`, which matches any value.
+    Universal,
+
+    /// Alternatives separated by `|`, at least one.
+    Components(Vec<SyntaxComponent>),
+}
+
+
+/// One of the alternatives of a `SyntaxDescriptor`, such as `<color>#`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SyntaxComponent {
+    /// The data type or keyword.
+    pub name: SyntaxComponentName,
+
+    /// Whether this component is a list, and of which kind.
+    pub multiplier: Option<SyntaxMultiplier>,
+}
+
+
+/// What a single `SyntaxComponent` matches.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SyntaxComponentName {
+    /// A data type, such as `<length>`.
+    DataType(DataType),
+
+    /// A keyword, matched case-sensitively.
+    Ident(String),
+}
+
+
+/// A data type supported in a `SyntaxDescriptor`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[allow(missing_docs)]
+pub enum DataType {
+    Angle,
+    Color,
+    CustomIdent,
+    Image,
+    Integer,
+    Length,
+    LengthPercentage,
+    Number,
+    Percentage,
+    Resolution,
+    String,
+    Time,
+    Url,
+    TransformFunction,
+    TransformList,
+}
+
+
+/// The `+` or `#` suffix of a `SyntaxComponent`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SyntaxMultiplier {
+    /// `+`: a space-separated list of one or more values.
+    SpaceSeparated,
+
+    /// `#`: a comma-separated list of one or more values.
+    CommaSeparated,
+}
+
+
+impl SyntaxDescriptor {
+    /// Parse the string value of a `syntax` descriptor (without quotes or escapes).
+    ///
+    /// Return an error if the syntax is invalid,
+    /// in which case the `@property` rule is invalid.
+    pub fn parse(syntax: &str) -> Result<SyntaxDescriptor, ParseError> {
+        Parser::new(syntax).parse_entirely(|input| {
+            if input.try(|input| input.expect_delim('*')).is_ok() {
+                return Ok(SyntaxDescriptor::Universal)
+            }
+            let mut components = Vec::new();
+            loop {
+                components.push(try!(parse_component(input)));
+                let start = input.position();
+                match input.next() {
+                    Ok(Token::Delim('|')) => {}
+                    Err(_) => return Ok(SyntaxDescriptor::Components(components)),
+                    Ok(token) => return Err(unexpected_token(token, start))
+                }
+            }
+        })
+    }
+
+    /// Check whether the rest of the input (such as a declaration value) matches this syntax.
+    ///
+    /// This only checks the syntax of values, not their ranges:
+    /// for example, math functions are accepted for every numeric data type
+    /// regardless of the units they use.
+    /// `var()` functions should be substituted first.
+    pub fn matches(&self, input: &mut Parser) -> bool {
+        match *self {
+            SyntaxDescriptor::Universal => true,
+            SyntaxDescriptor::Components(ref components) => components.iter().any(|component| {
+                input.try(|input| input.parse_entirely(|input| component.parse_value(input))).is_ok()
+            })
+        }
+    }
+}
+
+
+impl SyntaxComponent {
+    fn parse_value<'i, 't>(&self, input: &mut Parser<'i, 't>) -> Result<(), ParseError<'i>> {
+        match self.multiplier {
+            None => self.parse_single_value(input),
+            Some(SyntaxMultiplier::SpaceSeparated) => {
+                try!(self.parse_single_value(input));
+                while !input.is_exhausted() {
+                    try!(self.parse_single_value(input))
+                }
+                Ok(())
+            }
+            Some(SyntaxMultiplier::CommaSeparated) => {
+                input.parse_comma_separated(|input| self.parse_single_value(input)).map(|_| ())
+            }
+        }
+    }
+
+    fn parse_single_value<'i, 't>(&self, input: &mut Parser<'i, 't>)
+                                  -> Result<(), ParseError<'i>> {
+        match self.name {
+            SyntaxComponentName::DataType(data_type) => parse_data_type(data_type, input),
+            SyntaxComponentName::Ident(ref expected) => {
+                let start = input.position();
+                match try!(input.next()) {
+                    Token::Ident(ref value) if value == expected => Ok(()),
+                    token => Err(unexpected_token(token, start))
+                }
+            }
+        }
+    }
+}
+
+
+fn parse_component<'i, 't>(input: &mut Parser<'i, 't>)
+                           -> Result<SyntaxComponent, ParseError<'i>> {
+    let start = input.position();
+    let name = match try!(input.next()) {
+        Token::Delim('<') => {
+            let start = input.position();
+            let data_type = match try!(input.next_including_whitespace()) {
+                Token::Ident(name) => match data_type_from_name(&name) {
+                    Ok(data_type) => data_type,
+                    Err(()) => return Err(unexpected_token(Token::Ident(name), start))
+                },
+                token => return Err(unexpected_token(token, start))
+            };
+            let start = input.position();
+            match try!(input.next_including_whitespace()) {
+                Token::Delim('>') => SyntaxComponentName::DataType(data_type),
+                token => return Err(unexpected_token(token, start))
+            }
+        }
+        Token::Ident(name) => {
+            if is_reserved_keyword(&name) {
+                return Err(unexpected_token(Token::Ident(name), start))
+            }
+            SyntaxComponentName::Ident(name.into_owned())
+        }
+        token => return Err(unexpected_token(token, start))
+    };
+    let start_position = input.position();
+    let multiplier = match input.next_including_whitespace() {
+        Ok(Token::Delim('+')) => Some(SyntaxMultiplier::SpaceSeparated),
+        Ok(Token::Delim('#')) => Some(SyntaxMultiplier::CommaSeparated),
+        _ => {
+            input.reset(start_position);
+            None
+        }
+    };
+    // `<transform-list>` is already a list.
+    if multiplier.is_some() && name == SyntaxComponentName::DataType(DataType::TransformList) {
+        return Err(ParseError {
+            kind: ParseErrorKind::Invalid,
+            position: start_position,
+        })
+    }
+    Ok(SyntaxComponent {
+        name: name,
+        multiplier: multiplier,
+    })
+}
+
+
+fn data_type_from_name(name: &str) -> Result<DataType, ()> {
+    Ok(match name {
+        "angle" => DataType::Angle,
+        "color" => DataType::Color,
+        "custom-ident" => DataType::CustomIdent,
+        "image" => DataType::Image,
+        "integer" => DataType::Integer,
+        "length" => DataType::Length,
+        "length-percentage" => DataType::LengthPercentage,
+        "number" => DataType::Number,
+        "percentage" => DataType::Percentage,
+        "resolution" => DataType::Resolution,
+        "string" => DataType::String,
+        "time" => DataType::Time,
+        "url" => DataType::Url,
+        "transform-function" => DataType::TransformFunction,
+        "transform-list" => DataType::TransformList,
+        _ => return Err(())
+    })
+}
+
+
+/// CSS-wide keywords and `default` can not be used as keywords or `<custom-ident>`.
+fn is_reserved_keyword(name: &str) -> bool {
+    match_ignore_ascii_case! { name,
+        "initial" => true,
+        "inherit" => true,
+        "unset" => true,
+        "revert" => true,
+        "revert-layer" => true,
+        "default" => true,
+        _ => false
+    }
+}
+
+
+fn parse_data_type<'i, 't>(data_type: DataType, input: &mut Parser<'i, 't>)
+                           -> Result<(), ParseError<'i>> {
+    let start = input.position();
+    match data_type {
+        DataType::Color => Color::parse(input).map(|_| ()),
+        DataType::CustomIdent => match try!(input.next()) {
+            Token::Ident(ref name) if !is_reserved_keyword(name) => Ok(()),
+            token => Err(unexpected_token(token, start))
+        },
+        DataType::String => { try!(input.expect_string()); Ok(()) }
+        DataType::Url => { try!(input.expect_url()); Ok(()) }
+        DataType::Image => {
+            if input.try(|input| input.expect_url()).is_ok() {
+                return Ok(())
+            }
+            let name = try!(input.expect_function());
+            if !is_image_function(&name) {
+                return Err(unexpected_token(Token::Function(name), start))
+            }
+            input.parse_nested_block(|input| {
+                while input.next().is_ok() {}
+                Ok(())
+            })
+        }
+        DataType::TransformFunction => parse_transform_function(input),
+        DataType::TransformList => {
+            try!(parse_transform_function(input));
+            while !input.is_exhausted() {
+                try!(parse_transform_function(input))
+            }
+            Ok(())
+        }
+        DataType::Angle | DataType::Integer | DataType::Length | DataType::LengthPercentage |
+        DataType::Number | DataType::Percentage | DataType::Resolution | DataType::Time => {
+            let token = try!(input.next());
+            let matches = match token {
+                Token::Number(ref value) => match data_type {
+                    DataType::Number => true,
+                    DataType::Integer => value.int_value.is_some(),
+                    // Unitless zero lengths.
+                    DataType::Length | DataType::LengthPercentage => value.value == 0.,
+                    _ => false
+                },
+                Token::Percentage(_) => {
+                    data_type == DataType::Percentage || data_type == DataType::LengthPercentage
+                }
+                Token::Dimension(_, ref unit) => match (data_type, UnitKind::of(unit)) {
+                    (DataType::Angle, Ok(UnitKind::Angle)) |
+                    (DataType::Length, Ok(UnitKind::Length)) |
+                    (DataType::LengthPercentage, Ok(UnitKind::Length)) |
+                    (DataType::Resolution, Ok(UnitKind::Resolution)) |
+                    (DataType::Time, Ok(UnitKind::Time)) => true,
+                    _ => false
+                },
+                Token::Function(ref name) => {
+                    return input.parse_nested_block(|input| {
+                        parse_math_function(name, input).map(|_| ())
+                    })
+                }
+                _ => false
+            };
+            if matches { Ok(()) } else { Err(unexpected_token(token, start)) }
+        }
+    }
+}
+
+
+fn parse_transform_function<'i, 't>(input: &mut Parser<'i, 't>) -> Result<(), ParseError<'i>> {
+    let start = input.position();
+    let name = try!(input.expect_function());
+    let known = match_ignore_ascii_case! { name,
+        "matrix" => true,
+        "matrix3d" => true,
+        "translate" => true,
+        "translate3d" => true,
+        "translatex" => true,
+        "translatey" => true,
+        "translatez" => true,
+        "scale" => true,
+        "scale3d" => true,
+        "scalex" => true,
+        "scaley" => true,
+        "scalez" => true,
+        "rotate" => true,
+        "rotate3d" => true,
+        "rotatex" => true,
+        "rotatey" => true,
+        "rotatez" => true,
+        "skew" => true,
+        "skewx" => true,
+        "skewy" => true,
+        "perspective" => true,
+        _ => false
+    };
+    if !known {
+        return Err(unexpected_token(Token::Function(name), start))
+    }
+    input.parse_nested_block(|input| {
+        while input.next().is_ok() {}
+        Ok(())
+    })
+}
+
+
+fn is_image_function(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    name.ends_with("linear-gradient") || name.ends_with("radial-gradient") ||
+    name.ends_with("conic-gradient") ||
+    name == "image" || name == "image-set" || name == "cross-fade" || name == "element"
+}
+
@@ -0,0 +1,91 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::cmp;
+use std::fmt::Write;
+
+use ast::Diagnostic;
+use parser::byte_index;
+use super::SyntaxError;
+
+
+/// Render a syntax error recorded by `Parser::record_syntax_errors`
+/// as a human-readable message with the line of `css` where it occurs,
+/// and a caret under the position of the error:
+///
+/// ```text
+/// error: unclosed comment
+///  --> 1:5
+///   |
+/// 1 | a { /* b
+///   |     ^
+/// ```
+///
+/// `css` must be the input that the error was found in.
+pub fn render_syntax_error(css: &str, error: &SyntaxError) -> String {
+    let starts = line_starts(css);
+    let line_start = starts.get(error.location.line - 1).cloned().unwrap_or(css.len());
+    let start = cmp::min(line_start + error.location.column - 1, css.len());
+    render(css, &starts, &error.code.to_string(), start, start)
+}
+
+
+/// Render a diagnostic returned by `parse_stylesheet_with_diagnostics`
+/// or `parse_block_contents_with_diagnostics`
+/// like `render_syntax_error` does, with carets under the erroneous part of the input
+/// (until the end of its first line).
+///
+/// `css` must be the input that the diagnostic was found in.
+pub fn render_diagnostic(css: &str, diagnostic: &Diagnostic) -> String {
+    let start = byte_index(diagnostic.range.start);
+    let end = byte_index(diagnostic.range.end);
+    render(css, &line_starts(css), &diagnostic.kind.to_string(), start, end)
+}
+
+
+/// The byte index of the start of each line, per the newlines of CSS Syntax.
+fn line_starts(css: &str) -> Vec<usize> {
+    let mut starts = vec![0];
+    let mut chars = css.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\r' if chars.peek().map(|&(_, c)| c) == Some('\n') => {}
+            '\n' | '\r' | '\x0C' => starts.push(index + 1),
+            _ => {}
+        }
+    }
+    starts
+}
+
+
+fn render(css: &str, line_starts: &[usize], message: &str, start: usize, end: usize) -> String {
+    let line_index = match line_starts.binary_search(&start) {
+        Ok(index) => index,
+        Err(index) => index - 1,
+    };
+    let line_start = line_starts[line_index];
+    let line_end = line_starts.get(line_index + 1).map_or(css.len(), |&next_start| {
+        let line = &css[line_start..next_start];
+        line_start + line.trim_end_matches(|c| matches!(c, '\n' | '\r' | '\x0C')).len()
+    });
+    let line = &css[line_start..line_end];
+    let start = cmp::min(start, line_end);
+    let end = cmp::max(cmp::min(end, line_end), start);
+
+    // Keep tabs in the padding so that carets line up with the source.
+    let padding: String = css[line_start..start].chars().map(|c| {
+        if c == '\t' { '\t' } else { ' ' }
+    }).collect();
+    let carets = cmp::max(css[start..end].chars().count(), 1);
+
+    let line_number = (line_index + 1).to_string();
+    let gutter: String = line_number.chars().map(|_| ' ').collect();
+    let mut result = String::new();
+    write!(result, "error: {}\n", message).unwrap();
+    write!(result, "{}--> {}:{}\n", gutter, line_number, start - line_start + 1).unwrap();
+    write!(result, "{} |\n", gutter).unwrap();
+    write!(result, "{} | {}\n", line_number, line).unwrap();
+    write!(result, "{} | {}{}\n", gutter, padding, "^".repeat(carets)).unwrap();
+    result
+}
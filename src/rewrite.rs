@@ -0,0 +1,106 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use parser::{byte_index, position_from_byte_index};
+use super::{SourceRange, Node};
+
+
+/// Format-preserving editing of a stylesheet.
+///
+/// Replacements are recorded for ranges of the input (typically the spans of a tree
+/// built by `parse_stylesheet` from the same input),
+/// and `Rewriter::finish` re-emits the input with all other bytes copied verbatim,
+/// including comments and whitespace.
+///
+/// ```{rust,ignore}
+/// let mut input = Parser::new(css);
+/// let rules = parse_stylesheet(&mut input);
+/// let mut rewriter = Rewriter::new(css);
+/// // For each `declaration` to change, found in `rules`:
+/// try!(rewriter.replace_nodes(&declaration.value, "blue".to_owned()));
+/// let new_css = rewriter.finish();
+/// ```
+pub struct Rewriter<'i> {
+    input: &'i str,
+    /// Non-overlapping `(start, end, replacement)` edits in byte indices, sorted by position.
+    edits: Vec<(usize, usize, String)>,
+}
+
+
+impl<'i> Rewriter<'i> {
+    /// Create a new `Rewriter` for the given input, with no edit yet.
+    pub fn new(input: &'i str) -> Rewriter<'i> {
+        Rewriter {
+            input: input,
+            edits: Vec::new(),
+        }
+    }
+
+    /// Replace the given range of the input, such as the `span` of a rule or declaration.
+    ///
+    /// Return `Err(())` and do nothing if the range overlaps with a previous edit.
+    /// (Insertions, with an empty range, can be made at the same position as other edits
+    /// and are kept in the order they were made.)
+    pub fn replace(&mut self, range: SourceRange, replacement: String)
+                   -> Result<(), ()> {
+        let start = byte_index(range.start);
+        let end = byte_index(range.end);
+        debug_assert!(start <= end && end <= self.input.len());
+        let index = self.edits.iter().position(|&(other_start, other_end, _)| {
+            other_start > start || (other_start == start && other_end > start)
+        }).unwrap_or(self.edits.len());
+        if index > 0 && self.edits[index - 1].1 > start {
+            return Err(())
+        }
+        if index < self.edits.len() && self.edits[index].0 < end {
+            return Err(())
+        }
+        self.edits.insert(index, (start, end, replacement));
+        Ok(())
+    }
+
+    /// Remove the given range of the input.
+    ///
+    /// Return `Err(())` and do nothing if the range overlaps with a previous edit.
+    pub fn remove(&mut self, range: SourceRange) -> Result<(), ()> {
+        self.replace(range, String::new())
+    }
+
+    /// Replace the source text of a non-empty list of component values,
+    /// such as the prelude of a rule or the value of a declaration.
+    ///
+    /// Return `Err(())` and do nothing if the list is empty
+    /// or if it overlaps with a previous edit.
+    pub fn replace_nodes(&mut self, nodes: &[Node], replacement: String) -> Result<(), ()> {
+        match (nodes.first(), nodes.last()) {
+            (Some(first), Some(last)) => self.replace(first.span.start..last.span.end, replacement),
+            _ => Err(())
+        }
+    }
+
+    /// Return the input with all edits applied.
+    pub fn finish(&self) -> String {
+        self.finish_range(position_from_byte_index(0)..position_from_byte_index(self.input.len()))
+    }
+
+    /// Return the given range of the input with the edits inside it applied.
+    ///
+    /// Edits that are not entirely inside the range are ignored.
+    pub fn finish_range(&self, range: SourceRange) -> String {
+        let start = byte_index(range.start);
+        let end = byte_index(range.end);
+        let mut result = String::with_capacity(end - start);
+        let mut position = start;
+        for &(edit_start, edit_end, ref replacement) in &self.edits {
+            if edit_start < start || edit_end > end {
+                continue
+            }
+            result.push_str(&self.input[position..edit_start]);
+            result.push_str(replacement);
+            position = edit_end;
+        }
+        result.push_str(&self.input[position..end]);
+        result
+    }
+}
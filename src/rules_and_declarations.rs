@@ -5,21 +5,188 @@
 // https://drafts.csswg.org/css-syntax/#parsing
 
 use std::ascii::AsciiExt;
-use std::ops::Range;
-use std::borrow::Cow;
-use super::{Token, Parser, Delimiter, SourcePosition};
+use std::error::Error;
+use std::fmt;
+use super::{Token, Parser, ParseError, ParseErrorKind, ToCss, Delimiter, SourcePosition};
+use super::SourceRange;
+use parser::{unexpected_token, byte_index, MaybeOwned};
 
 
 /// Parse `!important`.
 ///
 /// Typical usage is `input.try(parse_important).is_ok()`
 /// at the end of a `DeclarationParser::parse_value` implementation.
-pub fn parse_important(input: &mut Parser) -> Result<(), ()> {
+pub fn parse_important<'i, 't>(input: &mut Parser<'i, 't>) -> Result<(), ParseError<'i>> {
     try!(input.expect_delim('!'));
     input.expect_ident_matching("important")
 }
 
 
+/// A [CSS-wide keyword](https://drafts.csswg.org/css-cascade/#defaulting-keywords),
+/// valid as the entire value of any property.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum CssWideKeyword {
+    /// `initial`
+    Initial,
+
+    /// `inherit`
+    Inherit,
+
+    /// `unset`
+    Unset,
+
+    /// `revert`
+    Revert,
+
+    /// `revert-layer`
+    RevertLayer,
+}
+
+
+impl CssWideKeyword {
+    /// Recognize a CSS-wide keyword from the (unescaped) value of an ident,
+    /// ASCII case-insensitively.
+    pub fn from_ident(ident: &str) -> Result<CssWideKeyword, ()> {
+        match_ignore_ascii_case! { ident,
+            "initial" => Ok(CssWideKeyword::Initial),
+            "inherit" => Ok(CssWideKeyword::Inherit),
+            "unset" => Ok(CssWideKeyword::Unset),
+            "revert" => Ok(CssWideKeyword::Revert),
+            "revert-layer" => Ok(CssWideKeyword::RevertLayer),
+            _ => Err(())
+        }
+    }
+
+    /// Parse a CSS-wide keyword.
+    ///
+    /// Typical usage is `input.try(CssWideKeyword::parse)`
+    /// at the start of a `DeclarationParser::parse_value` implementation,
+    /// before property-specific parsing.
+    /// The caller needs to check if the parser is exhausted
+    /// (optionally after `!important`), since these keywords are only valid on their own.
+    pub fn parse<'i, 't>(input: &mut Parser<'i, 't>) -> Result<CssWideKeyword, ParseError<'i>> {
+        let start = input.position();
+        let ident = try!(input.expect_ident());
+        CssWideKeyword::from_ident(&ident).map_err(|()| {
+            unexpected_token(Token::Ident(ident), start)
+        })
+    }
+
+    /// The name of this keyword, in lower case.
+    pub fn name(self) -> &'static str {
+        match self {
+            CssWideKeyword::Initial => "initial",
+            CssWideKeyword::Inherit => "inherit",
+            CssWideKeyword::Unset => "unset",
+            CssWideKeyword::Revert => "revert",
+            CssWideKeyword::RevertLayer => "revert-layer",
+        }
+    }
+}
+
+
+impl ToCss for CssWideKeyword {
+    fn to_css<W>(&self, dest: &mut W) -> fmt::Result where W: fmt::Write {
+        dest.write_str(self.name())
+    }
+}
+
+
+/// The kind of an invalid item skipped by `DeclarationListParser`, `RuleListParser`
+/// or `RuleBodyParser`, as given to their `on_invalid_item` callbacks.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum InvalidItem<'a> {
+    /// A declaration, or something else that is not an at-rule in a declaration list.
+    Declaration,
+
+    /// An at-rule whose prelude or block was rejected by the `AtRuleParser`.
+    ///
+    /// This can be used to count uses of unsupported at-rules.
+    AtRule {
+        /// The name of the at-rule, without the `@` marker.
+        name: &'a str,
+    },
+
+    /// An at-rule whose prelude was accepted by the `AtRuleParser`,
+    /// but that has a `{}` block when `AtRuleType::WithoutBlock` was returned,
+    /// or none when `AtRuleType::WithBlock` was returned.
+    MalformedAtRule {
+        /// The name of the at-rule, without the `@` marker.
+        name: &'a str,
+    },
+
+    /// A qualified rule.
+    QualifiedRule,
+}
+
+
+impl<'a> InvalidItem<'a> {
+    /// A name for this kind of item, such as `"at-rule"`, for messages.
+    pub fn name(self) -> &'static str {
+        match self {
+            InvalidItem::Declaration => "declaration",
+            InvalidItem::AtRule { .. } | InvalidItem::MalformedAtRule { .. } => "at-rule",
+            InvalidItem::QualifiedRule => "qualified rule",
+        }
+    }
+}
+
+
+/// The error for an invalid item skipped by `DeclarationListParser`, `RuleListParser`
+/// or `RuleBodyParser`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ItemError<'i> {
+    /// Why the item is invalid.
+    ///
+    /// When a method of `DeclarationParser`, `AtRuleParser` or `QualifiedRuleParser`
+    /// returns an error, this is that error.
+    pub error: ParseError<'i>,
+
+    /// The source range of the item.
+    pub span: SourceRange,
+}
+
+
+impl<'i> ItemError<'i> {
+    /// Return an error that owns its token, and so does not borrow the input.
+    pub fn into_owned(self) -> ItemError<'static> {
+        ItemError {
+            error: self.error.into_owned(),
+            span: self.span,
+        }
+    }
+}
+
+
+impl<'i> fmt::Display for ItemError<'i> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} in item at bytes {}..{}", self.error,
+               byte_index(self.span.start), byte_index(self.span.end))
+    }
+}
+
+
+impl<'i> Error for ItemError<'i> {
+    fn description(&self) -> &str {
+        self.error.description()
+    }
+
+    fn cause(&self) -> Option<&dyn Error> {
+        Some(&self.error)
+    }
+}
+
+
+/// An `ItemError` for an item from `start_position` to the current position of `input`.
+fn item_error<'i>(error: ParseError<'i>, start_position: SourcePosition, input: &Parser)
+                  -> ItemError<'i> {
+    ItemError {
+        error: error,
+        span: start_position..input.position(),
+    }
+}
+
+
 /// The return value for `AtRuleParser::parse_prelude`.
 /// Indicates whether the at-rule is expected to have a `{ /* ... */ }` block
 /// or end with a `;` semicolon.
@@ -48,7 +215,12 @@ pub enum AtRuleType<P, R> {
 ///
 /// For example, there could be different implementations for property declarations in style rules
 /// and for descriptors in `@font-face` rules.
-pub trait DeclarationParser {
+///
+/// `parse_value` is also given a context of type `C`, which defaults to `()`.
+/// The context is given to `DeclarationListParser::new_with_context`
+/// or `parse_one_declaration_with_context`,
+/// and can be shared with other parsers instead of being stored in the parser itself.
+pub trait DeclarationParser<C = ()> {
     /// The finished representation of a declaration.
     type Declaration;
 
@@ -56,7 +228,7 @@ pub trait DeclarationParser {
     ///
     /// Return the finished representation for the declaration
     /// as returned by `DeclarationListParser::next`,
-    /// or `Err(())` to ignore the entire declaration as invalid.
+    /// or an error to ignore the entire declaration as invalid.
     ///
     /// Declaration name matching should be case-insensitive in the ASCII range.
     /// This can be done with `std::ascii::Ascii::eq_ignore_ascii_case`,
@@ -69,7 +241,8 @@ pub trait DeclarationParser {
     /// If `!important` can be used in a given context,
     /// `input.try(parse_important).is_ok()` should be used at the end
     /// of the implementation of this method and the result should be part of the return value.
-    fn parse_value(&mut self, name: &str, input: &mut Parser) -> Result<Self::Declaration, ()>;
+    fn parse_value<'i, 't>(&mut self, context: &mut C, name: &str, input: &mut Parser<'i, 't>)
+                           -> Result<Self::Declaration, ParseError<'i>>;
 }
 
 
@@ -82,7 +255,9 @@ pub trait DeclarationParser {
 /// Default implementations that reject all at-rules are provided,
 /// so that `impl AtRuleParser<(), ()> for ... {}` can be used
 /// for using `DeclarationListParser` to parse a declartions list with only qualified rules.
-pub trait AtRuleParser {
+///
+/// Each method is also given a context of type `C`, as in `DeclarationParser`.
+pub trait AtRuleParser<C = ()> {
     /// The intermediate representation of an at-rule prelude.
     type Prelude;
 
@@ -92,7 +267,7 @@ pub trait AtRuleParser {
     /// Parse the prelude of an at-rule with the given `name`.
     ///
     /// Return the representation of the prelude and the type of at-rule,
-    /// or `Err(())` to ignore the entire at-rule as invalid.
+    /// or an error to ignore the entire at-rule as invalid.
     ///
     /// See `AtRuleType`’s documentation for the return value.
     ///
@@ -106,33 +281,35 @@ pub trait AtRuleParser {
     /// The given `input` is a "delimited" parser
     /// that ends wherever the prelude should end.
     /// (Before the next semicolon, the next `{`, or the end of the current block.)
-    fn parse_prelude(&mut self, name: &str, input: &mut Parser)
-                     -> Result<AtRuleType<Self::Prelude, Self::AtRule>, ()> {
+    fn parse_prelude<'i, 't>(&mut self, context: &mut C, name: &str, input: &mut Parser<'i, 't>)
+                             -> Result<AtRuleType<Self::Prelude, Self::AtRule>, ParseError<'i>> {
+        let _ = context;
         let _ = name;
-        let _ = input;
-        Err(())
+        Err(input.new_error(ParseErrorKind::Invalid))
     }
 
     /// Parse the content of a `{ /* ... */ }` block for the body of the at-rule.
     ///
     /// Return the finished representation of the at-rule
     /// as returned by `RuleListParser::next` or `DeclarationListParser::next`,
-    /// or `Err(())` to ignore the entire at-rule as invalid.
+    /// or an error to ignore the entire at-rule as invalid.
     ///
     /// This is only called when `parse_prelude` returned `WithBlock` or `OptionalBlock`,
     /// and a block was indeed found following the prelude.
-    fn parse_block(&mut self, prelude: Self::Prelude, input: &mut Parser)
-                   -> Result<Self::AtRule, ()> {
+    fn parse_block<'i, 't>(&mut self, context: &mut C, prelude: Self::Prelude,
+                           input: &mut Parser<'i, 't>)
+                           -> Result<Self::AtRule, ParseError<'i>> {
+        let _ = context;
         let _ = prelude;
-        let _ = input;
-        Err(())
+        Err(input.new_error(ParseErrorKind::Invalid))
     }
 
     /// An `OptionalBlock` prelude was followed by `;`.
     ///
     /// Convert the prelude into the finished representation of the at-rule
     /// as returned by `RuleListParser::next` or `DeclarationListParser::next`.
-    fn rule_without_block(&mut self, prelude: Self::Prelude) -> Self::AtRule {
+    fn rule_without_block(&mut self, context: &mut C, prelude: Self::Prelude) -> Self::AtRule {
+        let _ = context;
         let _ = prelude;
         panic!("The `AtRuleParser::rule_without_block` method must be overriden \
                 if `AtRuleParser::parse_prelude` ever returns `AtRuleType::OptionalBlock`.")
@@ -150,7 +327,9 @@ pub trait AtRuleParser {
 /// so that `impl QualifiedRuleParser<(), ()> for ... {}` can be used
 /// for example for using `RuleListParser` to parse a rule list with only at-rules
 /// (such as inside `@font-feature-values`).
-pub trait QualifiedRuleParser {
+///
+/// Each method is also given a context of type `C`, as in `DeclarationParser`.
+pub trait QualifiedRuleParser<C = ()> {
     /// The intermediate representation of a qualified rule prelude.
     type Prelude;
 
@@ -160,38 +339,44 @@ pub trait QualifiedRuleParser {
     /// Parse the prelude of a qualified rule. For style rules, this is as Selector list.
     ///
     /// Return the representation of the prelude,
-    /// or `Err(())` to ignore the entire at-rule as invalid.
+    /// or an error to ignore the entire at-rule as invalid.
     ///
     /// The prelude is the part before the `{ /* ... */ }` block.
     ///
     /// The given `input` is a "delimited" parser
     /// that ends where the prelude should end (before the next `{`).
-    fn parse_prelude(&mut self, input: &mut Parser) -> Result<Self::Prelude, ()> {
-        let _ = input;
-        Err(())
+    fn parse_prelude<'i, 't>(&mut self, context: &mut C, input: &mut Parser<'i, 't>)
+                             -> Result<Self::Prelude, ParseError<'i>> {
+        let _ = context;
+        Err(input.new_error(ParseErrorKind::Invalid))
     }
 
     /// Parse the content of a `{ /* ... */ }` block for the body of the qualified rule.
     ///
     /// Return the finished representation of the qualified rule
     /// as returned by `RuleListParser::next`,
-    /// or `Err(())` to ignore the entire at-rule as invalid.
-    fn parse_block(&mut self, prelude: Self::Prelude, input: &mut Parser)
-                   -> Result<Self::QualifiedRule, ()> {
+    /// or an error to ignore the entire at-rule as invalid.
+    fn parse_block<'i, 't>(&mut self, context: &mut C, prelude: Self::Prelude,
+                           input: &mut Parser<'i, 't>)
+                           -> Result<Self::QualifiedRule, ParseError<'i>> {
+        let _ = context;
         let _ = prelude;
-        let _ = input;
-        Err(())
+        Err(input.new_error(ParseErrorKind::Invalid))
     }
 }
 
 
 /// Provides an iterator for declaration list parsing.
-pub struct DeclarationListParser<'i: 't, 't: 'a, 'a, P> {
+pub struct DeclarationListParser<'i: 't, 't: 'a, 'a, P, C: 'a = ()> {
     /// The input given to `DeclarationListParser::new`
     pub input: &'a mut Parser<'i, 't>,
 
     /// The parser given to `DeclarationListParser::new`
     pub parser: P,
+
+    context: MaybeOwned<'a, C>,
+
+    invalid_item_callback: Option<Box<dyn FnMut(InvalidItem, SourceRange) + 'a>>,
 }
 
 
@@ -215,54 +400,239 @@ where P: DeclarationParser<Declaration = I> + AtRuleParser<AtRule = I> {
         DeclarationListParser {
             input: input,
             parser: parser,
+            context: MaybeOwned::Owned(Box::new(())),
+            invalid_item_callback: None,
         }
     }
 }
 
+
+impl<'i, 't, 'a, I, P, C> DeclarationListParser<'i, 't, 'a, P, C>
+where P: DeclarationParser<C, Declaration = I> + AtRuleParser<C, AtRule = I> {
+    /// Same as `new`, but `context` is passed to each method of `parser`.
+    pub fn new_with_context(input: &'a mut Parser<'i, 't>, context: &'a mut C, parser: P) -> Self {
+        DeclarationListParser {
+            input: input,
+            parser: parser,
+            context: MaybeOwned::Borrowed(context),
+            invalid_item_callback: None,
+        }
+    }
+
+    /// Call `callback` with the kind and the source range of each invalid item
+    /// when it is skipped, before `next` returns it as `Err`.
+    ///
+    /// This does not change parsing: it can be used to collect diagnostics
+    /// while using only the valid items, for example with `filter_map`,
+    /// or to count uses of unsupported at-rules with `InvalidItem::AtRule`.
+    pub fn on_invalid_item<F>(mut self, callback: F) -> Self
+    where F: FnMut(InvalidItem, SourceRange) + 'a {
+        self.invalid_item_callback = Some(Box::new(callback));
+        self
+    }
+}
+
 /// `DeclarationListParser` is an iterator that yields `Ok(_)` for a valid declaration or at-rule
-/// or `Err(())` for an invalid one.
-impl<'i, 't, 'a, I, P> Iterator for DeclarationListParser<'i, 't, 'a, P>
-where P: DeclarationParser<Declaration = I> + AtRuleParser<AtRule = I> {
-    type Item = Result<I, Range<SourcePosition>>;
+/// or `Err(_)` for an invalid one.
+impl<'i, 't, 'a, I, P, C> Iterator for DeclarationListParser<'i, 't, 'a, P, C>
+where P: DeclarationParser<C, Declaration = I> + AtRuleParser<C, AtRule = I> {
+    type Item = Result<I, ItemError<'i>>;
 
-    fn next(&mut self) -> Option<Result<I, Range<SourcePosition>>> {
+    fn next(&mut self) -> Option<Result<I, ItemError<'i>>> {
         loop {
             let start_position = self.input.position();
             match self.input.next_including_whitespace_and_comments() {
                 Ok(Token::WhiteSpace(_)) | Ok(Token::Comment(_)) | Ok(Token::Semicolon) => {}
                 Ok(Token::Ident(name)) => {
-                    return Some({
+                    let result = {
                         let parser = &mut self.parser;
+                        let context = &mut *self.context;
                         self.input.parse_until_after(Delimiter::Semicolon, |input| {
                             try!(input.expect_colon());
-                            parser.parse_value(&*name, input)
+                            parser.parse_value(context, &*name, input)
                         })
-                    }.map_err(|()| start_position..self.input.position()))
+                    }.map_err(|error| {
+                        (InvalidItem::Declaration, item_error(error, start_position, self.input))
+                    });
+                    return Some(report_invalid(&mut self.invalid_item_callback, self.input,
+                                               result))
                 }
                 Ok(Token::AtKeyword(name)) => {
-                    return Some(parse_at_rule(start_position, name, self.input, &mut self.parser))
+                    let result = parse_at_rule(start_position, &name, self.input,
+                                               &mut *self.context, &mut self.parser);
+                    return Some(report_invalid(&mut self.invalid_item_callback, self.input,
+                                               result))
                 }
-                Ok(_) => {
-                    return Some(self.input.parse_until_after(Delimiter::Semicolon, |_| Err(()))
-                                .map_err(|()| start_position..self.input.position()))
+                Ok(token) => {
+                    let error = unexpected_token(token, start_position);
+                    let _ = self.input.parse_until_after(Delimiter::Semicolon,
+                                                         |_| Ok::<(), ParseError>(()));
+                    let result = Err((InvalidItem::Declaration,
+                                      item_error(error, start_position, self.input)));
+                    return Some(report_invalid(&mut self.invalid_item_callback, self.input,
+                                               result))
+                }
+                Err(_) => return None,
+            }
+        }
+    }
+}
+
+
+/// Provides an iterator for parsing the body of a style rule per CSS Nesting,
+/// where declarations, at-rules and nested qualified rules can be interleaved.
+pub struct RuleBodyParser<'i: 't, 't: 'a, 'a, P, C: 'a = ()> {
+    /// The input given to `RuleBodyParser::new`
+    pub input: &'a mut Parser<'i, 't>,
+
+    /// The parser given to `RuleBodyParser::new`
+    pub parser: P,
+
+    context: MaybeOwned<'a, C>,
+
+    invalid_item_callback: Option<Box<dyn FnMut(InvalidItem, SourceRange) + 'a>>,
+}
+
+
+impl<'i, 't, 'a, I, P> RuleBodyParser<'i, 't, 'a, P>
+where P: DeclarationParser<Declaration = I> + AtRuleParser<AtRule = I> +
+         QualifiedRuleParser<QualifiedRule = I> {
+    /// Create a new `RuleBodyParser` for the given `input` and `parser`.
+    ///
+    /// This is like `DeclarationListParser`,
+    /// but the given `parser` also needs to implement `QualifiedRuleParser` for nested rules.
+    /// The return type for finished declarations, at-rules and qualified rules
+    /// needs to be the same. It could be a custom enum.
+    ///
+    /// An item that starts with an identifier is first parsed as a declaration.
+    /// If that fails, it is parsed again as a qualified rule,
+    /// which ends at a semicolon if there is one before a `{}` block.
+    /// Per CSS Nesting, a declaration (other than of a custom property)
+    /// with a `{}` block at the top level of its value is not valid,
+    /// so that something like `a:hover { … }` is a rule.
+    pub fn new(input: &'a mut Parser<'i, 't>, parser: P) -> Self {
+        RuleBodyParser {
+            input: input,
+            parser: parser,
+            context: MaybeOwned::Owned(Box::new(())),
+            invalid_item_callback: None,
+        }
+    }
+}
+
+
+impl<'i, 't, 'a, I, P, C> RuleBodyParser<'i, 't, 'a, P, C>
+where P: DeclarationParser<C, Declaration = I> + AtRuleParser<C, AtRule = I> +
+         QualifiedRuleParser<C, QualifiedRule = I> {
+    /// Same as `new`, but `context` is passed to each method of `parser`.
+    pub fn new_with_context(input: &'a mut Parser<'i, 't>, context: &'a mut C, parser: P) -> Self {
+        RuleBodyParser {
+            input: input,
+            parser: parser,
+            context: MaybeOwned::Borrowed(context),
+            invalid_item_callback: None,
+        }
+    }
+
+    /// Same as `DeclarationListParser::on_invalid_item`.
+    pub fn on_invalid_item<F>(mut self, callback: F) -> Self
+    where F: FnMut(InvalidItem, SourceRange) + 'a {
+        self.invalid_item_callback = Some(Box::new(callback));
+        self
+    }
+}
+
+
+/// `RuleBodyParser` is an iterator that yields `Ok(_)` for a valid declaration or rule
+/// or `Err(_)` for an invalid one.
+impl<'i, 't, 'a, I, P, C> Iterator for RuleBodyParser<'i, 't, 'a, P, C>
+where P: DeclarationParser<C, Declaration = I> + AtRuleParser<C, AtRule = I> +
+         QualifiedRuleParser<C, QualifiedRule = I> {
+    type Item = Result<I, ItemError<'i>>;
+
+    fn next(&mut self) -> Option<Result<I, ItemError<'i>>> {
+        loop {
+            let start_position = self.input.position();
+            match self.input.next_including_whitespace_and_comments() {
+                Ok(Token::WhiteSpace(_)) | Ok(Token::Comment(_)) | Ok(Token::Semicolon) => {}
+                Ok(Token::AtKeyword(name)) => {
+                    let result = parse_at_rule(start_position, &name, self.input,
+                                               &mut *self.context, &mut self.parser);
+                    return Some(report_invalid(&mut self.invalid_item_callback, self.input,
+                                               result))
+                }
+                Ok(token) => {
+                    // When an item that starts with an identifier is neither a declaration
+                    // nor a rule, report why it is not a declaration.
+                    let mut declaration_error = None;
+                    if let Token::Ident(name) = token {
+                        let result = {
+                            let parser = &mut self.parser;
+                            let context = &mut *self.context;
+                            self.input.parse_until_after(Delimiter::Semicolon, |input| {
+                                try!(input.expect_colon());
+                                if !name.starts_with("--") && has_curly_bracket_block(input) {
+                                    return Err(input.new_error(ParseErrorKind::Invalid))
+                                }
+                                parser.parse_value(context, &*name, input)
+                            })
+                        };
+                        match result {
+                            Ok(declaration) => return Some(Ok(declaration)),
+                            Err(error) => declaration_error = Some(error),
+                        }
+                    }
+                    let kind = if declaration_error.is_some() {
+                        InvalidItem::Declaration
+                    } else {
+                        InvalidItem::QualifiedRule
+                    };
+                    self.input.reset(start_position);
+                    let result = parse_qualified_rule(self.input, &mut *self.context,
+                                                      &mut self.parser, true)
+                                 .map_err(|error| {
+                                     (kind, item_error(declaration_error.unwrap_or(error),
+                                                       start_position, self.input))
+                                 });
+                    return Some(report_invalid(&mut self.invalid_item_callback, self.input,
+                                               result))
                 }
-                Err(()) => return None,
+                Err(_) => return None,
             }
         }
     }
 }
 
 
+/// Whether there is a `{}` block at the top level of the rest of `input`.
+/// The position of `input` is unchanged.
+fn has_curly_bracket_block(input: &mut Parser) -> bool {
+    let start_position = input.position();
+    let mut found = false;
+    while let Ok(token) = input.next() {
+        if token == Token::CurlyBracketBlock {
+            found = true;
+            break
+        }
+    }
+    input.reset(start_position);
+    found
+}
+
+
 /// Provides an iterator for rule list parsing.
-pub struct RuleListParser<'i: 't, 't: 'a, 'a, P> {
+pub struct RuleListParser<'i: 't, 't: 'a, 'a, P, C: 'a = ()> {
     /// The input given to `RuleListParser::new`
     pub input: &'a mut Parser<'i, 't>,
 
     /// The parser given to `RuleListParser::new`
     pub parser: P,
 
+    context: MaybeOwned<'a, C>,
+
     is_stylesheet: bool,
     any_rule_so_far: bool,
+    invalid_item_callback: Option<Box<dyn FnMut(InvalidItem, SourceRange) + 'a>>,
 }
 
 
@@ -282,8 +652,10 @@ where P: QualifiedRuleParser<QualifiedRule = R> + AtRuleParser<AtRule = R> {
         RuleListParser {
             input: input,
             parser: parser,
+            context: MaybeOwned::Owned(Box::new(())),
             is_stylesheet: true,
             any_rule_so_far: false,
+            invalid_item_callback: None,
         }
     }
 
@@ -294,23 +666,56 @@ where P: QualifiedRuleParser<QualifiedRule = R> + AtRuleParser<AtRule = R> {
     /// should only be ignored at the stylesheet top-level.
     /// (This is to deal with legacy work arounds for `<style>` HTML element parsing.)
     pub fn new_for_nested_rule(input: &'a mut Parser<'i, 't>, parser: P) -> Self {
+        RuleListParser {
+            is_stylesheet: false,
+            ..RuleListParser::new_for_stylesheet(input, parser)
+        }
+    }
+}
+
+
+impl<'i: 't, 't: 'a, 'a, R, P, C> RuleListParser<'i, 't, 'a, P, C>
+where P: QualifiedRuleParser<C, QualifiedRule = R> +
+         AtRuleParser<C, AtRule = R> {
+    /// Same as `new_for_stylesheet`, but `context` is passed to each method of `parser`.
+    pub fn new_for_stylesheet_with_context(input: &'a mut Parser<'i, 't>, context: &'a mut C,
+                                           parser: P) -> Self {
         RuleListParser {
             input: input,
             parser: parser,
-            is_stylesheet: false,
+            context: MaybeOwned::Borrowed(context),
+            is_stylesheet: true,
             any_rule_so_far: false,
+            invalid_item_callback: None,
+        }
+    }
+
+    /// Same as `new_for_nested_rule`, but `context` is passed to each method of `parser`.
+    pub fn new_for_nested_rule_with_context(input: &'a mut Parser<'i, 't>, context: &'a mut C,
+                                            parser: P) -> Self {
+        RuleListParser {
+            is_stylesheet: false,
+            ..RuleListParser::new_for_stylesheet_with_context(input, context, parser)
         }
     }
+
+    /// Same as `DeclarationListParser::on_invalid_item`.
+    pub fn on_invalid_item<F>(mut self, callback: F) -> Self
+    where F: FnMut(InvalidItem, SourceRange) + 'a {
+        self.invalid_item_callback = Some(Box::new(callback));
+        self
+    }
 }
 
 
 
-/// `RuleListParser` is an iterator that yields `Ok(_)` for a rule or `Err(())` for an invalid one.
-impl<'i, 't, 'a, R, P> Iterator for RuleListParser<'i, 't, 'a, P>
-where P: QualifiedRuleParser<QualifiedRule = R> + AtRuleParser<AtRule = R> {
-    type Item = Result<R, Range<SourcePosition>>;
+/// `RuleListParser` is an iterator that yields `Ok(_)` for a rule or `Err(_)` for an invalid one.
+impl<'i, 't, 'a, R, P, C> Iterator for RuleListParser<'i, 't, 'a, P, C>
+where P: QualifiedRuleParser<C, QualifiedRule = R> +
+         AtRuleParser<C, AtRule = R> {
+    type Item = Result<R, ItemError<'i>>;
 
-    fn next(&mut self) -> Option<Result<R, Range<SourcePosition>>> {
+    fn next(&mut self) -> Option<Result<R, ItemError<'i>>> {
         loop {
             let start_position = self.input.position();
             match self.input.next_including_whitespace_and_comments() {
@@ -321,52 +726,142 @@ where P: QualifiedRuleParser<QualifiedRule = R> + AtRuleParser<AtRule = R> {
                     self.any_rule_so_far = true;
                     if first_stylesheet_rule && name.eq_ignore_ascii_case("charset") {
                         let delimiters = Delimiter::Semicolon | Delimiter::CurlyBracketBlock;
-                        let _ = self.input.parse_until_after(delimiters, |_input| Ok(()));
+                        let _ = self.input.parse_until_after(delimiters,
+                                                             |_| Ok::<(), ParseError>(()));
                     } else {
-                        return Some(parse_at_rule(start_position, name, self.input, &mut self.parser))
+                        let result = parse_at_rule(start_position, &name, self.input,
+                                                   &mut *self.context, &mut self.parser);
+                        return Some(report_invalid(&mut self.invalid_item_callback, self.input,
+                                                   result))
                     }
                 }
                 Ok(_) => {
                     self.any_rule_so_far = true;
                     self.input.reset(start_position);
-                    return Some(parse_qualified_rule(self.input, &mut self.parser)
-                                .map_err(|()| start_position..self.input.position()))
+                    let result = parse_qualified_rule(self.input, &mut *self.context,
+                                                      &mut self.parser, false)
+                                 .map_err(|error| {
+                                     (InvalidItem::QualifiedRule,
+                                      item_error(error, start_position, self.input))
+                                 });
+                    return Some(report_invalid(&mut self.invalid_item_callback, self.input,
+                                               result))
                 }
-                Err(()) => return None,
+                Err(_) => return None,
             }
         }
     }
 }
 
 
+/// Call the `on_invalid_item` callback if `result` is an invalid item of the given kind,
+/// then `log_if_invalid`.
+fn report_invalid<'a, 'i, T>(callback: &mut Option<Box<dyn FnMut(InvalidItem, SourceRange) + 'a>>,
+                             input: &Parser, result: Result<T, (InvalidItem, ItemError<'i>)>)
+                             -> Result<T, ItemError<'i>> {
+    match result {
+        Ok(item) => Ok(item),
+        Err((kind, error)) => {
+            if let Some(ref mut callback) = *callback {
+                callback(kind, error.span.clone())
+            }
+            log_if_invalid(kind, input, Err(error))
+        }
+    }
+}
+
+
+/// With the `log` feature, log a warning (and the skipped source at the debug level)
+/// if `result` is an invalid item. Return `result` unchanged.
+#[cfg(feature = "log")]
+fn log_if_invalid<'i, T>(kind: InvalidItem, input: &Parser, result: Result<T, ItemError<'i>>)
+                         -> Result<T, ItemError<'i>> {
+    if let Err(ref error) = result {
+        let location = input.source_location(error.span.start);
+        warn!("Skipped invalid {} at {}:{}: {}", kind.name(), location.line, location.column,
+              error.error);
+        debug!("Skipped CSS: {}", input.slice(error.span.clone()));
+    }
+    result
+}
+
+
+#[cfg(not(feature = "log"))]
+#[inline]
+fn log_if_invalid<'i, T>(_kind: InvalidItem, _input: &Parser, result: Result<T, ItemError<'i>>)
+                         -> Result<T, ItemError<'i>> {
+    result
+}
+
+
 /// Parse a single declaration, such as an `( /* ... */ )` parenthesis in an `@supports` prelude.
-pub fn parse_one_declaration<P>(input: &mut Parser, parser: &mut P)
-                                -> Result<<P as DeclarationParser>::Declaration,
-                                          Range<SourcePosition>>
-                                where P: DeclarationParser {
-    let start_position = input.position();
+pub fn parse_one_declaration<'i, 't, P>(input: &mut Parser<'i, 't>, parser: &mut P)
+                                        -> Result<<P as DeclarationParser>::Declaration,
+                                                  ParseError<'i>>
+                                        where P: DeclarationParser {
+    parse_one_declaration_with_context(input, &mut (), parser)
+}
+
+
+/// Same as `parse_one_declaration`, but create the parser for the given string.
+///
+/// This is a shortcut for `parse_one_declaration(&mut Parser::new(css), parser)`,
+/// for example for a declaration from an attribute or in tests.
+pub fn parse_one_declaration_str<'i, P>(css: &'i str, parser: &mut P)
+                                        -> Result<<P as DeclarationParser>::Declaration,
+                                                  ParseError<'i>>
+                                        where P: DeclarationParser {
+    parse_one_declaration(&mut Parser::new(css), parser)
+}
+
+
+/// Same as `parse_one_declaration`, but `context` is passed to `parser`.
+pub fn parse_one_declaration_with_context<'i, 't, P, C>(input: &mut Parser<'i, 't>,
+                                                        context: &mut C, parser: &mut P)
+                                                        -> Result<P::Declaration, ParseError<'i>>
+                                                        where P: DeclarationParser<C> {
     input.parse_entirely(|input| {
         let name = try!(input.expect_ident());
         try!(input.expect_colon());
-        parser.parse_value(&*name, input)
-    }).map_err(|()| start_position..input.position())
+        parser.parse_value(context, &*name, input)
+    })
 }
 
 
 /// Parse a single rule, such as for CSSOM’s `CSSStyleSheet.insertRule`.
-pub fn parse_one_rule<R, P>(input: &mut Parser, parser: &mut P) -> Result<R, ()>
+pub fn parse_one_rule<'i, 't, R, P>(input: &mut Parser<'i, 't>, parser: &mut P)
+                                    -> Result<R, ParseError<'i>>
+where P: QualifiedRuleParser<QualifiedRule = R> + AtRuleParser<AtRule = R> {
+    parse_one_rule_with_context(input, &mut (), parser)
+}
+
+
+/// Same as `parse_one_rule`, but create the parser for the given string.
+///
+/// This is a shortcut for `parse_one_rule(&mut Parser::new(css), parser)`.
+pub fn parse_one_rule_str<'i, R, P>(css: &'i str, parser: &mut P) -> Result<R, ParseError<'i>>
 where P: QualifiedRuleParser<QualifiedRule = R> + AtRuleParser<AtRule = R> {
+    parse_one_rule(&mut Parser::new(css), parser)
+}
+
+
+/// Same as `parse_one_rule`, but `context` is passed to each method of `parser`.
+pub fn parse_one_rule_with_context<'i, 't, R, P, C>(input: &mut Parser<'i, 't>, context: &mut C,
+                                                    parser: &mut P) -> Result<R, ParseError<'i>>
+where P: QualifiedRuleParser<C, QualifiedRule = R> +
+         AtRuleParser<C, AtRule = R> {
     input.parse_entirely(|input| {
         loop {
             let start_position = input.position();
             match try!(input.next_including_whitespace_and_comments()) {
                 Token::WhiteSpace(_) | Token::Comment(_) => {}
                 Token::AtKeyword(name) => {
-                    return parse_at_rule(start_position, name, input, parser).map_err(|_| ())
+                    return parse_at_rule(start_position, &name, input, context, parser)
+                           .map_err(|(_, error)| error.error)
                 }
                 _ => {
                     input.reset(start_position);
-                    return parse_qualified_rule(input, parser).map_err(|_| ())
+                    return parse_qualified_rule(input, context, parser, false)
                 }
             }
         }
@@ -374,66 +869,106 @@ where P: QualifiedRuleParser<QualifiedRule = R> + AtRuleParser<AtRule = R> {
 }
 
 
-fn parse_at_rule<P>(start_position: SourcePosition, name: Cow<str>,
-                    input: &mut Parser, parser: &mut P)
-                    -> Result<<P as AtRuleParser>::AtRule, Range<SourcePosition>>
-                    where P: AtRuleParser {
+/// Parse an at-rule. On error, also return whether the at-rule was rejected by `parser`
+/// or is malformed.
+fn parse_at_rule<'i, 't, 'n, P, C>(start_position: SourcePosition, name: &'n str,
+                                   input: &mut Parser<'i, 't>, context: &mut C, parser: &mut P)
+                                   -> Result<<P as AtRuleParser<C>>::AtRule,
+                                             (InvalidItem<'n>, ItemError<'i>)>
+                                   where P: AtRuleParser<C> {
+    let rejected = InvalidItem::AtRule { name: name };
+    let malformed = InvalidItem::MalformedAtRule { name: name };
     let delimiters = Delimiter::Semicolon | Delimiter::CurlyBracketBlock;
     let result = input.parse_until_before(delimiters, |input| {
-        parser.parse_prelude(&*name, input)
+        parser.parse_prelude(context, name, input)
     });
-    match result {
+    // The prelude ends just before the `;` or `{}` block, if any.
+    let end_position = input.position();
+    let result = match result {
         Ok(AtRuleType::WithoutBlock(rule)) => {
             match input.next() {
-                Ok(Token::Semicolon) | Err(()) => Ok(rule),
-                Ok(Token::CurlyBracketBlock) => Err(start_position..input.position()),
+                Ok(Token::Semicolon) | Err(_) => Ok(rule),
+                Ok(Token::CurlyBracketBlock) => {
+                    // Skip the block so that it is part of the span of the invalid item.
+                    let _ = input.parse_nested_block(|input| {
+                        while input.next().is_ok() {}
+                        Ok::<(), ParseError>(())
+                    });
+                    Err((malformed, unexpected_token(Token::CurlyBracketBlock, end_position)))
+                }
                 Ok(_) => unreachable!()
             }
         }
         Ok(AtRuleType::WithBlock(prelude)) => {
             match input.next() {
                 Ok(Token::CurlyBracketBlock) => {
-                    input.parse_nested_block(move |input| parser.parse_block(prelude, input))
-                    .map_err(|()| start_position..input.position())
+                    input.parse_nested_block(move |input| {
+                        parser.parse_block(context, prelude, input)
+                    }).map_err(|error| (rejected, error))
+                }
+                Ok(Token::Semicolon) => {
+                    Err((malformed, unexpected_token(Token::Semicolon, end_position)))
                 }
-                Ok(Token::Semicolon) | Err(()) => Err(start_position..input.position()),
+                Err(error) => Err((malformed, error)),
                 Ok(_) => unreachable!()
             }
         }
         Ok(AtRuleType::OptionalBlock(prelude)) => {
             match input.next() {
-                Ok(Token::Semicolon) | Err(()) => Ok(parser.rule_without_block(prelude)),
+                Ok(Token::Semicolon) | Err(_) => Ok(parser.rule_without_block(context, prelude)),
                 Ok(Token::CurlyBracketBlock) => {
-                    input.parse_nested_block(move |input| parser.parse_block(prelude, input))
-                    .map_err(|()| start_position..input.position())
+                    input.parse_nested_block(move |input| {
+                        parser.parse_block(context, prelude, input)
+                    }).map_err(|error| (rejected, error))
                 }
                 _ => unreachable!()
             }
         }
-        Err(()) => {
-            let end_position = input.position();
+        Err(error) => {
             match input.next() {
-                Ok(Token::CurlyBracketBlock) | Ok(Token::Semicolon) | Err(()) => {}
+                Ok(Token::CurlyBracketBlock) | Ok(Token::Semicolon) | Err(_) => {}
                 _ => unreachable!()
             }
-            Err(start_position..end_position)
+            return Err((rejected, ItemError {
+                error: error,
+                span: start_position..end_position,
+            }))
         }
-    }
+    };
+    result.map_err(|(kind, error)| (kind, item_error(error, start_position, input)))
 }
 
 
-fn parse_qualified_rule<P>(input: &mut Parser, parser: &mut P)
-                           -> Result<<P as QualifiedRuleParser>::QualifiedRule, ()>
-                           where P: QualifiedRuleParser {
-    let prelude = input.parse_until_before(Delimiter::CurlyBracketBlock, |input| {
-        parser.parse_prelude(input)
+fn parse_qualified_rule<'i, 't, P, C>(input: &mut Parser<'i, 't>, context: &mut C, parser: &mut P,
+                                      nested: bool)
+                                      -> Result<<P as QualifiedRuleParser<C>>
+                                                ::QualifiedRule, ParseError<'i>>
+                                      where P: QualifiedRuleParser<C> {
+    // In nested contexts, a semicolon ends an (invalid) qualified rule.
+    let delimiters = if nested {
+        Delimiter::CurlyBracketBlock | Delimiter::Semicolon
+    } else {
+        Delimiter::CurlyBracketBlock
+    };
+    let prelude = input.parse_until_before(delimiters, |input| {
+        parser.parse_prelude(context, input)
     });
-    match try!(input.next()) {
-        Token::CurlyBracketBlock => {
+    let end_position = input.position();
+    // Report a rejected prelude rather than the lack of a block.
+    match input.next() {
+        Ok(Token::CurlyBracketBlock) => {
             // Do this here so that we consume the `{` even if the prelude is `Err`.
             let prelude = try!(prelude);
-            input.parse_nested_block(move |input| parser.parse_block(prelude, input))
+            input.parse_nested_block(move |input| parser.parse_block(context, prelude, input))
+        }
+        Ok(Token::Semicolon) => {
+            try!(prelude);
+            Err(unexpected_token(Token::Semicolon, end_position))
+        }
+        Err(error) => {
+            try!(prelude);
+            Err(error)
         }
-        _ => unreachable!()
+        Ok(_) => unreachable!()
     }
 }
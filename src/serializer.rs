@@ -3,10 +3,12 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use std::ascii::AsciiExt;
+use std::borrow::Cow;
 use std::cmp;
 use std::fmt::{self, Write};
 
-use super::{Token, NumericValue, PercentageValue};
+use tokenizer::Tokenizer;
+use super::{Token, NumericValue, PercentageValue, would_start_identifier};
 
 
 /// Trait for things the can serialize themselves in CSS syntax.
@@ -40,11 +42,101 @@ pub trait ToCss {
     fn fmt_to_css<W>(&self, dest: &mut W) -> fmt::Result where W: fmt::Write {
         self.to_css(dest).map_err(|_| fmt::Error)
     }
+
+    /// Serialize `self` in CSS syntax with the given options, writing to `dest`.
+    ///
+    /// The default implementation ignores `options` and calls `to_css`.
+    /// Types made of tokens or of other `ToCss` values override it to pass `options` on.
+    #[inline]
+    fn to_css_with_options<W>(&self, options: &SerializationOptions, dest: &mut W)
+                              -> fmt::Result where W: fmt::Write {
+        let _ = options;
+        self.to_css(dest)
+    }
+
+    /// Serialize `self` in CSS syntax with the given options and return a string.
+    ///
+    /// (This is a convenience wrapper for `to_css_with_options`
+    /// and probably should not be overridden.)
+    #[inline]
+    fn to_css_string_with_options(&self, options: &SerializationOptions) -> String {
+        let mut s = String::new();
+        self.to_css_with_options(options, &mut s).unwrap();
+        s
+    }
+}
+
+
+/// Options for `ToCss::to_css_with_options`,
+/// for example to pretty-print or to minify a stylesheet parsed with `parse_stylesheet`.
+///
+/// The default options give the same result as `ToCss::to_css`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct SerializationOptions {
+    /// The number of spaces for each level of nesting of rules and declarations in blocks.
+    /// Defaults to 2.
+    pub indent_width: usize,
+
+    /// The line break written between rules and declarations,
+    /// such as `"\n"` (the default) or `"\r\n"`.
+    pub newline: &'static str,
+
+    /// Whether to write rules and declarations without line breaks and indentation,
+    /// to remove comments, and to remove whitespace between tokens
+    /// or collapse it to a single space where it does not change how the result is parsed.
+    /// Defaults to `false`.
+    pub minify_whitespace: bool,
+
+    /// Whether to write hash tokens that are hex colors in lower case and in their short form
+    /// when they have one, such as `#abc` for `#AABBCC`. Defaults to `false`.
+    ///
+    /// This would also change ID selectors that look like hex colors,
+    /// so rules only apply it in declaration values.
+    pub shortest_hex_colors: bool,
+
+    /// Whether to write numbers in their shortest form: without a `+` sign,
+    /// without a `0` before the decimal point, and with an exponent if that is shorter.
+    /// Numbers with a decimal point are not turned into integers. Defaults to `false`.
+    ///
+    /// Rules only apply this in declaration values.
+    pub shortest_numbers: bool,
+}
+
+
+impl Default for SerializationOptions {
+    fn default() -> SerializationOptions {
+        SerializationOptions {
+            indent_width: 2,
+            newline: "\n",
+            minify_whitespace: false,
+            shortest_hex_colors: false,
+            shortest_numbers: false,
+        }
+    }
+}
+
+
+impl SerializationOptions {
+    /// Options for the smallest output:
+    /// `minify_whitespace`, `shortest_hex_colors`, and `shortest_numbers`.
+    pub fn minified() -> SerializationOptions {
+        SerializationOptions {
+            minify_whitespace: true,
+            shortest_hex_colors: true,
+            shortest_numbers: true,
+            ..SerializationOptions::default()
+        }
+    }
 }
 
 
 #[inline]
-fn write_numeric<W>(value: NumericValue, dest: &mut W) -> fmt::Result where W: fmt::Write {
+fn write_numeric<W>(value: NumericValue, options: &SerializationOptions, dest: &mut W)
+                    -> fmt::Result where W: fmt::Write {
+    if options.shortest_numbers {
+        return write_shortest_numeric(value, dest)
+    }
+
     // `value.value >= 0` is true for negative 0.
     if value.has_sign && value.value.is_sign_positive() {
         try!(dest.write_str("+"));
@@ -64,14 +156,69 @@ fn write_numeric<W>(value: NumericValue, dest: &mut W) -> fmt::Result where W: f
 }
 
 
+fn write_shortest_numeric<W>(value: NumericValue, dest: &mut W) -> fmt::Result
+where W: fmt::Write {
+    if let Some(int_value) = value.int_value {
+        // Not `value.value`, which is not exact for large integers.
+        return write!(dest, "{}", int_value)
+    }
+    let mut decimal = value.value.to_string();
+    if value.value.fract() == 0. {
+        decimal.push_str(".0")
+    }
+    if decimal.starts_with("0.") {
+        decimal.remove(0);
+    } else if decimal.starts_with("-0.") {
+        decimal.remove(1);
+    }
+    // An exponent keeps this a `<number>` rather than an `<integer>`.
+    let exponent = format!("{:e}", value.value);
+    if exponent.len() < decimal.len() {
+        decimal = exponent
+    }
+    dest.write_str(&decimal)
+}
+
+
+/// Whether this is the value of a hash token for a color such as `#fff` or `#ffffff80`.
+fn is_hex_color(value: &str) -> bool {
+    matches!(value.len(), 3 | 4 | 6 | 8) && value.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+
+/// Write `#abc` for `#AABBCC` and `#abcd` for `#AABBCCDD`, or the color in lower case
+/// if it has no short form.
+fn write_shortest_hex_color<W>(value: &str, dest: &mut W) -> fmt::Result where W: fmt::Write {
+    let bytes = value.as_bytes();
+    let has_short_form = matches!(bytes.len(), 6 | 8) &&
+        bytes.chunks(2).all(|pair| pair[0].eq_ignore_ascii_case(&pair[1]));
+    try!(dest.write_str("#"));
+    for (i, &byte) in bytes.iter().enumerate() {
+        if !has_short_form || i % 2 == 0 {
+            try!(dest.write_char(byte.to_ascii_lowercase() as char))
+        }
+    }
+    Ok(())
+}
+
+
 impl<'a> ToCss for Token<'a> {
     fn to_css<W>(&self, dest: &mut W) -> fmt::Result where W: fmt::Write {
+        self.to_css_with_options(&SerializationOptions::default(), dest)
+    }
+
+    fn to_css_with_options<W>(&self, options: &SerializationOptions, dest: &mut W)
+                              -> fmt::Result where W: fmt::Write {
         match *self {
             Token::Ident(ref value) => try!(serialize_identifier(&**value, dest)),
             Token::AtKeyword(ref value) => {
                 try!(dest.write_str("@"));
                 try!(serialize_identifier(&**value, dest));
             },
+            Token::Hash(ref value) | Token::IDHash(ref value)
+            if options.shortest_hex_colors && is_hex_color(value) => {
+                try!(write_shortest_hex_color(value, dest))
+            }
             Token::Hash(ref value) => {
                 try!(dest.write_str("#"));
                 try!(serialize_name(value, dest));
@@ -88,18 +235,18 @@ impl<'a> ToCss for Token<'a> {
             },
             Token::Delim(value) => try!(write!(dest, "{}", value)),
 
-            Token::Number(value) => try!(write_numeric(value, dest)),
+            Token::Number(value) => try!(write_numeric(value, options, dest)),
             Token::Percentage(PercentageValue { unit_value, int_value, has_sign }) => {
                 let value = NumericValue {
                     value: unit_value * 100.,
                     int_value: int_value,
                     has_sign: has_sign,
                 };
-                try!(write_numeric(value, dest));
+                try!(write_numeric(value, options, dest));
                 try!(dest.write_str("%"));
             },
             Token::Dimension(value, ref unit) => {
-                try!(write_numeric(value, dest));
+                try!(write_numeric(value, options, dest));
                 // Disambiguate with scientific notation.
                 let unit = &**unit;
                 if unit == "e" || unit == "E" || unit.starts_with("e-") || unit.starts_with("E-") {
@@ -132,8 +279,8 @@ impl<'a> ToCss for Token<'a> {
                 }
             }
 
-            Token::WhiteSpace(content) => try!(dest.write_str(content)),
-            Token::Comment(content) => try!(write!(dest, "/*{}*/", content)),
+            Token::WhiteSpace(ref content) => try!(dest.write_str(content)),
+            Token::Comment(ref content) => try!(write!(dest, "/*{}*/", content)),
             Token::Colon => try!(dest.write_str(":")),
             Token::Semicolon => try!(dest.write_str(";")),
             Token::Comma => try!(dest.write_str(",")),
@@ -190,6 +337,30 @@ pub fn serialize_identifier<W>(mut value: &str, dest: &mut W) -> fmt::Result whe
 }
 
 
+/// Return whether `value` can be written as a CSS identifier without any escaping.
+///
+/// This is the case if tokenizing `value` would give a single `Ident` token with that same value.
+pub fn is_css_identifier(value: &str) -> bool {
+    would_start_identifier(value) && value.bytes().all(|b| match b {
+        b'0'...b'9' | b'A'...b'Z' | b'a'...b'z' | b'_' | b'-' => true,
+        _ => !b.is_ascii(),
+    })
+}
+
+
+/// Return `value` as a CSS identifier, borrowed if it does not need any escaping.
+///
+/// Like `serialize_identifier`, this returns an empty string for an empty `value`.
+pub fn escape_identifier(value: &str) -> Cow<str> {
+    if is_css_identifier(value) {
+        return Cow::Borrowed(value)
+    }
+    let mut escaped = String::with_capacity(value.len() + 2);
+    serialize_identifier(value, &mut escaped).unwrap();
+    Cow::Owned(escaped)
+}
+
+
 fn serialize_name<W>(value: &str, dest: &mut W) -> fmt::Result where W:fmt::Write {
     let mut chunk_start = 0;
     for (i, b) in value.bytes().enumerate() {
@@ -435,3 +606,65 @@ impl<'a> Token<'a> {
         })
     }
 }
+
+
+/// Check that serializing the tokens of `input` gives CSS that tokenizes to the same tokens.
+///
+/// `/**/` comments are inserted between tokens where `needs_separator_when_before` says so.
+/// Comments are ignored when comparing the tokens,
+/// since the serialization may add some.
+/// Nothing is checked if the input contains `BadString` or `BadUrl` tokens:
+/// these parse errors do not have a serialization that round-trips.
+///
+/// Panics on failure with a message showing the serialized CSS
+/// and the first differing token with the tokens before it,
+/// so that this can be used in test suites and fuzz targets.
+pub fn assert_roundtrips(input: &str) {
+    fn significant_tokens(css: &str) -> Vec<Token> {
+        let mut tokenizer = Tokenizer::new(css);
+        let mut tokens = Vec::new();
+        while let Ok(token) = tokenizer.next() {
+            if !matches!(token, Token::Comment(_)) {
+                tokens.push(token)
+            }
+        }
+        tokens
+    }
+
+    let mut serialized = String::new();
+    let mut previous = TokenSerializationType::nothing();
+    let mut tokenizer = Tokenizer::new(input);
+    while let Ok(token) = tokenizer.next() {
+        let token_type = token.serialization_type();
+        if previous.needs_separator_when_before(token_type) {
+            serialized.push_str("/**/")
+        }
+        previous = token_type;
+        token.to_css(&mut serialized).unwrap();
+    }
+
+    let original = significant_tokens(input);
+    if original.iter().any(|token| matches!(*token, Token::BadString | Token::BadUrl)) {
+        return
+    }
+    let reparsed = significant_tokens(&serialized);
+    let index = match original.iter().zip(&reparsed).position(|(a, b)| a != b) {
+        Some(index) => index,
+        None if original.len() == reparsed.len() => return,
+        None => cmp::min(original.len(), reparsed.len()),
+    };
+    let mut message = format!("tokens of {:?} do not round-trip through {:?}\n",
+                              input, serialized);
+    for token in &original[index.saturating_sub(3)..index] {
+        write!(message, "  {:?}\n", token).unwrap();
+    }
+    match original.get(index) {
+        Some(token) => write!(message, "- {:?}\n", token).unwrap(),
+        None => message.push_str("- <end of input>\n"),
+    }
+    match reparsed.get(index) {
+        Some(token) => write!(message, "+ {:?}\n", token).unwrap(),
+        None => message.push_str("+ <end of input>\n"),
+    }
+    panic!("{}", message)
+}
@@ -0,0 +1,107 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::ascii::AsciiExt;
+use std::collections::{BTreeMap, BTreeSet};
+
+use ast::{Rule, QualifiedRule, AtRule, Declaration};
+use visit::{Visit, Walk};
+
+
+/// Metrics of a stylesheet, as returned by `stylesheet_stats`.
+///
+/// Rules and declarations are counted at any nesting depth.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct StylesheetStats {
+    /// The number of qualified rules, such as style rules.
+    pub qualified_rules: usize,
+
+    /// The number of at-rules for each name (without the `@` marker), in ASCII lower case.
+    pub at_rules: BTreeMap<String, usize>,
+
+    /// The number of declarations.
+    pub declarations: usize,
+
+    /// The distinct names of declarations.
+    /// They are in ASCII lower case, except for custom properties which are case-sensitive.
+    pub property_names: BTreeSet<String>,
+
+    /// How deeply rules are nested: 0 for an empty stylesheet,
+    /// 1 if there are only top-level rules, 2 for rules in `@media`, etc.
+    pub max_depth: usize,
+
+    /// The total length in bytes of the preludes of qualified rules
+    /// (that is, of selectors for style rules), in the input.
+    pub prelude_bytes: usize,
+}
+
+
+impl StylesheetStats {
+    /// The number of rules, both qualified rules and at-rules.
+    pub fn rules(&self) -> usize {
+        self.qualified_rules + self.at_rules.values().fold(0, |sum, &count| sum + count)
+    }
+}
+
+
+/// Compute metrics of the given rules, such as from `parse_stylesheet`,
+/// in a single walk of the tree.
+pub fn stylesheet_stats(rules: &[Rule]) -> StylesheetStats {
+    let mut counter = StatsCounter {
+        stats: StylesheetStats::default(),
+        depth: 0,
+    };
+    rules.walk(&mut counter);
+    counter.stats
+}
+
+
+struct StatsCounter {
+    stats: StylesheetStats,
+    depth: usize,
+}
+
+
+impl StatsCounter {
+    fn enter_rule(&mut self) {
+        self.depth += 1;
+        if self.depth > self.stats.max_depth {
+            self.stats.max_depth = self.depth
+        }
+    }
+}
+
+
+impl<'i> Visit<'i> for StatsCounter {
+    fn enter_qualified_rule(&mut self, rule: &QualifiedRule<'i>) {
+        self.enter_rule();
+        self.stats.qualified_rules += 1;
+        if let (Some(first), Some(last)) = (rule.prelude.first(), rule.prelude.last()) {
+            self.stats.prelude_bytes += last.span.end.byte_index() - first.span.start.byte_index()
+        }
+    }
+
+    fn exit_qualified_rule(&mut self, _rule: &QualifiedRule<'i>) {
+        self.depth -= 1
+    }
+
+    fn enter_at_rule(&mut self, rule: &AtRule<'i>) {
+        self.enter_rule();
+        *self.stats.at_rules.entry(rule.name.to_ascii_lowercase()).or_insert(0) += 1
+    }
+
+    fn exit_at_rule(&mut self, _rule: &AtRule<'i>) {
+        self.depth -= 1
+    }
+
+    fn enter_declaration(&mut self, declaration: &Declaration<'i>) {
+        self.stats.declarations += 1;
+        let name = if declaration.name.starts_with("--") {
+            declaration.name.clone().into_owned()
+        } else {
+            declaration.name.to_ascii_lowercase()
+        };
+        self.stats.property_names.insert(name);
+    }
+}
@@ -0,0 +1,191 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+// https://drafts.csswg.org/css-conditional/#at-supports
+
+use std::ascii::AsciiExt;
+use std::borrow::Cow;
+use std::fmt;
+
+use super::{Token, Parser, ParseError, ParseErrorKind, DeclarationParser, ToCss};
+use parser::unexpected_token;
+
+
+/// A `<supports-condition>`, such as in the prelude of an `@supports` rule.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SupportsCondition<'i> {
+    /// `not <supports-in-parens>`
+    Not(Box<SupportsCondition<'i>>),
+
+    /// `<supports-in-parens> [ and <supports-in-parens> ]+`
+    And(Vec<SupportsCondition<'i>>),
+
+    /// `<supports-in-parens> [ or <supports-in-parens> ]+`
+    Or(Vec<SupportsCondition<'i>>),
+
+    /// A `( <declaration> )` feature.
+    Declaration {
+        /// The source of the declaration, without the parentheses
+        /// and without leading and trailing whitespace.
+        text: Cow<'i, str>,
+
+        /// Whether the `DeclarationParser` given to `SupportsCondition::parse`
+        /// accepted the declaration.
+        supported: bool,
+    },
+
+    /// A `<general-enclosed>`: a function or parenthesized block
+    /// that is not otherwise valid here, with the given source.
+    /// It is never supported.
+    GeneralEnclosed(Cow<'i, str>),
+}
+
+
+impl<'i> SupportsCondition<'i> {
+    /// Parse a `<supports-condition>`.
+    ///
+    /// `parser` is called for each `(property: value)` declaration,
+    /// with a delimited parser for the value.
+    /// A declaration that it rejects is still valid syntax, but is not supported.
+    ///
+    /// This does not check that the input is exhausted afterwards:
+    /// use `Parser::parse_entirely` for an entire `@supports` prelude.
+    pub fn parse<'t, P>(input: &mut Parser<'i, 't>, parser: &mut P)
+                        -> Result<SupportsCondition<'i>, ParseError<'i>>
+                        where P: DeclarationParser {
+        if input.try(|input| input.expect_ident_matching("not")).is_ok() {
+            let condition = try!(parse_in_parens(input, parser));
+            return Ok(SupportsCondition::Not(Box::new(condition)))
+        }
+
+        let first = try!(parse_in_parens(input, parser));
+        let mut conditions = vec![first];
+        let mut is_and = None;
+        loop {
+            let position = input.position();
+            let keyword_is_and = match input.next() {
+                Ok(Token::Ident(ref keyword)) if keyword.eq_ignore_ascii_case("and") => true,
+                Ok(Token::Ident(ref keyword)) if keyword.eq_ignore_ascii_case("or") => false,
+                _ => {
+                    input.reset(position);
+                    break
+                }
+            };
+            // `and` and `or` can not be mixed without parentheses.
+            if *is_and.get_or_insert(keyword_is_and) != keyword_is_and {
+                input.reset(position);
+                return Err(input.new_error(ParseErrorKind::Invalid))
+            }
+            conditions.push(try!(parse_in_parens(input, parser)))
+        }
+        Ok(match is_and {
+            None => conditions.pop().unwrap(),
+            Some(true) => SupportsCondition::And(conditions),
+            Some(false) => SupportsCondition::Or(conditions),
+        })
+    }
+
+    /// Evaluate the condition, given which declarations are supported.
+    pub fn evaluate(&self) -> bool {
+        match *self {
+            SupportsCondition::Not(ref condition) => !condition.evaluate(),
+            SupportsCondition::And(ref conditions) => conditions.iter().all(|c| c.evaluate()),
+            SupportsCondition::Or(ref conditions) => conditions.iter().any(|c| c.evaluate()),
+            SupportsCondition::Declaration { supported, .. } => supported,
+            SupportsCondition::GeneralEnclosed(_) => false,
+        }
+    }
+}
+
+
+impl<'i> ToCss for SupportsCondition<'i> {
+    fn to_css<W>(&self, dest: &mut W) -> fmt::Result where W: fmt::Write {
+        match *self {
+            SupportsCondition::Not(ref condition) => {
+                try!(dest.write_str("not "));
+                write_in_parens(condition, dest)
+            }
+            SupportsCondition::And(ref conditions) => write_joined(conditions, " and ", dest),
+            SupportsCondition::Or(ref conditions) => write_joined(conditions, " or ", dest),
+            SupportsCondition::Declaration { ref text, .. } => write!(dest, "({})", text),
+            SupportsCondition::GeneralEnclosed(ref text) => dest.write_str(text),
+        }
+    }
+}
+
+
+/// `<supports-in-parens> = ( <supports-condition> ) | <supports-feature> | <general-enclosed>`
+fn parse_in_parens<'i, 't, P>(input: &mut Parser<'i, 't>, parser: &mut P)
+                              -> Result<SupportsCondition<'i>, ParseError<'i>>
+                              where P: DeclarationParser {
+    if let Ok(condition) = input.try(|input| {
+        try!(input.expect_parenthesis_block());
+        input.parse_nested_block(|input| SupportsCondition::parse(input, parser))
+    }) {
+        return Ok(condition)
+    }
+
+    if let Ok(condition) = input.try(|input| -> Result<_, ParseError<'i>> {
+        try!(input.expect_parenthesis_block());
+        input.parse_nested_block(|input| {
+            let start = input.position();
+            let name = try!(input.expect_ident());
+            try!(input.expect_colon());
+            let supported = input.try(|input| {
+                input.parse_entirely(|input| parser.parse_value(&mut (), &name, input))
+            }).is_ok();
+            while input.next().is_ok() {}
+            Ok(SupportsCondition::Declaration {
+                text: input.slice_from(start).trim_matches(is_whitespace).into(),
+                supported: supported,
+            })
+        })
+    }) {
+        return Ok(condition)
+    }
+
+    let start = input.position();
+    match try!(input.next()) {
+        Token::ParenthesisBlock | Token::Function(_) => {}
+        token => return Err(unexpected_token(token, start))
+    }
+    try!(input.parse_nested_block(|input| {
+        while input.next().is_ok() {}
+        Ok::<(), ParseError>(())
+    }));
+    let text = input.slice_from(start).trim_matches(is_whitespace);
+    Ok(SupportsCondition::GeneralEnclosed(text.into()))
+}
+
+
+fn write_in_parens<W>(condition: &SupportsCondition, dest: &mut W) -> fmt::Result
+where W: fmt::Write {
+    match *condition {
+        SupportsCondition::Not(_) |
+        SupportsCondition::And(_) |
+        SupportsCondition::Or(_) => {
+            try!(dest.write_str("("));
+            try!(condition.to_css(dest));
+            dest.write_str(")")
+        }
+        _ => condition.to_css(dest)
+    }
+}
+
+
+fn write_joined<W>(conditions: &[SupportsCondition], separator: &str, dest: &mut W)
+                   -> fmt::Result where W: fmt::Write {
+    for (i, condition) in conditions.iter().enumerate() {
+        if i > 0 {
+            try!(dest.write_str(separator));
+        }
+        try!(write_in_parens(condition, dest));
+    }
+    Ok(())
+}
+
+
+fn is_whitespace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '\r' | '\x0C')
+}
@@ -6,11 +6,19 @@
 
 use std::ops::Range;
 use std::cell::Cell;
+use std::error::Error;
+use std::fmt;
 use std::char;
 use std::ascii::AsciiExt;
 use std::borrow::{Cow, ToOwned};
 use std::borrow::Cow::{Owned, Borrowed};
 use std::i32;
+use std::mem;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(feature = "serde")]
+use serde::de::{self, EnumVisitor, VariantVisitor, Visitor};
 
 use self::Token::*;
 
@@ -71,7 +79,7 @@ pub enum Token<'a> {
     UnicodeRange(u32, u32),
 
     /// A [`<whitespace-token>`](https://drafts.csswg.org/css-syntax/#whitespace-token-diagram)
-    WhiteSpace(&'a str),
+    WhiteSpace(Cow<'a, str>),
 
     /// A comment.
     ///
@@ -79,7 +87,7 @@ pub enum Token<'a> {
     /// But we do, because we can (borrowed &str makes it cheap).
     ///
     /// The value does not include the `/*` `*/` markers.
-    Comment(&'a str),
+    Comment(Cow<'a, str>),
 
     /// A `:` `<colon-token>`
     Colon,  // :
@@ -171,6 +179,266 @@ impl<'a> Token<'a> {
             BadUrl | BadString | CloseParenthesis | CloseSquareBracket | CloseCurlyBracket
         )
     }
+
+    /// Return the name of a `Function` or `AtKeyword` token (without `(` or `@`),
+    /// or the unit of a `Dimension` token, as written in the input (with escapes decoded).
+    ///
+    /// Use this to serialize the token as it was written.
+    pub fn name(&self) -> Option<&str> {
+        match *self {
+            Function(ref name) | AtKeyword(ref name) | Dimension(_, ref name) => Some(name),
+            _ => None
+        }
+    }
+
+    /// Return the same as `name`, in ASCII lower case.
+    ///
+    /// Use this to match the name against known names,
+    /// since these names are ASCII case-insensitive.
+    /// The name is only copied if it contains upper case letters.
+    pub fn lowercase_name(&self) -> Option<Cow<str>> {
+        self.name().map(|name| {
+            if name.bytes().any(|b| matches!(b, b'A'...b'Z')) {
+                Owned(name.to_ascii_lowercase())
+            } else {
+                Borrowed(name)
+            }
+        })
+    }
+
+    /// Return a token that owns its strings, and so does not borrow the input.
+    ///
+    /// Like all tokens, the result is `Send` and `Sync`:
+    /// it can be shared with other threads, for example in an `Arc`.
+    ///
+    pub fn into_owned(self) -> Token<'static> {
+        let owned = |value: Cow<str>| -> Cow<'static, str> { Owned(value.into_owned()) };
+        match self {
+            Ident(value) => Ident(owned(value)),
+            AtKeyword(value) => AtKeyword(owned(value)),
+            Hash(value) => Hash(owned(value)),
+            IDHash(value) => IDHash(owned(value)),
+            QuotedString(value) => QuotedString(owned(value)),
+            UnquotedUrl(value) => UnquotedUrl(owned(value)),
+            Dimension(value, unit) => Dimension(value, owned(unit)),
+            Function(name) => Function(owned(name)),
+            WhiteSpace(value) => WhiteSpace(owned(value)),
+            Comment(value) => Comment(owned(value)),
+            Delim(value) => Delim(value),
+            Number(value) => Number(value),
+            Percentage(value) => Percentage(value),
+            UnicodeRange(start, end) => UnicodeRange(start, end),
+            Colon => Colon,
+            Semicolon => Semicolon,
+            Comma => Comma,
+            IncludeMatch => IncludeMatch,
+            DashMatch => DashMatch,
+            PrefixMatch => PrefixMatch,
+            SuffixMatch => SuffixMatch,
+            SubstringMatch => SubstringMatch,
+            Column => Column,
+            CDO => CDO,
+            CDC => CDC,
+            ParenthesisBlock => ParenthesisBlock,
+            SquareBracketBlock => SquareBracketBlock,
+            CurlyBracketBlock => CurlyBracketBlock,
+            BadUrl => BadUrl,
+            BadString => BadString,
+            CloseParenthesis => CloseParenthesis,
+            CloseSquareBracket => CloseSquareBracket,
+            CloseCurlyBracket => CloseCurlyBracket,
+        }
+    }
+}
+
+
+#[cfg(feature = "serde")]
+const TOKEN_VARIANTS: &'static [&'static str] = &[
+    "Ident", "AtKeyword", "Hash", "IDHash", "QuotedString", "UnquotedUrl", "Delim",
+    "Number", "Percentage", "Dimension", "UnicodeRange", "WhiteSpace", "Comment",
+    "Colon", "Semicolon", "Comma", "IncludeMatch", "DashMatch", "PrefixMatch", "SuffixMatch",
+    "SubstringMatch", "Column", "CDO", "CDC", "Function", "ParenthesisBlock",
+    "SquareBracketBlock", "CurlyBracketBlock", "BadUrl", "BadString", "CloseParenthesis",
+    "CloseSquareBracket", "CloseCurlyBracket",
+];
+
+
+/// Tokens are serialized as an enum, with a `(value, unit)` tuple for `Dimension`
+/// and a `(start, end)` tuple for `UnicodeRange`.
+#[cfg(feature = "serde")]
+impl<'a> Serialize for Token<'a> {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), S::Error>
+        where S: Serializer
+    {
+        match *self {
+            Ident(ref value) => serialize_newtype_variant(serializer, "Ident", value),
+            AtKeyword(ref value) => serialize_newtype_variant(serializer, "AtKeyword", value),
+            Hash(ref value) => serialize_newtype_variant(serializer, "Hash", value),
+            IDHash(ref value) => serialize_newtype_variant(serializer, "IDHash", value),
+            QuotedString(ref value) => {
+                serialize_newtype_variant(serializer, "QuotedString", value)
+            }
+            UnquotedUrl(ref value) => serialize_newtype_variant(serializer, "UnquotedUrl", value),
+            Delim(value) => serialize_newtype_variant(serializer, "Delim", value),
+            Number(value) => serialize_newtype_variant(serializer, "Number", value),
+            Percentage(value) => serialize_newtype_variant(serializer, "Percentage", value),
+            Dimension(value, ref unit) => {
+                serialize_newtype_variant(serializer, "Dimension", (value, unit))
+            }
+            UnicodeRange(start, end) => {
+                serialize_newtype_variant(serializer, "UnicodeRange", (start, end))
+            }
+            WhiteSpace(ref value) => serialize_newtype_variant(serializer, "WhiteSpace", value),
+            Comment(ref value) => serialize_newtype_variant(serializer, "Comment", value),
+            Function(ref name) => serialize_newtype_variant(serializer, "Function", name),
+            Colon => serialize_unit_variant(serializer, "Colon"),
+            Semicolon => serialize_unit_variant(serializer, "Semicolon"),
+            Comma => serialize_unit_variant(serializer, "Comma"),
+            IncludeMatch => serialize_unit_variant(serializer, "IncludeMatch"),
+            DashMatch => serialize_unit_variant(serializer, "DashMatch"),
+            PrefixMatch => serialize_unit_variant(serializer, "PrefixMatch"),
+            SuffixMatch => serialize_unit_variant(serializer, "SuffixMatch"),
+            SubstringMatch => serialize_unit_variant(serializer, "SubstringMatch"),
+            Column => serialize_unit_variant(serializer, "Column"),
+            CDO => serialize_unit_variant(serializer, "CDO"),
+            CDC => serialize_unit_variant(serializer, "CDC"),
+            ParenthesisBlock => serialize_unit_variant(serializer, "ParenthesisBlock"),
+            SquareBracketBlock => serialize_unit_variant(serializer, "SquareBracketBlock"),
+            CurlyBracketBlock => serialize_unit_variant(serializer, "CurlyBracketBlock"),
+            BadUrl => serialize_unit_variant(serializer, "BadUrl"),
+            BadString => serialize_unit_variant(serializer, "BadString"),
+            CloseParenthesis => serialize_unit_variant(serializer, "CloseParenthesis"),
+            CloseSquareBracket => serialize_unit_variant(serializer, "CloseSquareBracket"),
+            CloseCurlyBracket => serialize_unit_variant(serializer, "CloseCurlyBracket"),
+        }
+    }
+}
+
+
+/// Deserialized tokens own their strings.
+#[cfg(feature = "serde")]
+impl<'a> Deserialize for Token<'a> {
+    fn deserialize<D>(deserializer: &mut D) -> Result<Self, D::Error>
+        where D: Deserializer
+    {
+        deserializer.deserialize_enum("Token", TOKEN_VARIANTS, TokenVisitor)
+    }
+}
+
+
+#[cfg(feature = "serde")]
+fn serialize_unit_variant<S>(serializer: &mut S, variant: &'static str) -> Result<(), S::Error>
+where S: Serializer {
+    serializer.serialize_unit_variant("Token", token_variant_index(variant), variant)
+}
+
+
+#[cfg(feature = "serde")]
+fn serialize_newtype_variant<S, T>(serializer: &mut S, variant: &'static str, value: T)
+                                   -> Result<(), S::Error>
+where S: Serializer, T: Serialize {
+    serializer.serialize_newtype_variant("Token", token_variant_index(variant), variant, value)
+}
+
+
+#[cfg(feature = "serde")]
+fn token_variant_index(variant: &str) -> usize {
+    TOKEN_VARIANTS.iter().position(|&v| v == variant).unwrap()
+}
+
+
+#[cfg(feature = "serde")]
+struct TokenVisitor;
+
+#[cfg(feature = "serde")]
+impl EnumVisitor for TokenVisitor {
+    type Value = Token<'static>;
+
+    fn visit<V>(&mut self, mut visitor: V) -> Result<Token<'static>, V::Error>
+        where V: VariantVisitor
+    {
+        let TokenVariant(variant) = try!(visitor.visit_variant());
+        let token = match variant {
+            "Ident" => return Ok(Ident(try!(visitor.visit_newtype()))),
+            "AtKeyword" => return Ok(AtKeyword(try!(visitor.visit_newtype()))),
+            "Hash" => return Ok(Hash(try!(visitor.visit_newtype()))),
+            "IDHash" => return Ok(IDHash(try!(visitor.visit_newtype()))),
+            "QuotedString" => return Ok(QuotedString(try!(visitor.visit_newtype()))),
+            "UnquotedUrl" => return Ok(UnquotedUrl(try!(visitor.visit_newtype()))),
+            "Delim" => return Ok(Delim(try!(visitor.visit_newtype()))),
+            "Number" => return Ok(Number(try!(visitor.visit_newtype()))),
+            "Percentage" => return Ok(Percentage(try!(visitor.visit_newtype()))),
+            "Dimension" => {
+                let (value, unit) = try!(visitor.visit_newtype());
+                return Ok(Dimension(value, unit))
+            }
+            "UnicodeRange" => {
+                let (start, end) = try!(visitor.visit_newtype());
+                return Ok(UnicodeRange(start, end))
+            }
+            "WhiteSpace" => return Ok(WhiteSpace(try!(visitor.visit_newtype()))),
+            "Comment" => return Ok(Comment(try!(visitor.visit_newtype()))),
+            "Function" => return Ok(Function(try!(visitor.visit_newtype()))),
+            "Colon" => Colon,
+            "Semicolon" => Semicolon,
+            "Comma" => Comma,
+            "IncludeMatch" => IncludeMatch,
+            "DashMatch" => DashMatch,
+            "PrefixMatch" => PrefixMatch,
+            "SuffixMatch" => SuffixMatch,
+            "SubstringMatch" => SubstringMatch,
+            "Column" => Column,
+            "CDO" => CDO,
+            "CDC" => CDC,
+            "ParenthesisBlock" => ParenthesisBlock,
+            "SquareBracketBlock" => SquareBracketBlock,
+            "CurlyBracketBlock" => CurlyBracketBlock,
+            "BadUrl" => BadUrl,
+            "BadString" => BadString,
+            "CloseParenthesis" => CloseParenthesis,
+            "CloseSquareBracket" => CloseSquareBracket,
+            "CloseCurlyBracket" => CloseCurlyBracket,
+            _ => unreachable!(),
+        };
+        try!(visitor.visit_unit());
+        Ok(token)
+    }
+}
+
+
+/// The name of a variant of `Token`, deserialized from either its name or its index.
+#[cfg(feature = "serde")]
+struct TokenVariant(&'static str);
+
+#[cfg(feature = "serde")]
+impl Deserialize for TokenVariant {
+    fn deserialize<D>(deserializer: &mut D) -> Result<Self, D::Error>
+        where D: Deserializer
+    {
+        deserializer.deserialize_struct_field(TokenVariantVisitor)
+    }
+}
+
+#[cfg(feature = "serde")]
+struct TokenVariantVisitor;
+
+#[cfg(feature = "serde")]
+impl Visitor for TokenVariantVisitor {
+    type Value = TokenVariant;
+
+    fn visit_u64<E>(&mut self, index: u64) -> Result<TokenVariant, E> where E: de::Error {
+        match TOKEN_VARIANTS.get(index as usize) {
+            Some(&variant) => Ok(TokenVariant(variant)),
+            None => Err(E::invalid_value("unknown Token variant index")),
+        }
+    }
+
+    fn visit_str<E>(&mut self, name: &str) -> Result<TokenVariant, E> where E: de::Error {
+        match TOKEN_VARIANTS.iter().find(|&&variant| variant == name) {
+            Some(&variant) => Ok(TokenVariant(variant)),
+            None => Err(E::unknown_variant(name)),
+        }
+    }
 }
 
 
@@ -190,6 +458,30 @@ pub struct NumericValue {
 }
 
 
+#[cfg(feature = "serde")]
+impl Serialize for NumericValue {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), S::Error>
+        where S: Serializer
+    {
+        (self.value, self.int_value, self.has_sign).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Deserialize for NumericValue {
+    fn deserialize<D>(deserializer: &mut D) -> Result<Self, D::Error>
+        where D: Deserializer
+    {
+        let (value, int_value, has_sign) = try!(Deserialize::deserialize(deserializer));
+        Ok(NumericValue {
+            value: value,
+            int_value: int_value,
+            has_sign: has_sign,
+        })
+    }
+}
+
+
 /// The numeric value of `Percentage` tokens.
 #[derive(PartialEq, Debug, Copy, Clone)]
 pub struct PercentageValue {
@@ -204,6 +496,30 @@ pub struct PercentageValue {
 }
 
 
+#[cfg(feature = "serde")]
+impl Serialize for PercentageValue {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), S::Error>
+        where S: Serializer
+    {
+        (self.unit_value, self.int_value, self.has_sign).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Deserialize for PercentageValue {
+    fn deserialize<D>(deserializer: &mut D) -> Result<Self, D::Error>
+        where D: Deserializer
+    {
+        let (unit_value, int_value, has_sign) = try!(Deserialize::deserialize(deserializer));
+        Ok(PercentageValue {
+            unit_value: unit_value,
+            int_value: int_value,
+            has_sign: has_sign,
+        })
+    }
+}
+
+
 #[derive(Clone)]
 pub struct Tokenizer<'a> {
     input: &'a str,
@@ -213,6 +529,33 @@ pub struct Tokenizer<'a> {
     last_known_source_location: Cell<(SourcePosition, SourceLocation)>,
     var_functions: SeenStatus,
     viewport_percentages: SeenStatus,
+    /// `Some(_)` when recording syntax errors.
+    syntax_errors: Option<Vec<SyntaxError>>,
+    /// While recording syntax errors, the closing bytes for the blocks and functions
+    /// opened since recording started and not closed yet, innermost last.
+    open_brackets: Vec<u8>,
+    /// `Some(_)` when recording unknown functions: the known names in ASCII lower case,
+    /// and the unknown functions found so far.
+    unknown_functions: Option<(Vec<String>, Vec<UnknownFunction>)>,
+    /// How far the input has been tokenized while recording syntax errors or unknown functions.
+    recorded_until: usize,
+    /// Whether the last token started before `recorded_until`,
+    /// so that its errors have already been recorded.
+    replaying: bool,
+    /// Whether tokens are being skipped, so that their escapes don’t need to be decoded.
+    skipping: bool,
+    /// The last token that is not whitespace, a comment, or a token with an owned value,
+    /// to return it again without tokenizing after `reset`, as done by `Parser::try`.
+    cached_token: Option<CachedToken<'a>>,
+}
+
+#[derive(Clone)]
+struct CachedToken<'a> {
+    token: Token<'a>,
+    start: usize,
+    end: usize,
+    /// Whether escapes were decoded. (See `Tokenizer::decodes_escapes`.)
+    decoded: bool,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq)]
@@ -233,9 +576,34 @@ impl<'a> Tokenizer<'a> {
                                                    SourceLocation { line: 1, column: 1 })),
             var_functions: SeenStatus::DontCare,
             viewport_percentages: SeenStatus::DontCare,
+            syntax_errors: None,
+            open_brackets: Vec::new(),
+            unknown_functions: None,
+            recorded_until: 0,
+            replaying: false,
+            skipping: false,
+            cached_token: None,
         }
     }
 
+    /// Until `skipping(false)` is called, return tokens whose string values may be left
+    /// with escapes undecoded (as a borrowed slice of the input)
+    /// if that does not affect token boundaries or anything else the tokenizer reports.
+    /// Return the previous state.
+    #[inline]
+    pub fn skipping(&mut self, skipping: bool) -> bool {
+        mem::replace(&mut self.skipping, skipping)
+    }
+
+    /// Whether escapes need to be decoded: always, except when skipping tokens
+    /// and nothing depends on their values.
+    #[inline]
+    fn decodes_escapes(&self) -> bool {
+        !self.skipping || self.syntax_errors.is_some() ||
+        self.var_functions == SeenStatus::LookingForThem ||
+        self.viewport_percentages == SeenStatus::LookingForThem
+    }
+
     #[inline]
     pub fn look_for_var_functions(&mut self) {
         self.var_functions = SeenStatus::LookingForThem;
@@ -260,9 +628,201 @@ impl<'a> Tokenizer<'a> {
         seen
     }
 
+    #[inline]
+    fn see_function(&mut self, name: &str) {
+        if self.var_functions == SeenStatus::LookingForThem && name.eq_ignore_ascii_case("var") {
+            self.var_functions = SeenStatus::SeenAtLeastOne;
+        }
+    }
+
+    #[inline]
+    fn see_dimension_unit(&mut self, unit: &str) {
+        if self.viewport_percentages == SeenStatus::LookingForThem &&
+           (unit.eq_ignore_ascii_case("vh") ||
+            unit.eq_ignore_ascii_case("vw") ||
+            unit.eq_ignore_ascii_case("vmin") ||
+            unit.eq_ignore_ascii_case("vmax")) {
+            self.viewport_percentages = SeenStatus::SeenAtLeastOne;
+        }
+    }
+
+    #[inline]
+    fn is_recording(&self) -> bool {
+        self.syntax_errors.is_some() || self.unknown_functions.is_some()
+    }
+
+    #[inline]
+    pub fn record_syntax_errors(&mut self) {
+        if self.syntax_errors.is_none() {
+            if !self.is_recording() {
+                self.recorded_until = self.position;
+            }
+            self.syntax_errors = Some(Vec::new());
+            self.open_brackets.clear();
+        }
+    }
+
+    #[inline]
+    pub fn take_syntax_errors(&mut self) -> Vec<SyntaxError> {
+        match self.syntax_errors {
+            Some(ref mut errors) => mem::replace(errors, Vec::new()),
+            None => Vec::new(),
+        }
+    }
+
+    /// Record an error at `position` in the last token, unless it was already recorded.
+    pub fn report_syntax_error(&mut self, position: SourcePosition, code: SyntaxErrorCode) {
+        if self.syntax_errors.is_some() && !self.replaying {
+            let location = self.source_location(position);
+            if let Some(ref mut errors) = self.syntax_errors {
+                errors.push(SyntaxError {
+                    code: code,
+                    location: location,
+                })
+            }
+        }
+    }
+
+    #[inline]
+    fn report_syntax_error_here(&mut self, code: SyntaxErrorCode) {
+        let position = self.position();
+        self.report_syntax_error(position, code)
+    }
+
+    /// Record a closing token that starts at `start`
+    /// if it does not close the innermost block or function opened so far.
+    ///
+    /// This sees every token the first time it is tokenized,
+    /// including those skipped by the parser, so it doesn’t matter how they are consumed.
+    fn check_brackets(&mut self, start: usize, token: &Token) {
+        if self.syntax_errors.is_none() || self.replaying {
+            return
+        }
+        let closing_byte = match *token {
+            Function(_) | ParenthesisBlock => b')',
+            SquareBracketBlock => b']',
+            CurlyBracketBlock => b'}',
+            CloseParenthesis | CloseSquareBracket | CloseCurlyBracket => {
+                if self.open_brackets.last() == Some(&self.input.as_bytes()[start]) {
+                    self.open_brackets.pop();
+                } else {
+                    self.report_syntax_error(SourcePosition(start),
+                                             SyntaxErrorCode::UnmatchedCloseBracket)
+                }
+                return
+            }
+            _ => return
+        };
+        self.open_brackets.push(closing_byte)
+    }
+
+    pub fn record_unknown_functions(&mut self, known_names: &[&str]) {
+        if !self.is_recording() {
+            self.recorded_until = self.position;
+        }
+        let known_names = known_names.iter().map(|name| name.to_ascii_lowercase()).collect();
+        let found = self.take_unknown_functions();
+        self.unknown_functions = Some((known_names, found));
+    }
+
+    pub fn take_unknown_functions(&mut self) -> Vec<UnknownFunction> {
+        match self.unknown_functions {
+            Some((_, ref mut found)) => mem::replace(found, Vec::new()),
+            None => Vec::new(),
+        }
+    }
+
+    /// Record a function token that starts at `position`
+    /// if its name is unknown and it was not already recorded.
+    fn check_function_name(&mut self, position: SourcePosition, name: &str) {
+        let is_unknown = match self.unknown_functions {
+            Some((ref known_names, _)) => {
+                !self.replaying && !known_names.iter().any(|known| known.eq_ignore_ascii_case(name))
+            }
+            None => false,
+        };
+        if is_unknown {
+            let location = self.source_location(position);
+            if let Some((_, ref mut found)) = self.unknown_functions {
+                found.push(UnknownFunction {
+                    name: name.to_owned(),
+                    location: location,
+                })
+            }
+        }
+    }
+
     #[inline]
     pub fn next(&mut self) -> Result<Token<'a>, ()> {
-        next_token(self).ok_or(())
+        let start = self.position;
+        let token = if !self.is_recording() {
+            if let Some(token) = self.take_cached_token() {
+                Some(token)
+            } else {
+                let decoded = self.decodes_escapes();
+                let token = next_token(self);
+                if let Some(ref token) = token {
+                    self.cache_token(token, start, decoded)
+                }
+                token
+            }
+        } else {
+            // After `reset`, tokens are tokenized again: don’t record their errors twice.
+            self.replaying = start < self.recorded_until;
+            let token = next_token(self);
+            if let Some(ref token) = token {
+                self.check_brackets(start, token)
+            }
+            if self.position > self.recorded_until {
+                self.recorded_until = self.position
+            }
+            token
+        };
+        check_invariant!(if token.is_some() { self.position > start } else { self.is_eof() },
+                         "empty token or no token before the end of the input at {}", start);
+        check_invariant!(self.input.is_char_boundary(self.position),
+                         "token ends in the middle of a character at {}", self.position);
+        token.ok_or(())
+    }
+
+    /// Keep a copy of a token that was just tokenized from `start`,
+    /// unless it is whitespace or a comment,
+    /// or copying it would allocate because escapes were decoded into its value.
+    fn cache_token(&mut self, token: &Token<'a>, start: usize, decoded: bool) {
+        match *token {
+            WhiteSpace(_) | Comment(_) |
+            Ident(Owned(_)) | AtKeyword(Owned(_)) | Hash(Owned(_)) |
+            IDHash(Owned(_)) | QuotedString(Owned(_)) | UnquotedUrl(Owned(_)) |
+            Function(Owned(_)) | Dimension(_, Owned(_)) => return,
+            _ => {}
+        }
+        self.cached_token = Some(CachedToken {
+            token: token.clone(),
+            start: start,
+            end: self.position,
+            decoded: decoded,
+        })
+    }
+
+    /// If the cached token starts at the current position
+    /// and its escapes were decoded as needed, consume it again.
+    fn take_cached_token(&mut self) -> Option<Token<'a>> {
+        let decodes_escapes = self.decodes_escapes();
+        let (token, end) = match self.cached_token {
+            Some(ref cached) if cached.start == self.position &&
+                                (cached.decoded || !decodes_escapes) => {
+                (cached.token.clone(), cached.end)
+            }
+            _ => return None
+        };
+        self.position = end;
+        // Have the same side effects as tokenizing it.
+        match token {
+            Function(ref name) => self.see_function(name),
+            Dimension(_, ref unit) => self.see_dimension_unit(unit),
+            _ => {}
+        }
+        Some(token)
     }
 
     #[inline]
@@ -272,9 +832,17 @@ impl<'a> Tokenizer<'a> {
 
     #[inline]
     pub fn reset(&mut self, new_position: SourcePosition) {
+        check_invariant!(self.input.is_char_boundary(new_position.0),
+                         "reset to {}, not a position in the input", new_position.0);
         self.position = new_position.0;
     }
 
+    /// The last byte before the current position, if any.
+    #[inline]
+    pub fn previous_byte(&self) -> Option<u8> {
+        self.input.as_bytes()[..self.position].last().cloned()
+    }
+
     #[inline]
     pub fn slice_from(&self, start_pos: SourcePosition) -> &'a str {
         &self.input[start_pos.0..self.position]
@@ -285,6 +853,16 @@ impl<'a> Tokenizer<'a> {
         &self.input[range.start.0..range.end.0]
     }
 
+    /// A new tokenizer for the given range of the input, with the same positions.
+    /// Nothing is recorded, like with `Tokenizer::new`.
+    pub fn sub_tokenizer(&self, range: Range<SourcePosition>) -> Tokenizer<'a> {
+        let mut tokenizer = Tokenizer::new(&self.input[..range.end.0]);
+        tokenizer.position = range.start.0;
+        tokenizer.recorded_until = range.start.0;
+        tokenizer.last_known_source_location.set((range.start, self.source_location(range.start)));
+        tokenizer
+    }
+
     #[inline]
     pub fn current_source_location(&self) -> SourceLocation {
         let position = SourcePosition(self.position);
@@ -375,9 +953,127 @@ impl<'a> Tokenizer<'a> {
 #[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
 pub struct SourcePosition(usize);
 
+impl SourcePosition {
+    /// Counted in bytes, not code points. From 0.
+    #[inline]
+    pub fn byte_index(self) -> usize {
+        self.0
+    }
 
-/// The line and column number for a given position within the input.
+    /// The position at the given byte index.
+    #[inline]
+    pub fn from_byte_index(index: usize) -> SourcePosition {
+        SourcePosition(index)
+    }
+}
+
+
+/// A kind of error that CSS Syntax defines as a parse error,
+/// but that the tokenizer or parser recovers from.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum SyntaxErrorCode {
+    /// A comment is not closed before the end of the input.
+    EofInComment,
+
+    /// A quoted string is not closed before the end of the input.
+    EofInString,
+
+    /// A quoted string contains an unescaped newline. This gives a `BadString` token.
+    NewlineInString,
+
+    /// An unquoted `url(…)` is not closed before the end of the input.
+    EofInUrl,
+
+    /// An unquoted `url(…)` contains invalid characters. This gives a `BadUrl` token.
+    BadUrl,
+
+    /// A backslash is followed by a newline outside of a quoted string.
+    InvalidEscape,
+
+    /// A backslash is at the end of the input outside of a quoted string.
+    EofInEscape,
+
+    /// A `)`, `]`, or `}` token that does not close the innermost block or function,
+    /// whether the parser returns it or skips it.
+    UnmatchedCloseBracket,
+}
+
+
+impl SyntaxErrorCode {
+    fn message(self) -> &'static str {
+        match self {
+            SyntaxErrorCode::EofInComment => "unclosed comment",
+            SyntaxErrorCode::EofInString => "unclosed string",
+            SyntaxErrorCode::NewlineInString => "newline in string",
+            SyntaxErrorCode::EofInUrl => "unclosed url()",
+            SyntaxErrorCode::BadUrl => "invalid character in url()",
+            SyntaxErrorCode::InvalidEscape => "backslash followed by a newline",
+            SyntaxErrorCode::EofInEscape => "backslash at the end of the input",
+            SyntaxErrorCode::UnmatchedCloseBracket => "unmatched closing bracket",
+        }
+    }
+}
+
+
+impl fmt::Display for SyntaxErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+
+impl Error for SyntaxErrorCode {
+    fn description(&self) -> &str {
+        self.message()
+    }
+}
+
+
+/// A recoverable parse error, as recorded by `Parser::record_syntax_errors`.
 #[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct SyntaxError {
+    /// What kind of error this is.
+    pub code: SyntaxErrorCode,
+
+    /// Where the error occurs in the input.
+    pub location: SourceLocation,
+}
+
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {}:{}", self.code, self.location.line, self.location.column)
+    }
+}
+
+
+impl Error for SyntaxError {
+    fn description(&self) -> &str {
+        self.code.message()
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        Some(&self.code)
+    }
+}
+
+
+/// A function whose name is not one of the known names,
+/// as recorded by `Parser::record_unknown_functions`.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct UnknownFunction {
+    /// The name of the function, without the `(` marker.
+    pub name: String,
+
+    /// Where the function starts in the input.
+    pub location: SourceLocation,
+}
+
+
+/// The line and column number for a given position within the input.
+///
+/// Locations are ordered by line, then by column.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
 pub struct SourceLocation {
     /// The line number, starting at 1 for the first line.
     pub line: usize,
@@ -387,6 +1083,29 @@ pub struct SourceLocation {
 }
 
 
+#[cfg(feature = "serde")]
+impl Serialize for SourceLocation {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), S::Error>
+        where S: Serializer
+    {
+        (self.line, self.column).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Deserialize for SourceLocation {
+    fn deserialize<D>(deserializer: &mut D) -> Result<Self, D::Error>
+        where D: Deserializer
+    {
+        let (line, column) = try!(Deserialize::deserialize(deserializer));
+        Ok(SourceLocation {
+            line: line,
+            column: column,
+        })
+    }
+}
+
+
 fn next_token<'a>(tokenizer: &mut Tokenizer<'a>) -> Option<Token<'a>> {
     if tokenizer.is_eof() {
         return None
@@ -402,7 +1121,7 @@ fn next_token<'a>(tokenizer: &mut Tokenizer<'a>) -> Option<Token<'a>> {
                     _ => break,
                 }
             }
-            WhiteSpace(tokenizer.slice_from(start_position))
+            WhiteSpace(Borrowed(tokenizer.slice_from(start_position)))
         },
         '"' => consume_string(tokenizer, false),
         '#' => {
@@ -485,9 +1204,10 @@ fn next_token<'a>(tokenizer: &mut Tokenizer<'a>) -> Option<Token<'a>> {
                 None => {
                     tokenizer.position = tokenizer.input.len();
                     content = tokenizer.slice_from(start_position);
+                    tokenizer.report_syntax_error_here(SyntaxErrorCode::EofInComment);
                 }
             }
-            Comment(content)
+            Comment(Borrowed(content))
         }
         '0'...'9' => consume_numeric(tokenizer),
         ':' => { tokenizer.advance(1); Colon },
@@ -517,7 +1237,11 @@ fn next_token<'a>(tokenizer: &mut Tokenizer<'a>) -> Option<Token<'a>> {
         '[' => { tokenizer.advance(1); SquareBracketBlock },
         '\\' => {
             if !tokenizer.has_newline_at(1) { consume_ident_like(tokenizer) }
-            else { tokenizer.advance(1); Delim(c) }
+            else {
+                tokenizer.report_syntax_error_here(SyntaxErrorCode::InvalidEscape);
+                tokenizer.advance(1);
+                Delim(c)
+            }
         },
         ']' => { tokenizer.advance(1); CloseSquareBracket },
         '^' => {
@@ -551,7 +1275,7 @@ fn next_token<'a>(tokenizer: &mut Tokenizer<'a>) -> Option<Token<'a>> {
 fn consume_string<'a>(tokenizer: &mut Tokenizer<'a>, single_quote: bool) -> Token<'a> {
     match consume_quoted_string(tokenizer, single_quote) {
         Ok(value) => QuotedString(value),
-        Err(()) => BadString
+        Err(_) => BadString
     }
 }
 
@@ -561,9 +1285,11 @@ fn consume_quoted_string<'a>(tokenizer: &mut Tokenizer<'a>, single_quote: bool)
                              -> Result<Cow<'a, str>, ()> {
     tokenizer.advance(1);  // Skip the initial quote
     let start_pos = tokenizer.position();
+    let decode = tokenizer.decodes_escapes();
     let mut string;
     loop {
         if tokenizer.is_eof() {
+            tokenizer.report_syntax_error_here(SyntaxErrorCode::EofInString);
             return Ok(Borrowed(tokenizer.slice_from(start_pos)))
         }
         match tokenizer.next_char() {
@@ -578,10 +1304,17 @@ fn consume_quoted_string<'a>(tokenizer: &mut Tokenizer<'a>, single_quote: bool)
                 return Ok(Borrowed(value))
             }
             '\\' | '\0' => {
-                string = tokenizer.slice_from(start_pos).to_owned();
+                string = if decode {
+                    Some(tokenizer.slice_from(start_pos).to_owned())
+                } else {
+                    None
+                };
                 break
             }
-            '\n' | '\r' | '\x0C' => return Err(()),
+            '\n' | '\r' | '\x0C' => {
+                tokenizer.report_syntax_error_here(SyntaxErrorCode::NewlineInString);
+                return Err(())
+            }
             _ => {
                 tokenizer.consume_char();
             }
@@ -590,11 +1323,17 @@ fn consume_quoted_string<'a>(tokenizer: &mut Tokenizer<'a>, single_quote: bool)
 
     while !tokenizer.is_eof() {
         if matches!(tokenizer.next_char(), '\n' | '\r' | '\x0C') {
+            tokenizer.report_syntax_error_here(SyntaxErrorCode::NewlineInString);
             return Err(());
         }
-        match tokenizer.consume_char() {
-            '"' if !single_quote => break,
-            '\'' if single_quote => break,
+        let char_position = tokenizer.position();
+        let c = match tokenizer.consume_char() {
+            '"' if !single_quote => {
+                return Ok(decoded_or_slice(tokenizer, start_pos..char_position, string))
+            }
+            '\'' if single_quote => {
+                return Ok(decoded_or_slice(tokenizer, start_pos..char_position, string))
+            }
             '\\' => {
                 if !tokenizer.is_eof() {
                     match tokenizer.next_char() {
@@ -606,16 +1345,55 @@ fn consume_quoted_string<'a>(tokenizer: &mut Tokenizer<'a>, single_quote: bool)
                                 tokenizer.advance(1);
                             }
                         }
-                        _ => string.push(consume_escape(tokenizer))
+                        _ => {
+                            let c = consume_escape(tokenizer);
+                            if let Some(ref mut string) = string {
+                                string.push(c)
+                            }
+                        }
                     }
                 }
                 // else: escaped EOF, do nothing.
+                continue
             }
-            '\0' => string.push('\u{FFFD}'),
-            c => string.push(c),
+            '\0' => '\u{FFFD}',
+            c => c,
+        };
+        if let Some(ref mut string) = string {
+            string.push(c)
         }
     }
-    Ok(Owned(string))
+    tokenizer.report_syntax_error_here(SyntaxErrorCode::EofInString);
+    let end_position = tokenizer.position();
+    Ok(decoded_or_slice(tokenizer, start_pos..end_position, string))
+}
+
+
+/// The decoded value if escapes were decoded, or else the given slice of the input.
+fn decoded_or_slice<'a>(tokenizer: &Tokenizer<'a>, range: Range<SourcePosition>,
+                        decoded: Option<String>) -> Cow<'a, str> {
+    match decoded {
+        Some(decoded) => Owned(decoded),
+        None => Borrowed(tokenizer.slice(range)),
+    }
+}
+
+
+/// Return whether the given string starts with an identifier (or a function name),
+/// per [CSS Syntax](https://drafts.csswg.org/css-syntax/#would-start-an-identifier).
+///
+/// The rest of the string is not checked. See also `is_css_identifier`.
+pub fn would_start_identifier(value: &str) -> bool {
+    is_ident_start(&mut Tokenizer::new(value))
+}
+
+
+/// Return the byte index of the end of the token that starts at byte index `start`.
+/// (Not exported outside this crate.)
+pub fn token_end(input: &str, start: usize) -> usize {
+    let mut tokenizer = Tokenizer::new(&input[start..]);
+    let _ = tokenizer.next();
+    start + tokenizer.position().byte_index()
 }
 
 
@@ -625,7 +1403,7 @@ fn is_ident_start(tokenizer: &mut Tokenizer) -> bool {
         'a'...'z' | 'A'...'Z' | '_' | '\0' => true,
         '-' => tokenizer.has_at_least(1) && match tokenizer.char_at(1) {
             'a'...'z' | 'A'...'Z' | '-' | '_' | '\0' => true,
-            '\\' => !tokenizer.has_newline_at(1),
+            '\\' => !tokenizer.has_newline_at(2),
             c => c > '\x7F',  // Non-ASCII
         },
         '\\' => !tokenizer.has_newline_at(1),
@@ -635,16 +1413,22 @@ fn is_ident_start(tokenizer: &mut Tokenizer) -> bool {
 
 
 fn consume_ident_like<'a>(tokenizer: &mut Tokenizer<'a>) -> Token<'a> {
-    let value = consume_name(tokenizer);
+    let start_position = tokenizer.position();
+    // Always decoded, to find `url(` functions.
+    let value = consume_name_with(tokenizer, true);
     if !tokenizer.is_eof() && tokenizer.next_char() == '(' {
         tokenizer.advance(1);
         if value.eq_ignore_ascii_case("url") {
-            consume_unquoted_url(tokenizer).unwrap_or(Function(value))
-        } else {
-            if tokenizer.var_functions == SeenStatus::LookingForThem &&
-                value.eq_ignore_ascii_case("var") {
-                tokenizer.var_functions = SeenStatus::SeenAtLeastOne;
+            match consume_unquoted_url(tokenizer) {
+                Ok(token) => token,
+                Err(_) => {
+                    tokenizer.check_function_name(start_position, &value);
+                    Function(value)
+                }
             }
+        } else {
+            tokenizer.see_function(&value);
+            tokenizer.check_function_name(start_position, &value);
             Function(value)
         }
     } else {
@@ -653,6 +1437,12 @@ fn consume_ident_like<'a>(tokenizer: &mut Tokenizer<'a>) -> Token<'a> {
 }
 
 fn consume_name<'a>(tokenizer: &mut Tokenizer<'a>) -> Cow<'a, str> {
+    let decode = tokenizer.decodes_escapes();
+    consume_name_with(tokenizer, decode)
+}
+
+/// If `decode` is false, escapes are consumed but the name is returned as in the input.
+fn consume_name_with<'a>(tokenizer: &mut Tokenizer<'a>, decode: bool) -> Cow<'a, str> {
     let start_pos = tokenizer.position();
     let mut value;
     loop {
@@ -662,7 +1452,11 @@ fn consume_name<'a>(tokenizer: &mut Tokenizer<'a>) -> Cow<'a, str> {
         match tokenizer.next_char() {
             'a'...'z' | 'A'...'Z' | '0'...'9' | '_' | '-'  => tokenizer.advance(1),
             '\\' | '\0' => {
-                value = tokenizer.slice_from(start_pos).to_owned();
+                value = if decode {
+                    Some(tokenizer.slice_from(start_pos).to_owned())
+                } else {
+                    None
+                };
                 break
             }
             c if c.is_ascii() => return Borrowed(tokenizer.slice_from(start_pos)),
@@ -674,7 +1468,7 @@ fn consume_name<'a>(tokenizer: &mut Tokenizer<'a>) -> Cow<'a, str> {
 
     while !tokenizer.is_eof() {
         let c = tokenizer.next_char();
-        value.push(match c {
+        let c = match c {
             'a'...'z' | 'A'...'Z' | '0'...'9' | '_' | '-'  => {
                 tokenizer.advance(1);
                 c
@@ -687,9 +1481,13 @@ fn consume_name<'a>(tokenizer: &mut Tokenizer<'a>) -> Cow<'a, str> {
             '\0' => { tokenizer.advance(1); '\u{FFFD}' },
             c if c.is_ascii() => break,
             _ => tokenizer.consume_char(),
-        })
+        };
+        if let Some(ref mut value) = value {
+            value.push(c)
+        }
     }
-    Owned(value)
+    let end_position = tokenizer.position();
+    decoded_or_slice(tokenizer, start_pos..end_position, value)
 }
 
 
@@ -796,14 +1594,7 @@ fn consume_numeric<'a>(tokenizer: &mut Tokenizer<'a>) -> Token<'a> {
     };
     if is_ident_start(tokenizer) {
         let name = consume_name(tokenizer);
-        if tokenizer.viewport_percentages == SeenStatus::LookingForThem {
-            if name.eq_ignore_ascii_case("vh") ||
-               name.eq_ignore_ascii_case("vw") ||
-               name.eq_ignore_ascii_case("vmin") ||
-               name.eq_ignore_ascii_case("vmax") {
-                   tokenizer.viewport_percentages = SeenStatus::SeenAtLeastOne;
-           }
-        }
+        tokenizer.see_dimension_unit(&name);
         Dimension(value, name)
     } else {
         Number(value)
@@ -827,13 +1618,16 @@ fn consume_unquoted_url<'a>(tokenizer: &mut Tokenizer<'a>) -> Result<Token<'a>,
         }
     }
     tokenizer.position = tokenizer.input.len();
+    tokenizer.report_syntax_error_here(SyntaxErrorCode::EofInUrl);
     return Ok(UnquotedUrl(Borrowed("")));
 
     fn consume_unquoted_url<'a>(tokenizer: &mut Tokenizer<'a>) -> Token<'a> {
         let start_pos = tokenizer.position();
+        let decode = tokenizer.decodes_escapes();
         let mut string;
         loop {
             if tokenizer.is_eof() {
+                tokenizer.report_syntax_error_here(SyntaxErrorCode::EofInUrl);
                 return UnquotedUrl(Borrowed(tokenizer.slice_from(start_pos)))
             }
             match tokenizer.next_char() {
@@ -849,11 +1643,16 @@ fn consume_unquoted_url<'a>(tokenizer: &mut Tokenizer<'a>) -> Result<Token<'a>,
                 }
                 '\x01'...'\x08' | '\x0B' | '\x0E'...'\x1F' | '\x7F'  // non-printable
                     | '"' | '\'' | '(' => {
+                    let error_position = tokenizer.position();
                     tokenizer.advance(1);
-                    return consume_bad_url(tokenizer)
+                    return consume_bad_url(tokenizer, error_position)
                 },
                 '\\' | '\0' => {
-                    string = tokenizer.slice_from(start_pos).to_owned();
+                    string = if decode {
+                        Some(tokenizer.slice_from(start_pos).to_owned())
+                    } else {
+                        None
+                    };
                     break
                 }
                 _ => {
@@ -862,39 +1661,52 @@ fn consume_unquoted_url<'a>(tokenizer: &mut Tokenizer<'a>) -> Result<Token<'a>,
             }
         }
         while !tokenizer.is_eof() {
+            let char_position = tokenizer.position();
             let next_char = match tokenizer.consume_char() {
                 ' ' | '\t' | '\n' | '\r' | '\x0C' => {
-                    return consume_url_end(tokenizer, Owned(string))
+                    let value = decoded_or_slice(tokenizer, start_pos..char_position, string);
+                    return consume_url_end(tokenizer, value)
+                }
+                ')' => {
+                    let value = decoded_or_slice(tokenizer, start_pos..char_position, string);
+                    return UnquotedUrl(value)
                 }
-                ')' => break,
                 '\x01'...'\x08' | '\x0B' | '\x0E'...'\x1F' | '\x7F'  // non-printable
-                    | '"' | '\'' | '(' => return consume_bad_url(tokenizer),
+                    | '"' | '\'' | '(' => return consume_bad_url(tokenizer, char_position),
                 '\\' => {
                     if tokenizer.has_newline_at(0) {
-                        return consume_bad_url(tokenizer)
+                        return consume_bad_url(tokenizer, char_position)
                     }
                     consume_escape(tokenizer)
                 },
                 '\0' => '\u{FFFD}',
                 c => c
             };
-            string.push(next_char)
+            if let Some(ref mut string) = string {
+                string.push(next_char)
+            }
         }
-        UnquotedUrl(Owned(string))
+        tokenizer.report_syntax_error_here(SyntaxErrorCode::EofInUrl);
+        let end_position = tokenizer.position();
+        UnquotedUrl(decoded_or_slice(tokenizer, start_pos..end_position, string))
     }
 
     fn consume_url_end<'a>(tokenizer: &mut Tokenizer<'a>, string: Cow<'a, str>) -> Token<'a> {
         while !tokenizer.is_eof() {
+            let char_position = tokenizer.position();
             match tokenizer.consume_char() {
                 ' ' | '\t' | '\n' | '\r' | '\x0C' => (),
-                ')' => break,
-                _ => return consume_bad_url(tokenizer)
+                ')' => return UnquotedUrl(string),
+                _ => return consume_bad_url(tokenizer, char_position)
             }
         }
+        tokenizer.report_syntax_error_here(SyntaxErrorCode::EofInUrl);
         UnquotedUrl(string)
     }
 
-    fn consume_bad_url<'a>(tokenizer: &mut Tokenizer<'a>) -> Token<'a> {
+    fn consume_bad_url<'a>(tokenizer: &mut Tokenizer<'a>, error_position: SourcePosition)
+                           -> Token<'a> {
+        tokenizer.report_syntax_error(error_position, SyntaxErrorCode::BadUrl);
         // Consume up to the closing )
         while !tokenizer.is_eof() {
             match tokenizer.consume_char() {
@@ -962,7 +1774,11 @@ fn consume_hex_digits<'a>(tokenizer: &mut Tokenizer<'a>) -> (u32, u32) {
 // and that the next input character has already been verified
 // to not be a newline.
 fn consume_escape(tokenizer: &mut Tokenizer) -> char {
-    if tokenizer.is_eof() { return '\u{FFFD}' }  // Escaped EOF
+    if tokenizer.is_eof() {
+        // Escaped EOF
+        tokenizer.report_syntax_error_here(SyntaxErrorCode::EofInEscape);
+        return '\u{FFFD}'
+    }
     match tokenizer.next_char() {
         '0'...'9' | 'A'...'F' | 'a'...'f' => {
             let (c, _) = consume_hex_digits(tokenizer);
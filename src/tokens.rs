@@ -0,0 +1,55 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::ops::Range;
+
+use tokenizer::{Tokenizer, Token};
+
+
+/// Split the input into a flat sequence of tokens, such as for an editor or a syntax highlighter.
+///
+/// The returned iterator yields each token with its byte range in the input.
+/// Unlike with `Parser`, blocks are not matched: closing brackets are returned as they appear,
+/// even when unmatched, and `BadString` and `BadUrl` tokens are returned like any other.
+/// Whitespace and comment tokens are included, so that the ranges cover the whole input,
+/// unless `Tokens::skip_comments` is used.
+pub fn tokenize(input: &str) -> Tokens {
+    Tokens {
+        tokenizer: Tokenizer::new(input),
+        skip_comments: false,
+    }
+}
+
+
+/// An iterator of tokens and their byte ranges. See the `tokenize` function.
+#[derive(Clone)]
+pub struct Tokens<'i> {
+    tokenizer: Tokenizer<'i>,
+    skip_comments: bool,
+}
+
+
+impl<'i> Tokens<'i> {
+    /// Do not return `Comment` tokens.
+    pub fn skip_comments(mut self) -> Self {
+        self.skip_comments = true;
+        self
+    }
+}
+
+
+impl<'i> Iterator for Tokens<'i> {
+    type Item = (Token<'i>, Range<usize>);
+
+    fn next(&mut self) -> Option<(Token<'i>, Range<usize>)> {
+        loop {
+            let start = self.tokenizer.position().byte_index();
+            match self.tokenizer.next() {
+                Ok(Token::Comment(_)) if self.skip_comments => {}
+                Ok(token) => return Some((token, start..self.tokenizer.position().byte_index())),
+                Err(()) => return None,
+            }
+        }
+    }
+}
@@ -0,0 +1,267 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::borrow::Cow;
+
+use ast::{ComponentValue, Node, Rule, BlockItem, Declaration};
+use parser::{byte_index, position_from_byte_index};
+use tokenizer::token_end;
+use super::{Token, Parser, ToCss, TokenSerializationType, Rewriter};
+use super::{parse_stylesheet, serialize_identifier};
+
+
+/// A declaration, as given to and returned by the declaration steps of a `Transform`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct DeclarationTokens<'i> {
+    /// The property or descriptor name.
+    pub name: Cow<'i, str>,
+
+    /// The tokens of the value, without leading and trailing whitespace
+    /// and without `!important`.
+    ///
+    /// Functions and blocks are represented by their opening token,
+    /// the tokens of their content, and their closing token.
+    pub value: Vec<Token<'i>>,
+
+    /// Whether the declaration ends with `!important`.
+    pub important: bool,
+}
+
+
+/// A rule, as given to and returned by the rule steps of a `Transform`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct RuleTokens<'i> {
+    /// For an at-rule, its name without the `@` marker. `None` for a qualified rule.
+    pub at_keyword: Option<Cow<'i, str>>,
+
+    /// The tokens of the prelude, without leading and trailing whitespace.
+    /// For style rules, this is a selector list.
+    pub prelude: Vec<Token<'i>>,
+}
+
+
+/// A pipeline of user functions that transform the declarations and rules of a stylesheet.
+///
+/// Steps are called in the order they were added, each with the output of the previous one.
+/// A step returns the list of declarations (or rules) that replace the one it is given:
+/// an empty list removes it, and several items duplicate it,
+/// for example to add vendor-prefixed versions of a property.
+/// The block of a duplicated rule is repeated after each of the new preludes.
+///
+/// `Transform::run` takes care of parsing, of walking nested blocks,
+/// and of serializing the result.
+/// Only the declarations and rules that a step changed are re-serialized
+/// (without their comments): the rest of the input is copied verbatim.
+///
+/// ```{rust,ignore}
+/// let mut transform = Transform::new();
+/// transform.declaration_step(|declaration| {
+///     if declaration.name == "user-select" {
+///         let mut prefixed = declaration.clone();
+///         prefixed.name = "-webkit-user-select".into();
+///         vec![prefixed, declaration]
+///     } else {
+///         vec![declaration]
+///     }
+/// });
+/// let new_css = transform.run(css);
+/// ```
+pub struct Transform<'f> {
+    declaration_steps: Vec<Box<for<'i> FnMut(DeclarationTokens<'i>)
+                                             -> Vec<DeclarationTokens<'i>> + 'f>>,
+    rule_steps: Vec<Box<for<'i> FnMut(RuleTokens<'i>) -> Vec<RuleTokens<'i>> + 'f>>,
+}
+
+
+impl<'f> Transform<'f> {
+    /// Create a new `Transform` with no step, that does not change anything.
+    pub fn new() -> Transform<'f> {
+        Transform {
+            declaration_steps: Vec::new(),
+            rule_steps: Vec::new(),
+        }
+    }
+
+    /// Add a step that is called for each declaration, including in nested rules and at-rules.
+    pub fn declaration_step<F>(&mut self, step: F) -> &mut Transform<'f>
+    where F: for<'i> FnMut(DeclarationTokens<'i>) -> Vec<DeclarationTokens<'i>> + 'f {
+        self.declaration_steps.push(Box::new(step));
+        self
+    }
+
+    /// Add a step that is called for the prelude of each rule, including nested rules,
+    /// before its block is transformed.
+    pub fn rule_step<F>(&mut self, step: F) -> &mut Transform<'f>
+    where F: for<'i> FnMut(RuleTokens<'i>) -> Vec<RuleTokens<'i>> + 'f {
+        self.rule_steps.push(Box::new(step));
+        self
+    }
+
+    /// Parse the given stylesheet, and return it with all steps applied.
+    pub fn run(&mut self, css: &str) -> String {
+        let rules = parse_stylesheet(&mut Parser::new(css));
+        let mut rewriter = Rewriter::new(css);
+        for rule in &rules {
+            self.transform_rule(css, rule, &mut rewriter)
+        }
+        rewriter.finish()
+    }
+
+    fn transform_block(&mut self, input: &str, items: &[BlockItem], rewriter: &mut Rewriter) {
+        for item in items {
+            match *item {
+                BlockItem::Declaration(ref declaration) => {
+                    self.transform_declaration(input, declaration, rewriter)
+                }
+                BlockItem::Rule(ref rule) => self.transform_rule(input, rule, rewriter),
+            }
+        }
+    }
+
+    fn transform_declaration(&mut self, input: &str, declaration: &Declaration,
+                             rewriter: &mut Rewriter) {
+        let original = DeclarationTokens {
+            name: declaration.name.clone(),
+            value: nodes_to_tokens(&declaration.value),
+            important: declaration.important,
+        };
+        let mut declarations = vec![original.clone()];
+        for step in &mut self.declaration_steps {
+            declarations = declarations.into_iter().flat_map(|d| step(d)).collect();
+        }
+        if declarations.len() == 1 && declarations[0] == original {
+            return
+        }
+
+        let mut range = declaration.span.clone();
+        if declarations.is_empty() {
+            // Also remove the semicolon, if it directly follows.
+            let end = byte_index(range.end);
+            let after = &input[end..];
+            if after.trim_start().starts_with(';') {
+                range.end = position_from_byte_index(end + after.find(';').unwrap() + 1);
+            }
+        }
+        let mut replacement = String::new();
+        for (i, declaration) in declarations.iter().enumerate() {
+            if i > 0 {
+                replacement.push_str("; ")
+            }
+            serialize_declaration(declaration, &mut replacement)
+        }
+        // Edits are made in the order of the input, and never overlap.
+        rewriter.replace(range, replacement).unwrap()
+    }
+
+    fn transform_rule(&mut self, input: &str, rule: &Rule, rewriter: &mut Rewriter) {
+        let span = rule.span();
+        let (at_keyword, prelude, block) = match *rule {
+            Rule::QualifiedRule(ref rule) => (None, &rule.prelude, Some(&rule.block)),
+            Rule::AtRule(ref rule) => (Some(rule.name.clone()), &rule.prelude, rule.block.as_ref()),
+        };
+        let original = RuleTokens {
+            at_keyword: at_keyword,
+            prelude: nodes_to_tokens(prelude),
+        };
+        let mut rules = vec![original.clone()];
+        for step in &mut self.rule_steps {
+            rules = rules.into_iter().flat_map(|r| step(r)).collect();
+        }
+        if rules.len() == 1 && rules[0] == original {
+            if let Some(block) = block {
+                self.transform_block(input, block, rewriter)
+            }
+            return
+        }
+
+        // The rest of the rule after the prelude: its block (with its own steps applied)
+        // or its semicolon.
+        let prelude_end = match (prelude.last(), original.at_keyword.is_some()) {
+            (Some(node), _) => node.span.end,
+            (None, true) => position_from_byte_index(token_end(input, byte_index(span.start))),
+            (None, false) => span.start,
+        };
+        let mut block_rewriter = Rewriter::new(input);
+        if let Some(block) = block {
+            self.transform_block(input, block, &mut block_rewriter)
+        }
+        let rest = block_rewriter.finish_range(prelude_end..span.end);
+
+        let mut replacement = String::new();
+        for (i, rule) in rules.into_iter().enumerate() {
+            if i > 0 {
+                replacement.push('\n')
+            }
+            if let Some(name) = rule.at_keyword {
+                Token::AtKeyword(name).to_css(&mut replacement).unwrap();
+                if !rule.prelude.is_empty() {
+                    replacement.push(' ')
+                }
+            }
+            serialize_tokens(&rule.prelude, &mut replacement);
+            replacement.push_str(&rest);
+        }
+        rewriter.replace(span, replacement).unwrap()
+    }
+}
+
+
+/// Flatten a tree of component values back into a list of tokens.
+fn nodes_to_tokens<'i>(nodes: &[Node<'i>]) -> Vec<Token<'i>> {
+    fn push_tokens<'i>(nodes: &[Node<'i>], tokens: &mut Vec<Token<'i>>) {
+        for node in nodes {
+            let (content, closing) = match node.value {
+                ComponentValue::PreservedToken(ref token) => {
+                    tokens.push(token.clone());
+                    continue
+                }
+                ComponentValue::Function(ref name, ref arguments) => {
+                    tokens.push(Token::Function(name.clone()));
+                    (arguments, Token::CloseParenthesis)
+                }
+                ComponentValue::ParenthesisBlock(ref content) => {
+                    tokens.push(Token::ParenthesisBlock);
+                    (content, Token::CloseParenthesis)
+                }
+                ComponentValue::SquareBracketBlock(ref content) => {
+                    tokens.push(Token::SquareBracketBlock);
+                    (content, Token::CloseSquareBracket)
+                }
+                ComponentValue::CurlyBracketBlock(ref content) => {
+                    tokens.push(Token::CurlyBracketBlock);
+                    (content, Token::CloseCurlyBracket)
+                }
+            };
+            push_tokens(content, tokens);
+            tokens.push(closing);
+        }
+    }
+    let mut tokens = Vec::new();
+    push_tokens(nodes, &mut tokens);
+    tokens
+}
+
+
+/// Write tokens, with an empty comment between those that would otherwise be parsed differently.
+fn serialize_tokens(tokens: &[Token], dest: &mut String) {
+    let mut previous = TokenSerializationType::nothing();
+    for token in tokens {
+        let token_type = token.serialization_type();
+        if previous.needs_separator_when_before(token_type) {
+            dest.push_str("/**/")
+        }
+        previous = token_type;
+        token.to_css(dest).unwrap()
+    }
+}
+
+
+fn serialize_declaration(declaration: &DeclarationTokens, dest: &mut String) {
+    serialize_identifier(&declaration.name, dest).unwrap();
+    dest.push_str(": ");
+    serialize_tokens(&declaration.value, dest);
+    if declaration.important {
+        dest.push_str(" !important")
+    }
+}
@@ -0,0 +1,73 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+// https://drafts.csswg.org/css-fonts/#unicode-range-desc
+
+use std::fmt;
+
+use super::{Token, Parser, ParseError, ToCss};
+use parser::unexpected_token;
+
+
+/// The largest code point, U+10FFFF.
+const MAX_CODE_POINT: u32 = 0x10FFFF;
+
+
+/// A `<urange>` value: an inclusive range of code points, such as `U+0-7F` or `U+4??`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct UnicodeRange {
+    /// The first code point of the range.
+    pub start: u32,
+
+    /// The last code point of the range, inclusive.
+    pub end: u32,
+}
+
+
+impl UnicodeRange {
+    /// Parse a unicode range token. Wildcards are expanded: `U+4??` is `0x400` to `0x4FF`.
+    ///
+    /// Return an error if the start is greater than the end,
+    /// or if the end is greater than U+10FFFF.
+    pub fn parse<'i, 't>(input: &mut Parser<'i, 't>) -> Result<UnicodeRange, ParseError<'i>> {
+        let position = input.position();
+        match try!(input.next()) {
+            Token::UnicodeRange(start, end) if start <= end && end <= MAX_CODE_POINT => {
+                Ok(UnicodeRange { start: start, end: end })
+            }
+            token => Err(unexpected_token(token, position))
+        }
+    }
+
+    /// Whether the given character is in this range.
+    #[inline]
+    pub fn contains(self, c: char) -> bool {
+        self.start <= c as u32 && c as u32 <= self.end
+    }
+}
+
+
+/// Serialize with wildcards where possible: `U+400-4FF` is written `U+4??`.
+impl ToCss for UnicodeRange {
+    fn to_css<W>(&self, dest: &mut W) -> fmt::Result where W: fmt::Write {
+        Token::UnicodeRange(self.start, self.end).to_css(dest)
+    }
+}
+
+
+/// Parse a comma-separated list of unicode ranges,
+/// as in the value of the `unicode-range` descriptor of `@font-face`.
+///
+/// Return the inclusive `(start, end)` range of code points of each item, in order.
+/// Wildcards are expanded: `U+4??` is `(0x400, 0x4FF)`.
+///
+/// Return an error if an item is not a single unicode range token,
+/// if its start is greater than its end, or if its end is greater than U+10FFFF.
+/// The caller needs to check if the parser is exhausted.
+pub fn parse_unicode_range_list<'i, 't>(input: &mut Parser<'i, 't>)
+                                        -> Result<Vec<(u32, u32)>, ParseError<'i>> {
+    input.parse_comma_separated(|input| {
+        UnicodeRange::parse(input).map(|range| (range.start, range.end))
+    })
+}
@@ -0,0 +1,231 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+// https://drafts.csswg.org/css-values/#dimensions
+
+use std::f32::consts::PI;
+use std::fmt;
+
+use super::{Token, Parser, ParseError, ToCss};
+use parser::unexpected_token;
+
+
+/// The category of a `Unit`: what kind of quantity it measures.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum UnitKind {
+    /// `<length>`
+    Length,
+
+    /// `<angle>`
+    Angle,
+
+    /// `<time>`
+    Time,
+
+    /// `<frequency>`
+    Frequency,
+
+    /// `<resolution>`
+    Resolution,
+
+    /// `<flex>`, as in CSS Grid.
+    Flex,
+}
+
+
+macro_rules! units {
+    ( $( $variant: ident = $name: expr, $kind: ident; )+ ) => {
+        /// A unit defined in CSS Values and Units or another spec,
+        /// such as the unit of a `Dimension` token.
+        ///
+        /// Variants are named after the unit they represent.
+        #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+        #[allow(missing_docs)]
+        pub enum Unit {
+            $( $variant, )+
+        }
+
+        impl Unit {
+            /// Look up a unit by name, ASCII case-insensitively.
+            ///
+            /// Typical usage is with the unit of a `Dimension` token.
+            /// Return `Err(())` for unknown units.
+            pub fn from_name(name: &str) -> Result<Unit, ()> {
+                ascii_case_insensitive_phf_map! {
+                    unit -> Unit = { $( $name => Unit::$variant, )+ }
+                }
+                unit(name).cloned().ok_or(())
+            }
+
+            /// The canonical name of this unit, as it should be serialized.
+            pub fn name(self) -> &'static str {
+                match self {
+                    $( Unit::$variant => $name, )+
+                }
+            }
+
+            /// What kind of quantity this unit measures.
+            pub fn kind(self) -> UnitKind {
+                match self {
+                    $( Unit::$variant => UnitKind::$kind, )+
+                }
+            }
+        }
+    }
+}
+
+
+units! {
+    // Font-relative lengths
+    Em = "em", Length;
+    Rem = "rem", Length;
+    Ex = "ex", Length;
+    Rex = "rex", Length;
+    Cap = "cap", Length;
+    Rcap = "rcap", Length;
+    Ch = "ch", Length;
+    Rch = "rch", Length;
+    Ic = "ic", Length;
+    Ric = "ric", Length;
+    Lh = "lh", Length;
+    Rlh = "rlh", Length;
+
+    // Viewport-percentage lengths
+    Vw = "vw", Length;
+    Vh = "vh", Length;
+    Vi = "vi", Length;
+    Vb = "vb", Length;
+    Vmin = "vmin", Length;
+    Vmax = "vmax", Length;
+    Svw = "svw", Length;
+    Svh = "svh", Length;
+    Svi = "svi", Length;
+    Svb = "svb", Length;
+    Svmin = "svmin", Length;
+    Svmax = "svmax", Length;
+    Lvw = "lvw", Length;
+    Lvh = "lvh", Length;
+    Lvi = "lvi", Length;
+    Lvb = "lvb", Length;
+    Lvmin = "lvmin", Length;
+    Lvmax = "lvmax", Length;
+    Dvw = "dvw", Length;
+    Dvh = "dvh", Length;
+    Dvi = "dvi", Length;
+    Dvb = "dvb", Length;
+    Dvmin = "dvmin", Length;
+    Dvmax = "dvmax", Length;
+
+    // Container query lengths
+    Cqw = "cqw", Length;
+    Cqh = "cqh", Length;
+    Cqi = "cqi", Length;
+    Cqb = "cqb", Length;
+    Cqmin = "cqmin", Length;
+    Cqmax = "cqmax", Length;
+
+    // Absolute lengths
+    Px = "px", Length;
+    Cm = "cm", Length;
+    Mm = "mm", Length;
+    Q = "Q", Length;
+    In = "in", Length;
+    Pt = "pt", Length;
+    Pc = "pc", Length;
+
+    Deg = "deg", Angle;
+    Grad = "grad", Angle;
+    Rad = "rad", Angle;
+    Turn = "turn", Angle;
+
+    S = "s", Time;
+    Ms = "ms", Time;
+
+    Hz = "Hz", Frequency;
+    KHz = "kHz", Frequency;
+
+    Dpi = "dpi", Resolution;
+    Dpcm = "dpcm", Resolution;
+    Dppx = "dppx", Resolution;
+    X = "x", Resolution;
+
+    Fr = "fr", Flex;
+}
+
+
+impl UnitKind {
+    /// Return the kind of the given unit name, if it is a known unit.
+    ///
+    /// This is a shortcut for `Unit::from_name(name).map(Unit::kind)`.
+    pub fn of(name: &str) -> Result<UnitKind, ()> {
+        Unit::from_name(name).map(Unit::kind)
+    }
+}
+
+
+/// The number of canonical units in one of the given unit.
+/// Only valid for units of the kinds with a type below.
+fn canonical_factor(unit: Unit) -> f32 {
+    match unit {
+        Unit::Deg => 1.,
+        Unit::Grad => 0.9,
+        Unit::Rad => 180. / PI,
+        Unit::Turn => 360.,
+        Unit::S => 1.,
+        Unit::Ms => 0.001,
+        Unit::Hz => 1.,
+        Unit::KHz => 1000.,
+        Unit::Dppx | Unit::X => 1.,
+        Unit::Dpi => 1. / 96.,
+        Unit::Dpcm => 2.54 / 96.,
+        _ => unreachable!()
+    }
+}
+
+
+macro_rules! canonical_dimension {
+    ($name: ident, $kind: ident, $doc: expr, $canonical_unit: expr) => {
+        #[doc = $doc]
+        #[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
+        pub struct $name(pub f32);
+
+        impl $name {
+            /// Parse a `Dimension` token whose unit is of the right kind,
+            /// and convert it to the canonical unit.
+            ///
+            /// Unitless zero is not accepted. Math functions are not supported.
+            pub fn parse<'i, 't>(input: &mut Parser<'i, 't>) -> Result<$name, ParseError<'i>> {
+                let start = input.position();
+                let token = try!(input.next());
+                match token {
+                    Token::Dimension(value, ref unit) => $name::from_dimension(value.value, unit),
+                    _ => Err(())
+                }.map_err(|()| unexpected_token(token, start))
+            }
+
+            /// Convert a value in the given unit to the canonical unit.
+            ///
+            /// Return `Err(())` for an unknown unit or a unit of another kind.
+            pub fn from_dimension(value: f32, unit: &str) -> Result<$name, ()> {
+                let unit = try!(Unit::from_name(unit));
+                if unit.kind() != UnitKind::$kind {
+                    return Err(())
+                }
+                Ok($name(value * canonical_factor(unit)))
+            }
+        }
+
+        impl ToCss for $name {
+            fn to_css<W>(&self, dest: &mut W) -> fmt::Result where W: fmt::Write {
+                try!(self.0.to_css(dest));
+                dest.write_str($canonical_unit)
+            }
+        }
+    }
+}
+
+canonical_dimension!(Angle, Angle, "An `<angle>` value, in degrees.", "deg");
+canonical_dimension!(Time, Time, "A `<time>` value, in seconds.", "s");
+canonical_dimension!(Frequency, Frequency, "A `<frequency>` value, in hertz.", "Hz");
+canonical_dimension!(Resolution, Resolution, "A `<resolution>` value, in dots per `px` unit.", "dppx");
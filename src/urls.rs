@@ -0,0 +1,189 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::ascii::AsciiExt;
+use std::borrow::Cow;
+
+use ast::{ComponentValue, Node, Rule, AtRule, QualifiedRule, Declaration};
+use visit::{Visit, Walk};
+use super::{Token, Parser, SourceRange, Rewriter, parse_stylesheet, serialize_string};
+
+
+/// Where a URL was found by `find_urls`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum UrlContext<'i> {
+    /// The target of an `@import` rule, either a `url()` or a string.
+    Import,
+
+    /// The prelude of another at-rule, with the given name (without the `@` marker).
+    AtRulePrelude(Cow<'i, str>),
+
+    /// The value of a declaration, with the given property or descriptor name.
+    Declaration(Cow<'i, str>),
+}
+
+
+/// A URL found by `find_urls`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct UrlReference<'i> {
+    /// The URL, with escapes decoded. It is not resolved against any base URL.
+    pub url: Cow<'i, str>,
+
+    /// The span of the `url(…)` or `src(…)` function, or of the string token
+    /// for strings in `image-set()` and `@import` rules.
+    pub span: SourceRange,
+
+    /// Where the URL is.
+    pub context: UrlContext<'i>,
+
+    /// Whether the URL is one of the images of an `image-set()` function
+    /// (or `-webkit-image-set()`).
+    pub in_image_set: bool,
+}
+
+
+/// Find the URLs of subresources in the given rules, in document order.
+///
+/// This includes every `url()` and `src()` function (quoted or not),
+/// strings in the arguments of `image-set()`,
+/// and the target of `@import` rules when it is a string.
+/// URLs in the preludes of qualified rules and of `@namespace` rules are ignored,
+/// since they do not refer to a resource.
+pub fn find_urls<'i>(rules: &[Rule<'i>]) -> Vec<UrlReference<'i>> {
+    let mut finder = UrlFinder {
+        urls: Vec::new(),
+        contexts: Vec::new(),
+        functions: Vec::new(),
+    };
+    rules.walk(&mut finder);
+    finder.urls
+}
+
+
+/// Parse the given stylesheet, and return it with URLs replaced.
+///
+/// `replace` is called for each URL found by `find_urls`,
+/// and returns the new URL or `None` to keep the existing one.
+/// New URLs are written as quoted strings, in a `url()` function unless the URL was a string.
+/// The rest of the input is copied verbatim.
+pub fn rewrite_urls<F>(css: &str, mut replace: F) -> String
+where F: FnMut(&UrlReference) -> Option<String> {
+    let rules = parse_stylesheet(&mut Parser::new(css));
+    let input = Parser::new(css);
+    let mut rewriter = Rewriter::new(css);
+    for reference in find_urls(&rules) {
+        if let Some(new_url) = replace(&reference) {
+            let is_string = input.slice(reference.span.clone()).starts_with(|c| {
+                c == '"' || c == '\''
+            });
+            let mut replacement = String::new();
+            if !is_string {
+                replacement.push_str("url(")
+            }
+            serialize_string(&new_url, &mut replacement).unwrap();
+            if !is_string {
+                replacement.push_str(")")
+            }
+            // References do not overlap.
+            rewriter.replace(reference.span, replacement).unwrap()
+        }
+    }
+    rewriter.finish()
+}
+
+
+struct UrlFinder<'i> {
+    urls: Vec<UrlReference<'i>>,
+    /// `None` where URLs are ignored.
+    contexts: Vec<Option<UrlContext<'i>>>,
+    /// The names of the functions that we are in, in ASCII lower case.
+    functions: Vec<String>,
+}
+
+
+impl<'i> UrlFinder<'i> {
+    fn push(&mut self, url: Cow<'i, str>, node: &Node<'i>) {
+        if let Some(&Some(ref context)) = self.contexts.last() {
+            let in_image_set = self.functions.iter().any(|name| {
+                name == "image-set" || name == "-webkit-image-set"
+            });
+            self.urls.push(UrlReference {
+                url: url,
+                span: node.span.clone(),
+                context: context.clone(),
+                in_image_set: in_image_set,
+            })
+        }
+    }
+}
+
+
+impl<'i> Visit<'i> for UrlFinder<'i> {
+    fn enter_qualified_rule(&mut self, _rule: &QualifiedRule<'i>) {
+        self.contexts.push(None)
+    }
+
+    fn exit_qualified_rule(&mut self, _rule: &QualifiedRule<'i>) {
+        self.contexts.pop();
+    }
+
+    fn enter_at_rule(&mut self, rule: &AtRule<'i>) {
+        let context = match_ignore_ascii_case! { rule.name,
+            "import" => Some(UrlContext::Import),
+            "namespace" => None,
+            _ => Some(UrlContext::AtRulePrelude(rule.name.clone()))
+        };
+        self.contexts.push(context)
+    }
+
+    fn exit_at_rule(&mut self, _rule: &AtRule<'i>) {
+        self.contexts.pop();
+    }
+
+    fn enter_declaration(&mut self, declaration: &Declaration<'i>) {
+        self.contexts.push(Some(UrlContext::Declaration(declaration.name.clone())))
+    }
+
+    fn exit_declaration(&mut self, _declaration: &Declaration<'i>) {
+        self.contexts.pop();
+    }
+
+    fn enter_function(&mut self, name: &str, node: &Node<'i>) {
+        let name = name.to_ascii_lowercase();
+        if name == "url" || name == "src" {
+            let argument = if let ComponentValue::Function(_, ref arguments) = node.value {
+                arguments.iter().filter_map(|argument| match argument.value {
+                    ComponentValue::PreservedToken(Token::QuotedString(ref url)) => Some(url),
+                    _ => None,
+                }).next().cloned()
+            } else {
+                None
+            };
+            if let Some(url) = argument {
+                self.push(url, node)
+            }
+        }
+        self.functions.push(name)
+    }
+
+    fn exit_function(&mut self, _name: &str, _node: &Node<'i>) {
+        self.functions.pop();
+    }
+
+    fn visit_token(&mut self, token: &Token<'i>, node: &Node<'i>) {
+        match *token {
+            Token::UnquotedUrl(ref url) => self.push(url.clone(), node),
+            Token::QuotedString(ref url) => {
+                let is_url = match self.functions.last() {
+                    Some(name) => name == "image-set" || name == "-webkit-image-set",
+                    None => self.contexts.last() == Some(&Some(UrlContext::Import)),
+                };
+                if is_url {
+                    self.push(url.clone(), node)
+                }
+            }
+            _ => {}
+        }
+    }
+}
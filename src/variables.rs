@@ -0,0 +1,241 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+// https://drafts.csswg.org/css-variables/#substitute-a-var
+
+use std::ascii::AsciiExt;
+use std::borrow::Cow;
+
+use ast::{ComponentValue, Node, is_whitespace, trim_whitespace, strip_important};
+use ast::parse_component_value_list;
+use super::{Token, Parser, SourceRange};
+
+
+/// A declaration value that is kept as component values until `var()` substitution:
+/// the value of a custom property, or a value that contains `var()` functions.
+#[derive(PartialEq, Debug, Clone)]
+pub struct UnparsedValue<'i> {
+    /// The component values, without leading and trailing whitespace
+    /// and without `!important`.
+    pub nodes: Vec<Node<'i>>,
+
+    /// The `var()` functions in `nodes`, as found by `find_var_references`.
+    pub references: Vec<VarReference<'i>>,
+
+    /// Whether the value ended with `!important`.
+    pub important: bool,
+}
+
+
+impl<'i> UnparsedValue<'i> {
+    /// Parse the rest of the input, such as in `DeclarationParser::parse_value`.
+    pub fn parse<'t>(input: &mut Parser<'i, 't>) -> UnparsedValue<'i> {
+        let mut nodes = parse_component_value_list(input);
+        trim_whitespace(&mut nodes);
+        let important = strip_important(&mut nodes);
+        UnparsedValue {
+            references: find_var_references(&nodes),
+            nodes: nodes,
+            important: important,
+        }
+    }
+
+    /// Return the value with `var()` functions substituted.
+    /// See `substitute_var_functions`.
+    pub fn substitute<'a, F>(&self, lookup: F) -> Result<Vec<Node<'i>>, ()>
+    where F: FnMut(&str) -> Option<&'a [Node<'i>]>, 'i: 'a {
+        if self.references.is_empty() {
+            return Ok(self.nodes.clone())
+        }
+        substitute_var_functions(&self.nodes, lookup)
+    }
+}
+
+
+/// A `var()` function found by `find_var_references`.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct VarReference<'i> {
+    /// The name of the custom property, including the `--` prefix.
+    pub name: Cow<'i, str>,
+
+    /// The span of the `var()` function.
+    pub span: SourceRange,
+
+    /// Whether the function has a fallback value, which may be empty.
+    pub has_fallback: bool,
+}
+
+
+/// Find the `var()` functions in a tree of component values, in document order.
+///
+/// This includes `var()` functions nested in other functions, in blocks,
+/// and in the fallback value of another `var()` function.
+/// Malformed `var()` functions are ignored.
+pub fn find_var_references<'i>(value: &[Node<'i>]) -> Vec<VarReference<'i>> {
+    let mut references = Vec::new();
+    find_references(value, &mut references);
+    references
+}
+
+
+fn find_references<'i>(nodes: &[Node<'i>], references: &mut Vec<VarReference<'i>>) {
+    for node in nodes {
+        match node.value {
+            ComponentValue::Function(ref name, ref arguments) if name.eq_ignore_ascii_case("var") => {
+                if let Ok((name, fallback)) = parse_var_arguments(arguments) {
+                    references.push(VarReference {
+                        name: name.clone(),
+                        span: node.span.clone(),
+                        has_fallback: fallback.is_some(),
+                    });
+                    find_references(fallback.unwrap_or(&[]), references)
+                }
+            }
+            ComponentValue::PreservedToken(_) => {}
+            ComponentValue::Function(_, ref content) |
+            ComponentValue::ParenthesisBlock(ref content) |
+            ComponentValue::SquareBracketBlock(ref content) |
+            ComponentValue::CurlyBracketBlock(ref content) => find_references(content, references),
+        }
+    }
+}
+
+
+/// Replace `var()` functions in a tree of component values
+/// with the values of the custom properties they reference.
+///
+/// `lookup` is called with a custom property name (including the `--` prefix)
+/// and returns its value (typically parsed with `parse_component_value_list`),
+/// or `None` if the property is not defined.
+/// Values returned by `lookup` can themselves contain `var()` functions,
+/// which are substituted recursively.
+///
+/// When a custom property is not defined, is part of a dependency cycle,
+/// or its value is invalid, the fallback value of the `var()` function is used.
+/// Return `Err(())` if there is no fallback value in that case,
+/// or if a `var()` function is malformed.
+/// The value is then invalid at computed-value time.
+///
+/// Substituted nodes keep the span they had in the value they came from.
+pub fn substitute_var_functions<'i, 'a, F>(value: &[Node<'i>], mut lookup: F)
+                                           -> Result<Vec<Node<'i>>, ()>
+where F: FnMut(&str) -> Option<&'a [Node<'i>]>, 'i: 'a {
+    let mut result = Vec::new();
+    match substitute(value, &mut lookup, &mut Vec::new(), &mut result) {
+        Ok(()) => Ok(result),
+        Err(_) => Err(()),
+    }
+}
+
+
+enum Invalid<'i> {
+    /// Invalid syntax or a missing value.
+    Value,
+
+    /// A dependency cycle that starts with the custom property of the given name.
+    Cycle(Cow<'i, str>),
+}
+
+
+/// `stack` is the names of the custom properties being substituted,
+/// from the outermost to the innermost.
+fn substitute<'i, 'a, F>(nodes: &[Node<'i>], lookup: &mut F, stack: &mut Vec<Cow<'i, str>>,
+                         result: &mut Vec<Node<'i>>) -> Result<(), Invalid<'i>>
+where F: FnMut(&str) -> Option<&'a [Node<'i>]>, 'i: 'a {
+    for node in nodes {
+        let value = match node.value {
+            ComponentValue::Function(ref name, ref arguments) if name.eq_ignore_ascii_case("var") => {
+                try!(substitute_var(arguments, lookup, stack, result));
+                continue
+            }
+            ComponentValue::PreservedToken(ref token) => {
+                ComponentValue::PreservedToken(token.clone())
+            }
+            ComponentValue::Function(ref name, ref arguments) => {
+                let arguments = try!(substitute_nested(arguments, lookup, stack));
+                ComponentValue::Function(name.clone(), arguments)
+            }
+            ComponentValue::ParenthesisBlock(ref content) => {
+                ComponentValue::ParenthesisBlock(try!(substitute_nested(content, lookup, stack)))
+            }
+            ComponentValue::SquareBracketBlock(ref content) => {
+                ComponentValue::SquareBracketBlock(try!(substitute_nested(content, lookup, stack)))
+            }
+            ComponentValue::CurlyBracketBlock(ref content) => {
+                ComponentValue::CurlyBracketBlock(try!(substitute_nested(content, lookup, stack)))
+            }
+        };
+        result.push(Node {
+            value: value,
+            span: node.span.clone(),
+        })
+    }
+    Ok(())
+}
+
+
+fn substitute_nested<'i, 'a, F>(nodes: &[Node<'i>], lookup: &mut F, stack: &mut Vec<Cow<'i, str>>)
+                                -> Result<Vec<Node<'i>>, Invalid<'i>>
+where F: FnMut(&str) -> Option<&'a [Node<'i>]>, 'i: 'a {
+    let mut result = Vec::new();
+    try!(substitute(nodes, lookup, stack, &mut result));
+    Ok(result)
+}
+
+
+fn substitute_var<'i, 'a, F>(arguments: &[Node<'i>], lookup: &mut F,
+                             stack: &mut Vec<Cow<'i, str>>, result: &mut Vec<Node<'i>>)
+                             -> Result<(), Invalid<'i>>
+where F: FnMut(&str) -> Option<&'a [Node<'i>]>, 'i: 'a {
+    let (name, fallback) = try!(parse_var_arguments(arguments).map_err(|()| Invalid::Value));
+    if stack.contains(name) {
+        return Err(Invalid::Cycle(name.clone()))
+    }
+    if let Some(value) = lookup(name) {
+        stack.push(name.clone());
+        let mut substituted = Vec::new();
+        let outcome = substitute(value, lookup, stack, &mut substituted);
+        stack.pop();
+        match outcome {
+            Ok(()) => {
+                result.extend(substituted);
+                return Ok(())
+            }
+            // This custom property is part of the cycle, so the one containing this `var()` is too.
+            Err(Invalid::Cycle(ref start)) if start != name => return outcome,
+            // The custom property is invalid at computed-value time: use the fallback.
+            Err(_) => {}
+        }
+    }
+    match fallback {
+        Some(fallback) => substitute(fallback, lookup, stack, result),
+        None => Err(Invalid::Value),
+    }
+}
+
+
+/// Return the custom property name and the fallback value, if any.
+fn parse_var_arguments<'a, 'i>(arguments: &'a [Node<'i>])
+                               -> Result<(&'a Cow<'i, str>, Option<&'a [Node<'i>]>), ()> {
+    let mut non_whitespace = arguments.iter().enumerate().filter(|&(_, node)| !is_whitespace(node));
+    let name = match non_whitespace.next() {
+        Some((_, &Node { value: ComponentValue::PreservedToken(Token::Ident(ref name)), .. }))
+        if name.starts_with("--") => name,
+        _ => return Err(())
+    };
+    match non_whitespace.next() {
+        None => Ok((name, None)),
+        Some((index, &Node { value: ComponentValue::PreservedToken(Token::Comma), .. })) => {
+            let mut fallback = &arguments[index + 1..];
+            while fallback.first().map_or(false, is_whitespace) {
+                fallback = &fallback[1..]
+            }
+            while fallback.last().map_or(false, is_whitespace) {
+                fallback = &fallback[..fallback.len() - 1]
+            }
+            Ok((name, Some(fallback)))
+        }
+        Some(_) => Err(())
+    }
+}
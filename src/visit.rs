@@ -0,0 +1,179 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use super::Token;
+use ast::{ComponentValue, Node, Rule, BlockItem, QualifiedRule, AtRule, Declaration};
+
+
+/// Callbacks for walking a tree built by `parse_stylesheet` or `parse_component_value_list`.
+/// See the `Walk` trait.
+///
+/// All methods have default implementations that do nothing,
+/// so that implementations only need to override the ones they care about.
+/// For example, to find all `calc()` functions:
+///
+/// ```{rust,ignore}
+/// struct FindCalc(Vec<SourceRange>);
+///
+/// impl<'i> Visit<'i> for FindCalc {
+///     fn enter_function(&mut self, name: &str, node: &Node<'i>) {
+///         if name.eq_ignore_ascii_case("calc") {
+///             self.0.push(node.span.clone())
+///         }
+///     }
+/// }
+///
+/// let mut find_calc = FindCalc(Vec::new());
+/// parse_stylesheet(input).walk(&mut find_calc);
+/// ```
+pub trait Visit<'i> {
+    /// Called before walking the prelude and block of a qualified rule.
+    fn enter_qualified_rule(&mut self, rule: &QualifiedRule<'i>) {
+        let _ = rule;
+    }
+
+    /// Called after walking the prelude and block of a qualified rule.
+    fn exit_qualified_rule(&mut self, rule: &QualifiedRule<'i>) {
+        let _ = rule;
+    }
+
+    /// Called before walking the prelude and block (if any) of an at-rule.
+    fn enter_at_rule(&mut self, rule: &AtRule<'i>) {
+        let _ = rule;
+    }
+
+    /// Called after walking the prelude and block (if any) of an at-rule.
+    fn exit_at_rule(&mut self, rule: &AtRule<'i>) {
+        let _ = rule;
+    }
+
+    /// Called before walking the value of a declaration.
+    fn enter_declaration(&mut self, declaration: &Declaration<'i>) {
+        let _ = declaration;
+    }
+
+    /// Called after walking the value of a declaration.
+    fn exit_declaration(&mut self, declaration: &Declaration<'i>) {
+        let _ = declaration;
+    }
+
+    /// Called before walking the arguments of a function with the given name.
+    fn enter_function(&mut self, name: &str, node: &Node<'i>) {
+        let _ = name;
+        let _ = node;
+    }
+
+    /// Called after walking the arguments of a function with the given name.
+    fn exit_function(&mut self, name: &str, node: &Node<'i>) {
+        let _ = name;
+        let _ = node;
+    }
+
+    /// Called before walking the content of a `( … )`, `[ … ]`, or `{ … }` block
+    /// found in a component value list.
+    ///
+    /// This is not called for the blocks of rules, see `enter_qualified_rule` and `enter_at_rule`.
+    fn enter_block(&mut self, node: &Node<'i>) {
+        let _ = node;
+    }
+
+    /// Called after walking the content of a `( … )`, `[ … ]`, or `{ … }` block.
+    fn exit_block(&mut self, node: &Node<'i>) {
+        let _ = node;
+    }
+
+    /// Called for each preserved token.
+    fn visit_token(&mut self, token: &Token<'i>, node: &Node<'i>) {
+        let _ = token;
+        let _ = node;
+    }
+}
+
+
+/// Tree nodes that can be walked, calling `Visit` methods in document order.
+pub trait Walk<'i> {
+    /// Walk this node and its descendants.
+    fn walk<V>(&self, visitor: &mut V) where V: Visit<'i>;
+}
+
+
+impl<'i, T> Walk<'i> for [T] where T: Walk<'i> {
+    fn walk<V>(&self, visitor: &mut V) where V: Visit<'i> {
+        for item in self {
+            item.walk(visitor)
+        }
+    }
+}
+
+
+impl<'i> Walk<'i> for Node<'i> {
+    fn walk<V>(&self, visitor: &mut V) where V: Visit<'i> {
+        match self.value {
+            ComponentValue::PreservedToken(ref token) => visitor.visit_token(token, self),
+            ComponentValue::Function(ref name, ref arguments) => {
+                visitor.enter_function(name, self);
+                arguments.walk(visitor);
+                visitor.exit_function(name, self);
+            }
+            ComponentValue::ParenthesisBlock(ref content) |
+            ComponentValue::SquareBracketBlock(ref content) |
+            ComponentValue::CurlyBracketBlock(ref content) => {
+                visitor.enter_block(self);
+                content.walk(visitor);
+                visitor.exit_block(self);
+            }
+        }
+    }
+}
+
+
+impl<'i> Walk<'i> for Rule<'i> {
+    fn walk<V>(&self, visitor: &mut V) where V: Visit<'i> {
+        match *self {
+            Rule::QualifiedRule(ref rule) => rule.walk(visitor),
+            Rule::AtRule(ref rule) => rule.walk(visitor),
+        }
+    }
+}
+
+
+impl<'i> Walk<'i> for BlockItem<'i> {
+    fn walk<V>(&self, visitor: &mut V) where V: Visit<'i> {
+        match *self {
+            BlockItem::Declaration(ref declaration) => declaration.walk(visitor),
+            BlockItem::Rule(ref rule) => rule.walk(visitor),
+        }
+    }
+}
+
+
+impl<'i> Walk<'i> for QualifiedRule<'i> {
+    fn walk<V>(&self, visitor: &mut V) where V: Visit<'i> {
+        visitor.enter_qualified_rule(self);
+        self.prelude.walk(visitor);
+        self.block.walk(visitor);
+        visitor.exit_qualified_rule(self);
+    }
+}
+
+
+impl<'i> Walk<'i> for AtRule<'i> {
+    fn walk<V>(&self, visitor: &mut V) where V: Visit<'i> {
+        visitor.enter_at_rule(self);
+        self.prelude.walk(visitor);
+        if let Some(ref block) = self.block {
+            block.walk(visitor);
+        }
+        visitor.exit_at_rule(self);
+    }
+}
+
+
+impl<'i> Walk<'i> for Declaration<'i> {
+    fn walk<V>(&self, visitor: &mut V) where V: Visit<'i> {
+        visitor.enter_declaration(self);
+        self.value.walk(visitor);
+        visitor.exit_declaration(self);
+    }
+}
@@ -0,0 +1,226 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! JavaScript bindings for the tokenizer and for some one-shot parsers,
+//! enabled by the `wasm` Cargo feature.
+//!
+//! The functions and types of this module are exported with `wasm-bindgen`,
+//! with camelCase names: `tokenize`, `parseColor`, and `parseDeclarationList`.
+//! They can also be called from Rust, for example in tests.
+//!
+//! Offsets are counted in bytes of the UTF-8 input, not in UTF-16 code units.
+
+use wasm_bindgen::prelude::*;
+
+use ast::declaration_value_source;
+use tokenizer::Tokenizer;
+use super::{Token, Parser, ParseError, DeclarationParser, DeclarationListParser, AtRuleParser};
+#[cfg(feature = "color")] use super::Color;
+
+
+/// A token, as returned by `tokenize`.
+#[wasm_bindgen]
+#[derive(Clone, PartialEq, Debug)]
+pub struct JsToken {
+    kind: &'static str,
+    value: String,
+    number: Option<f32>,
+    start: usize,
+    end: usize,
+}
+
+
+#[wasm_bindgen]
+impl JsToken {
+    /// The kind of token, named like the variants of `Token`: `"Ident"`, `"Number"`, etc.
+    #[wasm_bindgen(getter, js_name = type)]
+    pub fn kind(&self) -> String {
+        self.kind.to_owned()
+    }
+
+    /// The unescaped value of `Ident`, `AtKeyword`, `Hash`, `IDHash`, `QuotedString`,
+    /// `UnquotedUrl`, `WhiteSpace` and `Comment` tokens, the name of `Function` tokens,
+    /// the unit of `Dimension` tokens, or the character of `Delim` tokens.
+    /// Empty for other tokens.
+    #[wasm_bindgen(getter)]
+    pub fn value(&self) -> String {
+        self.value.clone()
+    }
+
+    /// The value of `Number` and `Dimension` tokens,
+    /// or the value of `Percentage` tokens divided by 100.
+    #[wasm_bindgen(getter)]
+    pub fn number(&self) -> Option<f32> {
+        self.number
+    }
+
+    /// Byte offset of the start of the token.
+    #[wasm_bindgen(getter)]
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Byte offset of the end of the token (exclusive).
+    #[wasm_bindgen(getter)]
+    pub fn end(&self) -> usize {
+        self.end
+    }
+}
+
+
+/// Tokenize the given input, including whitespace and comments.
+///
+/// Blocks are not matched: closing brackets are returned as they appear.
+#[wasm_bindgen]
+pub fn tokenize(input: &str) -> Vec<JsToken> {
+    let mut tokenizer = Tokenizer::new(input);
+    let mut tokens = Vec::new();
+    loop {
+        let start = tokenizer.position().byte_index();
+        let token = match tokenizer.next() {
+            Ok(token) => token,
+            Err(_) => return tokens,
+        };
+        let end = tokenizer.position().byte_index();
+        tokens.push(to_js_token(&token, start, end))
+    }
+}
+
+
+/// Parse the given input as a `<color>` value.
+///
+/// Return `[red, green, blue, alpha]` with channels in 0.0 ... 1.0,
+/// an empty array for `currentColor`,
+/// or `undefined` if the input is not a valid color or has other tokens after the color.
+#[cfg(feature = "color")]
+#[wasm_bindgen(js_name = parseColor)]
+pub fn parse_color(input: &str) -> Option<Vec<f32>> {
+    match Parser::new(input).parse_entirely(Color::parse) {
+        Ok(Color::CurrentColor) => Some(Vec::new()),
+        Ok(Color::RGBA(rgba)) => Some(vec![rgba.red, rgba.green, rgba.blue, rgba.alpha]),
+        Err(_) => None,
+    }
+}
+
+
+/// A declaration, as returned by `parseDeclarationList`.
+#[wasm_bindgen]
+#[derive(Clone, PartialEq, Debug)]
+pub struct JsDeclaration {
+    name: String,
+    value: String,
+    important: bool,
+}
+
+
+#[wasm_bindgen]
+impl JsDeclaration {
+    /// The property or descriptor name, unescaped.
+    #[wasm_bindgen(getter)]
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    /// The source text of the value,
+    /// without leading and trailing whitespace and without `!important`.
+    #[wasm_bindgen(getter)]
+    pub fn value(&self) -> String {
+        self.value.clone()
+    }
+
+    /// Whether the declaration ends with `!important`.
+    #[wasm_bindgen(getter)]
+    pub fn important(&self) -> bool {
+        self.important
+    }
+}
+
+
+/// Parse the given input as a declaration list,
+/// such as the content of a `style` attribute or of a `{ … }` block.
+///
+/// Invalid declarations and at-rules are skipped.
+#[wasm_bindgen(js_name = parseDeclarationList)]
+pub fn parse_declaration_list(input: &str) -> Vec<JsDeclaration> {
+    let mut parser = Parser::new(input);
+    DeclarationListParser::new(&mut parser, JsDeclarationParser).filter_map(|result| {
+        result.ok()
+    }).collect()
+}
+
+
+struct JsDeclarationParser;
+
+impl DeclarationParser for JsDeclarationParser {
+    type Declaration = JsDeclaration;
+
+    fn parse_value<'i, 't>(&mut self, _context: &mut (), name: &str, input: &mut Parser<'i, 't>)
+                           -> Result<JsDeclaration, ParseError<'i>> {
+        let (value, important) = declaration_value_source(input);
+        Ok(JsDeclaration {
+            name: name.to_owned(),
+            value: value.to_owned(),
+            important: important,
+        })
+    }
+}
+
+impl AtRuleParser for JsDeclarationParser {
+    type Prelude = ();
+    type AtRule = JsDeclaration;
+}
+
+
+fn to_js_token(token: &Token, start: usize, end: usize) -> JsToken {
+    let (kind, value, number): (_, &str, _) = match *token {
+        Token::Ident(ref value) => ("Ident", value, None),
+        Token::AtKeyword(ref value) => ("AtKeyword", value, None),
+        Token::Hash(ref value) => ("Hash", value, None),
+        Token::IDHash(ref value) => ("IDHash", value, None),
+        Token::QuotedString(ref value) => ("QuotedString", value, None),
+        Token::UnquotedUrl(ref value) => ("UnquotedUrl", value, None),
+        Token::Delim(c) => {
+            return JsToken {
+                kind: "Delim",
+                value: c.to_string(),
+                number: None,
+                start: start,
+                end: end,
+            }
+        }
+        Token::Number(value) => ("Number", "", Some(value.value)),
+        Token::Percentage(value) => ("Percentage", "", Some(value.unit_value)),
+        Token::Dimension(value, ref unit) => ("Dimension", unit, Some(value.value)),
+        Token::UnicodeRange(..) => ("UnicodeRange", "", None),
+        Token::WhiteSpace(ref value) => ("WhiteSpace", value, None),
+        Token::Comment(ref value) => ("Comment", value, None),
+        Token::Colon => ("Colon", "", None),
+        Token::Semicolon => ("Semicolon", "", None),
+        Token::Comma => ("Comma", "", None),
+        Token::IncludeMatch => ("IncludeMatch", "", None),
+        Token::DashMatch => ("DashMatch", "", None),
+        Token::PrefixMatch => ("PrefixMatch", "", None),
+        Token::SuffixMatch => ("SuffixMatch", "", None),
+        Token::SubstringMatch => ("SubstringMatch", "", None),
+        Token::Column => ("Column", "", None),
+        Token::CDO => ("CDO", "", None),
+        Token::CDC => ("CDC", "", None),
+        Token::Function(ref name) => ("Function", name, None),
+        Token::ParenthesisBlock => ("ParenthesisBlock", "", None),
+        Token::SquareBracketBlock => ("SquareBracketBlock", "", None),
+        Token::CurlyBracketBlock => ("CurlyBracketBlock", "", None),
+        Token::BadUrl => ("BadUrl", "", None),
+        Token::BadString => ("BadString", "", None),
+        Token::CloseParenthesis => ("CloseParenthesis", "", None),
+        Token::CloseSquareBracket => ("CloseSquareBracket", "", None),
+        Token::CloseCurlyBracket => ("CloseCurlyBracket", "", None),
+    };
+    JsToken {
+        kind: kind,
+        value: value.to_owned(),
+        number: number,
+        start: start,
+        end: end,
+    }
+}